@@ -1,11 +1,13 @@
 use axum::{
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
     Json,
-    extract::State,
+    extract::{DefaultBodyLimit, Path, Query, State},
     response::{IntoResponse, Response},
-    http::{Method, header, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    http::{HeaderMap, Method, header, StatusCode},
 };
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -15,22 +17,55 @@ use std::fmt;
 use tokio::fs;
 
 use crate::personality::PersonalityProfile;
-use crate::DeepSeekProvider;
-use crate::database::Database;
-use crate::completion::CompletionProvider;
+use crate::providers::registry::{ProviderRegistry, TASK_CHAT_LEARNING, TASK_CHAT_SUMMARY, TASK_MEMORY_REFLECTION};
+use crate::database::{Database, DatabaseError, ImportMode, Session};
+use crate::archive::{self, Archive, ArchiveError};
+use crate::knowledge_base::knowledge_base::{
+    format_citations, EntryUpdate, KnowledgeBaseError, KnowledgeBaseHandler, KnowledgeEntry, KnowledgeHit, KNOWLEDGE_LIMIT, MANUAL_SOURCE,
+};
+use crate::completion::{ChatMessage, CompletionError, CompletionProvider, StreamEvent};
+use crate::tokens;
+use crate::memory::LongTermMemory;
+use crate::learning::{format_facts, Learner, LearningConfig, LearningQueue};
+use crate::embeddings::{self, VectorStore};
+use crate::memory::long_term::{format_memories, RECALL_LIMIT};
+use crate::memory::reflection::{ReflectionConfig, Reflector};
+use crate::memory::summary::{Summarizer, SummaryConfig};
+use crate::usage::{BudgetLimits, Feature, UsageLedger};
 
 
 #[derive(Clone)]
 pub struct AppState {
-    deepseek: Arc<DeepSeekProvider>,
+    registry: Arc<ProviderRegistry>,
     personality: Arc<RwLock<PersonalityProfile>>,
     db: Arc<Database>,
+    usage: UsageLedger,
+    vectors: VectorStore,
+    summarizer: Summarizer,
+    reflector: Reflector,
+    knowledge_base: KnowledgeBaseHandler,
+    learning: LearningQueue,
+    /// Token the admin endpoints require; they are disabled without one.
+    admin_token: Option<String>,
 }
 
+/// User of requests that don't say who they are.
+const ANONYMOUS_USER: &str = "anonymous";
+
+/// Largest archive accepted by `/admin/import`.
+const ARCHIVE_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
 #[derive(Deserialize)]
 pub struct ChatRequest {
     message: String,
     character: Option<String>,
+    /// Who is chatting; memories and history are kept apart per user. The id
+    /// is taken on trust, so it is all that protects a user's sessions and
+    /// memories: clients must use unguessable ids (e.g. random UUIDs), never
+    /// names or emails.
+    user_id: Option<String>,
+    /// Conversation to continue; a new one is started when missing.
+    session_id: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -41,6 +76,10 @@ pub struct CharacterRequest {
 #[derive(Serialize)]
 pub struct ChatResponse {
     response: String,
+    session_id: String,
+    /// Knowledge base entries given to the model; the response cites them
+    /// by position as `[1]`, `[2]`, ...
+    citations: Vec<KnowledgeHit>,
     tokens: TokenInfo,
 }
 
@@ -51,6 +90,61 @@ pub struct TokenInfo {
     total: usize,
 }
 
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    days: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct SessionsQuery {
+    user_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    /// Character to export; everything when missing.
+    character: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    mode: Option<ImportMode>,
+}
+
+#[derive(Deserialize)]
+pub struct KnowledgeListQuery {
+    tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct KnowledgeSearchQuery {
+    q: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct NewKnowledgeEntry {
+    content: String,
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TagRequest {
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MarkdownImportRequest {
+    /// Folder on the server holding the Markdown files.
+    directory: String,
+}
+
 #[derive(Serialize)]
 pub struct CharacterResponse {
     status: String,
@@ -75,13 +169,21 @@ impl fmt::Display for ApiError {
 impl Error for ApiError {}
 
 pub async fn create_api(
-    deepseek: DeepSeekProvider,
+    registry: ProviderRegistry,
     personality: PersonalityProfile,
     db: Database,
+    knowledge_base: KnowledgeBaseHandler,
 ) -> Router {
     let state = AppState {
-        deepseek: Arc::new(deepseek),
+        registry: Arc::new(registry),
         personality: Arc::new(RwLock::new(personality)),
+        usage: UsageLedger::new(db.clone(), BudgetLimits::from_env()),
+        vectors: VectorStore::new(db.clone(), embeddings::from_env()),
+        summarizer: Summarizer::new(SummaryConfig::from_env()),
+        reflector: Reflector::new(ReflectionConfig::from_env()),
+        learning: LearningQueue::spawn(db.clone(), knowledge_base.clone(), LearningConfig::from_env()),
+        knowledge_base,
+        admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|token| !token.trim().is_empty()),
         db: Arc::new(db),
     };
 
@@ -98,109 +200,565 @@ pub async fn create_api(
 
     Router::new()
         .route("/chat", post(chat_handler))
+        .route("/chat/stream", post(chat_stream_handler))
         .route("/character", post(character_handler))
+        .route("/usage", get(usage_handler))
+        .route("/sessions", get(sessions_handler))
+        .route("/kb", get(knowledge_list_handler).post(knowledge_add_handler))
+        .route("/kb/search", get(knowledge_search_handler))
+        .route("/kb/import", post(knowledge_import_handler))
+        .route("/kb/:id", get(knowledge_show_handler).put(knowledge_edit_handler).delete(knowledge_delete_handler))
+        .route("/kb/:id/tags", put(knowledge_tag_handler))
+        .route("/admin/export", get(export_handler))
+        .route("/admin/import", post(import_handler).layer(DefaultBodyLimit::max(ARCHIVE_BODY_LIMIT)))
         .route("/health", get(health_check))
         .layer(cors)
         .with_state(state)
 }
 
+/// Maps a failed completion to the closest HTTP status for API clients.
+fn ai_error_status(error: &CompletionError) -> StatusCode {
+    match error {
+        CompletionError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        CompletionError::ContextTooLong(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        CompletionError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        CompletionError::BudgetExceeded(_) => StatusCode::PAYMENT_REQUIRED,
+        CompletionError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        CompletionError::AuthFailed(_)
+        | CompletionError::ServerError { .. }
+        | CompletionError::MalformedResponse(_) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 async fn chat_handler(
-    State(mut state): State<AppState>,
+    State(state): State<AppState>,
     Json(request): Json<ChatRequest>,
 ) -> Response {
-    let input_tokens = request.message.split_whitespace().count();
-    
-    // Get recent conversations from database
-    let recent_convos = match state.db.get_recent_conversations(5).await {
-        Ok(convos) => convos,
-        Err(e) => {
-            eprintln!("Database error: {}", e);
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse { status: "Database error".to_string() })
-            ).into_response();
-        }
-    };
-    
     // Get current personality and build context
     let personality = state.personality.read().await;
     println!("Generating response as character: {}", personality.name);
     
-    // Create new DeepSeek provider with current system prompt
-    let system_prompt = personality.generate_system_prompt();
-    let api_key = match std::env::var("DEEPSEEK_API_KEY") {
-        Ok(key) => key,
+    // Build the provider for the current personality's system prompt and backend
+    let provider = match state.registry.for_character(&personality) {
+        Ok(provider) => state.usage.meter(provider, Feature::Chat, personality.name.clone()),
         Err(e) => {
-            eprintln!("API key error: {}", e);
+            eprintln!("Failed to create provider: {}", e);
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse { status: "API key error".to_string() })
+                Json(ApiResponse { status: format!("Failed to create provider: {}", e) })
             ).into_response();
         }
     };
     
-    let new_provider = match DeepSeekProvider::new(api_key, system_prompt).await {
-        Ok(provider) => provider,
+    let session = match open_session(&state, &request, &personality.name).await {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+    let notes = state.knowledge_base.search(&request.message, KNOWLEDGE_LIMIT).await;
+    let messages = match conversation_messages(&state, &session, &personality, &provider, &request.message, &notes).await {
+        Ok(messages) => messages,
         Err(e) => {
-            eprintln!("Failed to create provider: {}", e);
+            eprintln!("Database error: {}", e);
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse { status: "Failed to create provider".to_string() })
+                Json(ApiResponse { status: "Database error".to_string() })
             ).into_response();
         }
     };
-    
-    state.deepseek = Arc::new(new_provider);
-    
-    let mut context = String::new();
-    for (_timestamp, user_msg, ai_msg, pers_name) in recent_convos {
-        if pers_name == personality.name {
-            context.push_str(&format!("User: {}\nAI: {}\n", user_msg, ai_msg));
-        }
-    }
-
-    // Create prompt with context
-    let prompt = if context.is_empty() {
-        request.message.clone()
-    } else {
-        format!("Previous conversation:\n{}\n\nCurrent message: {}", context, request.message)
-    };
 
-    // Get AI response using current personality's system prompt
-    let response = match state.deepseek.complete(&prompt).await {
-        Ok(resp) => resp,
+    // Get AI response using current personality's system prompt, trimmed to fit the model
+    let messages = tokens::fit_to_context(&provider, &messages);
+    let completion = match provider.complete_chat(&messages).await {
+        Ok(completion) => completion,
         Err(e) => {
             eprintln!("AI error: {}", e);
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                ai_error_status(&e),
                 Json(ApiResponse { status: format!("AI error: {}", e) })
             ).into_response();
         }
     };
 
-    let response_tokens = response.split_whitespace().count();
+    let response = completion.content;
     
-    // Save conversation to database with current personality
-    if let Err(e) = state.db.save_conversation(
-        request.message.clone(),
-        response.clone(),
-        personality.name.clone(),
-    ).await {
+    // Save the exchange to the session
+    if let Err(e) = state.db.save_conversation(&session, request.message.clone(), response.clone()).await {
         eprintln!("Warning: Failed to save conversation to database: {}", e);
     }
+    remember_turn(&state, &session, &personality, &request.message).await;
+    learner(&state, &session, &personality).observe(&request.message, &response);
 
     Json(ChatResponse {
         response,
+        session_id: session.id,
+        citations: notes,
         tokens: TokenInfo {
-            input: input_tokens,
-            response: response_tokens,
-            total: input_tokens + response_tokens,
+            input: completion.usage.prompt_tokens,
+            response: completion.usage.completion_tokens,
+            total: completion.usage.total_tokens(),
         },
     }).into_response()
 }
 
+/// The session a chat request continues, or a new one. Callers that don't
+/// say who they are all count as one anonymous user, and share its sessions.
+/// See `ChatRequest::user_id` for why ids must be unguessable.
+async fn open_session(state: &AppState, request: &ChatRequest, character: &str) -> Result<Session, Response> {
+    let user = request.user_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|user| !user.is_empty())
+        .unwrap_or(ANONYMOUS_USER);
+    match state.db.open_session(request.session_id.clone(), user.to_string(), character.to_string()).await {
+        Ok(Some(session)) => Ok(session),
+        Ok(None) => Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse { status: "Session belongs to another user or character".to_string() })
+        ).into_response()),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse { status: "Database error".to_string() })
+            ).into_response())
+        }
+    }
+}
+
+/// Learns from the session user's exchanges with its character, extracting
+/// facts with the `chat.learning` route, or offline when its provider cannot
+/// be built.
+fn learner(state: &AppState, session: &Session, personality: &PersonalityProfile) -> Learner {
+    let provider = state.registry
+        .for_command(TASK_CHAT_LEARNING, personality, personality.generate_system_prompt())
+        .map(|provider| Arc::new(state.usage.meter(provider, Feature::Chat, personality.name.clone())) as Arc<dyn CompletionProvider>)
+        .map_err(|e| eprintln!("Warning: Learning without a model: {}", e))
+        .ok();
+    state.learning.learner(&session.user_id, &session.character, provider)
+}
+
+/// Replays earlier turns of the session as real chat messages, oldest first,
+/// followed by the new user message. Older turns are condensed into a running
+/// summary once they outgrow the history budget; the summary, the user's
+/// long-term memories and learned facts relevant to the message and the
+/// knowledge base `notes` go into the system prompt.
+async fn conversation_messages(
+    state: &AppState,
+    session: &Session,
+    personality: &PersonalityProfile,
+    provider: &dyn CompletionProvider,
+    message: &str,
+    notes: &[KnowledgeHit],
+) -> Result<Vec<ChatMessage>, DatabaseError> {
+    let summary_provider = state.registry
+        .for_command(TASK_CHAT_SUMMARY, personality, personality.generate_system_prompt())
+        .map(|summarizer| state.usage.meter(summarizer, Feature::Chat, personality.name.clone()));
+    let summarize_with: &dyn CompletionProvider = match &summary_provider {
+        Ok(summarizer) => summarizer,
+        Err(_) => provider,
+    };
+    let history = state.summarizer
+        .load_history(&state.db, summarize_with, &session.id)
+        .await?;
+
+    let recalled = LongTermMemory::load((*state.db).clone(), &session.user_id, &session.character).await?
+        .with_vectors(state.vectors.clone())
+        .recall(message, RECALL_LIMIT)
+        .await;
+
+    let mut system = provider.system_message().to_string();
+    if !recalled.is_empty() {
+        system.push_str(&format!("\n\nThings you remember:\n{}", format_memories(&recalled)));
+    }
+    let learned = learner(state, session, personality).relevant_context(message).await;
+    if !learned.is_empty() {
+        system.push_str(&format!("\n\nThings you have learned:\n{}", format_facts(&learned)));
+    }
+    if !notes.is_empty() {
+        system.push_str(&format!("\n\n{}", format_citations(notes)));
+    }
+    if let Some(summary) = history.summary_section() {
+        system.push_str(&format!("\n\n{}", summary));
+    }
+
+    let mut messages = vec![ChatMessage::system(system)];
+    messages.extend(history.messages());
+    messages.push(ChatMessage::user(message));
+    Ok(messages)
+}
+
+/// Keeps the user's message as a long-term memory when it is worth
+/// remembering, and lets the character reflect once enough has piled up.
+async fn remember_turn(state: &AppState, session: &Session, personality: &PersonalityProfile, message: &str) {
+    let mut memory = match LongTermMemory::load((*state.db).clone(), &session.user_id, &session.character).await {
+        Ok(memory) => memory.with_vectors(state.vectors.clone()),
+        Err(e) => {
+            eprintln!("Warning: Failed to load memories: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = memory.observe("chat", message).await {
+        eprintln!("Warning: Failed to save memory: {}", e);
+    }
+
+    match state.registry.for_command(TASK_MEMORY_REFLECTION, personality, personality.generate_system_prompt()) {
+        Ok(provider) => {
+            let provider = state.usage.meter(provider, Feature::Chat, personality.name.clone());
+            if let Err(e) = state.reflector.reflect(&state.db, &provider, &mut memory, false).await {
+                eprintln!("Warning: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Warning: Failed to create reflection provider: {}", e),
+    }
+}
+
+/// Streams the response as server-sent events: a `session` event with the
+/// session id, one `message` event per token delta, a `usage` event with the
+/// token counts, then a final `done` event (or `error` if generation fails
+/// midway).
+async fn chat_stream_handler(
+    State(state): State<AppState>,
+    Json(request): Json<ChatRequest>,
+) -> Response {
+    let personality = state.personality.read().await.clone();
+    println!("Streaming response as character: {}", personality.name);
+
+    let provider = match state.registry.for_character(&personality) {
+        Ok(provider) => state.usage.meter(provider, Feature::Chat, personality.name.clone()),
+        Err(e) => {
+            eprintln!("Failed to create provider: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse { status: format!("Failed to create provider: {}", e) })
+            ).into_response();
+        }
+    };
+
+    let session = match open_session(&state, &request, &personality.name).await {
+        Ok(session) => session,
+        Err(response) => return response,
+    };
+    let notes = state.knowledge_base.search(&request.message, KNOWLEDGE_LIMIT).await;
+    let messages = match conversation_messages(&state, &session, &personality, &provider, &request.message, &notes).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse { status: "Database error".to_string() })
+            ).into_response();
+        }
+    };
+    let messages = tokens::fit_to_context(&provider, &messages);
+    let session_id = session.id.clone();
+    let deltas = match provider.complete_stream(&messages).await {
+        Ok(deltas) => deltas,
+        Err(e) => {
+            eprintln!("AI error: {}", e);
+            return (
+                ai_error_status(&e),
+                Json(ApiResponse { status: format!("AI error: {}", e) })
+            ).into_response();
+        }
+    };
+
+    let collected = Arc::new(tokio::sync::Mutex::new(Some(String::new())));
+    let events = {
+        let collected = collected.clone();
+        deltas.then(move |delta| {
+            let collected = collected.clone();
+            async move {
+                match delta {
+                    Ok(StreamEvent::Delta(text)) => {
+                        if let Some(response) = collected.lock().await.as_mut() {
+                            response.push_str(&text);
+                        }
+                        Event::default().data(text)
+                    }
+                    Ok(StreamEvent::Usage(usage)) => Event::default()
+                        .event("usage")
+                        .json_data(&usage)
+                        .unwrap_or_else(|_| Event::default().event("usage").data("")),
+                    Ok(StreamEvent::ToolCalls(calls)) => Event::default()
+                        .event("tool_calls")
+                        .json_data(&calls)
+                        .unwrap_or_else(|_| Event::default().event("tool_calls").data("")),
+                    Err(e) => {
+                        eprintln!("AI stream error: {}", e);
+                        // Don't save a partial response
+                        collected.lock().await.take();
+                        Event::default().event("error").data(e.to_string())
+                    }
+                }
+            }
+        })
+    };
+
+    // Save the conversation once the model has finished
+    let finish = stream::once(async move {
+        if let Some(response) = collected.lock().await.take() {
+            if let Err(e) = state.db.save_conversation(&session, request.message.clone(), response.clone()).await {
+                eprintln!("Warning: Failed to save conversation to database: {}", e);
+            }
+            remember_turn(&state, &session, &personality, &request.message).await;
+            learner(&state, &session, &personality).observe(&request.message, &response);
+        }
+        Event::default().event("done").data("")
+    });
+
+    let citations = Event::default()
+        .event("citations")
+        .json_data(&notes)
+        .unwrap_or_else(|_| Event::default().event("citations").data("[]"));
+    let started = stream::iter([Event::default().event("session").data(session_id), citations]);
+    Sse::new(started.chain(events).chain(finish).map(Ok::<_, std::convert::Infallible>))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Token usage and spend by day, character and feature over the last `days` days (default 7).
+async fn usage_handler(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> Response {
+    match state.usage.report(query.days.unwrap_or(7).max(1)).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse { status: "Database error".to_string() })
+            ).into_response()
+        }
+    }
+}
+
+/// Sessions of `user_id` (the anonymous user by default), most recently
+/// active first. Admin only: listing them would give away the session ids,
+/// and with them the history, of any user whose id is known.
+async fn sessions_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<SessionsQuery>,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    let user = query.user_id.unwrap_or_else(|| ANONYMOUS_USER.to_string());
+    match state.db.get_sessions(user).await {
+        Ok(sessions) => Json(sessions).into_response(),
+        Err(e) => {
+            eprintln!("Database error: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse { status: "Database error".to_string() })
+            ).into_response()
+        }
+    }
+}
+
+/// The response refusing the request, unless it carries the admin token as
+/// a bearer token.
+fn admin_rejection(state: &AppState, headers: &HeaderMap) -> Option<Response> {
+    let Some(token) = state.admin_token.as_deref() else {
+        return Some((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse { status: "Admin endpoints are disabled; set ADMIN_TOKEN to enable them".to_string() })
+        ).into_response());
+    };
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    (given != Some(token)).then(|| (
+        StatusCode::UNAUTHORIZED,
+        Json(ApiResponse { status: "Invalid admin token".to_string() })
+    ).into_response())
+}
+
+/// Archive of `character`, or of everything including the knowledge base.
+async fn export_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    match archive::export(&state.db, query.character.as_deref(), &state.knowledge_base).await {
+        Ok(archive) => Json(archive).into_response(),
+        Err(e) => {
+            eprintln!("Export error: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse { status: format!("Export failed: {}", e) })
+            ).into_response()
+        }
+    }
+}
+
+/// Merges an archive into what is stored (`mode=merge`, the default) or
+/// replaces the data it covers (`mode=replace`), reporting what was added
+/// and what conflicted.
+async fn import_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ImportQuery>,
+    Json(archive): Json<Archive>,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    let mode = query.mode.unwrap_or_default();
+    match archive::import(&state.db, Some(&state.vectors), &state.knowledge_base, archive, mode).await {
+        Ok(report) => Json(report).into_response(),
+        Err(ArchiveError::UnsupportedVersion(version)) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse { status: ArchiveError::UnsupportedVersion(version).to_string() })
+        ).into_response(),
+        Err(e) => {
+            eprintln!("Import error: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse { status: format!("Import failed: {}", e) })
+            ).into_response()
+        }
+    }
+}
+
+/// Knowledge base entries, only those with `tag` if given.
+async fn knowledge_list_handler(
+    State(state): State<AppState>,
+    Query(query): Query<KnowledgeListQuery>,
+) -> Response {
+    let tag = query.tag.map(|tag| tag.to_lowercase());
+    let entries: Vec<KnowledgeEntry> = state.knowledge_base.entries().await
+        .into_iter()
+        .filter(|entry| tag.as_ref().is_none_or(|tag| entry.tags.contains(tag)))
+        .collect();
+    Json(entries).into_response()
+}
+
+/// Entries ranked against `q`, best first.
+async fn knowledge_search_handler(
+    State(state): State<AppState>,
+    Query(query): Query<KnowledgeSearchQuery>,
+) -> Response {
+    Json(state.knowledge_base.search(&query.q, query.limit.unwrap_or(10)).await).into_response()
+}
+
+async fn knowledge_show_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.knowledge_base.get(&id).await {
+        Some(entry) => Json(entry).into_response(),
+        None => knowledge_not_found(),
+    }
+}
+
+async fn knowledge_add_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<NewKnowledgeEntry>,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    if request.content.trim().is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse { status: "An entry needs content".to_string() })
+        ).into_response();
+    }
+    let mut entry = KnowledgeEntry::new(MANUAL_SOURCE, request.keywords, &request.content);
+    entry.tags = request.tags;
+    match state.knowledge_base.add(entry).await {
+        Ok(entry) => (StatusCode::CREATED, Json(entry)).into_response(),
+        Err(e) => knowledge_error(e),
+    }
+}
+
+async fn knowledge_edit_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(update): Json<EntryUpdate>,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    match state.knowledge_base.update(&id, update).await {
+        Ok(Some(entry)) => Json(entry).into_response(),
+        Ok(None) => knowledge_not_found(),
+        Err(e) => knowledge_error(e),
+    }
+}
+
+async fn knowledge_tag_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(request): Json<TagRequest>,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    match state.knowledge_base.tag(&id, &request.add, &request.remove).await {
+        Ok(Some(entry)) => Json(entry).into_response(),
+        Ok(None) => knowledge_not_found(),
+        Err(e) => knowledge_error(e),
+    }
+}
+
+async fn knowledge_delete_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    match state.knowledge_base.delete(&id).await {
+        Ok(Some(entry)) => Json(entry).into_response(),
+        Ok(None) => knowledge_not_found(),
+        Err(e) => knowledge_error(e),
+    }
+}
+
+/// Adds the Markdown files in a folder on the server.
+async fn knowledge_import_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<MarkdownImportRequest>,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    match state.knowledge_base.import_markdown(&request.directory).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => knowledge_error(e),
+    }
+}
+
+fn knowledge_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ApiResponse { status: "Knowledge base entry not found".to_string() })
+    ).into_response()
+}
+
+fn knowledge_error(error: KnowledgeBaseError) -> Response {
+    eprintln!("Knowledge base error: {}", error);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiResponse { status: format!("Knowledge base error: {}", error) })
+    ).into_response()
+}
+
 async fn character_handler(
-    State(mut state): State<AppState>,
+    State(state): State<AppState>,
     Json(request): Json<CharacterRequest>
 ) -> Result<Json<ApiResponse>, (StatusCode, Json<ApiResponse>)> {
     println!("Changing character to: {}", request.character);
@@ -210,28 +768,16 @@ async fn character_handler(
         Ok(content) => {
             match serde_json::from_str::<PersonalityProfile>(&content) {
                 Ok(profile) => {
+                    // Make sure the character's provider can be built before switching
+                    if let Err(e) = state.registry.for_character(&profile) {
+                        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse {
+                            status: format!("Failed to create provider: {}", e)
+                        })));
+                    }
+
                     // Update the personality
                     *state.personality.write().await = profile;
                     
-                    // Create new DeepSeek provider with updated system prompt
-                    let system_prompt = state.personality.read().await.generate_system_prompt();
-                    let api_key = std::env::var("DEEPSEEK_API_KEY").map_err(|e| {
-                        (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse {
-                            status: format!("API key error: {}", e)
-                        }))
-                    })?;
-                    
-                    let new_provider = match DeepSeekProvider::new(api_key, system_prompt).await {
-                        Ok(provider) => provider,
-                        Err(e) => {
-                            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse {
-                                status: format!("Failed to create provider: {}", e)
-                            })));
-                        }
-                    };
-                    
-                    state.deepseek = Arc::new(new_provider);
-                    
                     Ok(Json(ApiResponse { 
                         status: "Character changed successfully".to_string() 
                     }))
@@ -0,0 +1,191 @@
+// src/archive/mod.rs
+use std::collections::HashMap;
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::fs;
+use crate::database::{Database, DatabaseError, ImportCounts, ImportMode, ImportReport, Snapshot};
+use crate::embeddings::store::{memory_namespace, VectorStore};
+use crate::files;
+use crate::knowledge_base::knowledge_base::{KnowledgeBaseError, KnowledgeBaseFile, KnowledgeBaseHandler, KnowledgeEntry};
+
+#[cfg(test)]
+mod tests;
+
+/// Version of the archive format written by `export`. Archives of a newer
+/// version are refused rather than half understood.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Archive file error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid archive: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    KnowledgeBase(#[from] KnowledgeBaseError),
+    #[error("Archive version {0} is not supported (newest known is {ARCHIVE_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+/// Conversations, memories and learned knowledge of one character or of all
+/// of them, as a single JSON document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// The character the archive holds, or `None` for everything.
+    pub character: Option<String>,
+    #[serde(flatten)]
+    pub snapshot: Snapshot,
+    /// The shared knowledge base file; only in archives of everything. Read
+    /// like the file itself, so older shapes are migrated on import.
+    #[serde(default)]
+    pub knowledge_base: Option<Value>,
+}
+
+/// What an import did, per kind of record.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ArchiveReport {
+    #[serde(flatten)]
+    pub database: ImportReport,
+    pub knowledge_base: ImportCounts,
+}
+
+impl ArchiveReport {
+    /// One line per kind of record, followed by the conflicts.
+    pub fn lines(&self) -> Vec<String> {
+        let db = &self.database;
+        let mut lines: Vec<String> = [
+            ("Sessions", db.sessions),
+            ("Conversations", db.conversations),
+            ("Memories", db.memories),
+            ("Learned knowledge", db.knowledge),
+            ("Document insights", db.document_insights),
+            ("Knowledge base entries", self.knowledge_base),
+        ]
+        .iter()
+        .map(|(kind, counts)| {
+            format!("{}: {} added, {} unchanged, {} conflicting", kind, counts.added, counts.unchanged, counts.conflicts)
+        })
+        .collect();
+        lines.extend(db.conflicts.iter().map(|conflict| format!("Conflict: {}", conflict)));
+        lines
+    }
+}
+
+/// Exports `character`, or every character when it is `None` together with
+/// `knowledge_base`.
+pub async fn export(db: &Database, character: Option<&str>, knowledge_base: &KnowledgeBaseHandler) -> Result<Archive, ArchiveError> {
+    let snapshot = db.export_snapshot(character.map(str::to_string)).await?;
+    let knowledge_base = match character {
+        Some(_) => None,
+        None => Some(serde_json::to_value(KnowledgeBaseFile::new(knowledge_base.entries().await))?),
+    };
+
+    Ok(Archive {
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        character: character.map(str::to_string),
+        snapshot,
+        knowledge_base,
+    })
+}
+
+/// Imports `archive`, merging it into what is stored or replacing the data
+/// in its scope. Memories that are added are embedded with `vectors` so
+/// they can be recalled by similarity; failing that they are still recalled
+/// by keyword.
+pub async fn import(
+    db: &Database,
+    vectors: Option<&VectorStore>,
+    knowledge_base: &KnowledgeBaseHandler,
+    archive: Archive,
+    mode: ImportMode,
+) -> Result<ArchiveReport, ArchiveError> {
+    if archive.version == 0 || archive.version > ARCHIVE_VERSION {
+        return Err(ArchiveError::UnsupportedVersion(archive.version));
+    }
+    // Refuse the whole archive before anything is written if part of it is unreadable
+    let imported_knowledge = archive.knowledge_base
+        .map(|value| KnowledgeBaseFile::from_value(value).map(|(file, _)| file))
+        .transpose()?;
+
+    let mut database = db.import_snapshot(archive.snapshot, archive.character, mode).await?;
+
+    if let Some(vectors) = vectors {
+        let mut by_character: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for (id, character, content) in database.new_memories.drain(..) {
+            by_character.entry(character).or_default().push((id.to_string(), content));
+        }
+        for (character, items) in by_character {
+            if let Err(e) = vectors.index(&memory_namespace(&character), items).await {
+                log::warn!("Failed to index imported memories of {}: {}", character, e);
+            }
+        }
+    }
+
+    let mut counts = ImportCounts::default();
+    if let Some(imported) = imported_knowledge {
+        let entries = match mode {
+            ImportMode::Merge => {
+                merge_knowledge_base(knowledge_base.entries().await, imported.entries, &mut counts, &mut database.conflicts)
+            }
+            ImportMode::Replace => {
+                counts.added = imported.entries.len();
+                imported.entries
+            }
+        };
+        knowledge_base.replace_entries(entries).await?;
+    }
+
+    Ok(ArchiveReport { database, knowledge_base: counts })
+}
+
+/// Reads an archive file, refusing versions this build does not know.
+pub async fn read(path: impl AsRef<Path>) -> Result<Archive, ArchiveError> {
+    let data = fs::read_to_string(path).await?;
+    let raw: Value = serde_json::from_str(&data)?;
+    let version = raw.get("version").and_then(Value::as_u64).unwrap_or_default() as u32;
+    if version == 0 || version > ARCHIVE_VERSION {
+        return Err(ArchiveError::UnsupportedVersion(version));
+    }
+    Ok(serde_json::from_value(raw)?)
+}
+
+/// Writes `archive` to `path`, never leaving a partly written file behind.
+pub async fn write(path: impl AsRef<Path>, archive: &Archive) -> Result<(), ArchiveError> {
+    files::write_atomic(path, serde_json::to_string_pretty(archive)?.as_bytes()).await?;
+    Ok(())
+}
+
+/// Adds the entries of `imported` missing from `stored`. An entry with the
+/// id of a stored one that says something else is a conflict, and the
+/// stored one is kept.
+fn merge_knowledge_base(
+    mut stored: Vec<KnowledgeEntry>,
+    imported: Vec<KnowledgeEntry>,
+    counts: &mut ImportCounts,
+    conflicts: &mut Vec<String>,
+) -> Vec<KnowledgeEntry> {
+    for entry in imported {
+        let same = |other: &KnowledgeEntry| other.content == entry.content && other.keywords == entry.keywords;
+        match stored.iter().find(|other| other.id == entry.id) {
+            Some(other) if same(other) => counts.unchanged += 1,
+            Some(_) => {
+                counts.conflicts += 1;
+                conflicts.push(format!("Kept the stored knowledge base entry {}", entry.id));
+            }
+            None if stored.iter().any(same) => counts.unchanged += 1,
+            None => {
+                stored.push(entry);
+                counts.added += 1;
+            }
+        }
+    }
+    stored
+}
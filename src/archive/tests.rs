@@ -0,0 +1,173 @@
+use super::*;
+use crate::database::{learned_knowledge_key, LOCAL_USER};
+use crate::knowledge_base::knowledge_base::MANUAL_SOURCE;
+use crate::embeddings::HashEmbedder;
+use crate::memory::LongTermMemory;
+use std::sync::Arc;
+
+/// A database with a session, a memory reflected on, learned knowledge of
+/// Nova and Echo and a document insight.
+async fn populated() -> Database {
+    let db = Database::new(":memory:").await.unwrap();
+    for character in ["Nova", "Echo"] {
+        let session = db.open_session(None, LOCAL_USER.to_string(), character.to_string()).await.unwrap().unwrap();
+        db.save_conversation(&session, format!("Hi {}", character), "Hello!".to_string()).await.unwrap();
+        db.save_knowledge(
+            learned_knowledge_key("topic", LOCAL_USER, character, "greetings"),
+            format!("{} likes to say hello", character),
+        ).await.unwrap();
+    }
+
+    let mut memory = LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap();
+    let source = memory.add_memory("chat", "My cat is called Miso", 0.7).await.unwrap();
+    memory.add_derived_memory("reflection", "The user has a cat", 0.6, vec![source]).await.unwrap();
+
+    db.save_document_insight("notes.md".to_string(), "Cats sleep a lot".to_string(), 0.9, "fact".to_string())
+        .await
+        .unwrap();
+    db
+}
+
+/// An empty knowledge base kept in `dir`.
+async fn knowledge_base(dir: &tempfile::TempDir) -> KnowledgeBaseHandler {
+    KnowledgeBaseHandler::load(dir.path().join("knowledge_base.json").to_str().unwrap()).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_character_export_round_trips_into_an_empty_database() {
+    let db = populated().await;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nova.json");
+    let knowledge_base = knowledge_base(&dir).await;
+
+    let exported = export(&db, Some("Nova"), &knowledge_base).await.unwrap();
+    assert_eq!(exported.snapshot.sessions.len(), 1);
+    assert_eq!(exported.snapshot.knowledge.len(), 1);
+    assert!(exported.knowledge_base.is_none());
+    write(&path, &exported).await.unwrap();
+
+    let target = Database::new(":memory:").await.unwrap();
+    let vectors = VectorStore::new(target.clone(), Arc::new(HashEmbedder::default()));
+    let report = import(&target, Some(&vectors), &knowledge_base, read(&path).await.unwrap(), ImportMode::Merge)
+        .await
+        .unwrap();
+    assert_eq!(report.database.conversations.added, 1);
+    assert_eq!(report.database.memories.added, 2);
+    assert!(report.database.conflicts.is_empty());
+
+    let memories = LongTermMemory::load(target.clone(), LOCAL_USER, "Nova").await.unwrap();
+    let derived = memories.memories().iter().find(|m| m.source == "reflection").unwrap();
+    let source = memories.memories().iter().find(|m| m.content.contains("Miso")).unwrap();
+    assert_eq!(derived.derived_from, vec![source.id]);
+    assert!(!vectors.search(&memory_namespace("Nova"), "cat", 5).await.unwrap().is_empty());
+
+    // Importing the same archive again changes nothing
+    let again = import(&target, None, &knowledge_base, read(&path).await.unwrap(), ImportMode::Merge)
+        .await
+        .unwrap();
+    assert_eq!(again.database.memories.added, 0);
+    assert_eq!(again.database.memories.unchanged, 2);
+    assert_eq!(again.database.conversations.unchanged, 1);
+    assert_eq!(again.database.document_insights.unchanged, 1);
+}
+
+#[tokio::test]
+async fn test_merge_keeps_stored_values_and_reports_conflicts() {
+    let db = populated().await;
+    let dir = tempfile::tempdir().unwrap();
+    let knowledge_base = knowledge_base(&dir).await;
+    let mut exported = export(&db, Some("Nova"), &knowledge_base).await.unwrap();
+    exported.snapshot.knowledge[0].value = "Nova prefers to wave".to_string();
+    exported.snapshot.sessions[0].user_id = "someone-else".to_string();
+
+    let report = import(&db, None, &knowledge_base, exported, ImportMode::Merge).await.unwrap();
+    assert_eq!(report.database.knowledge.conflicts, 1);
+    assert_eq!(report.database.sessions.conflicts, 1);
+    assert_eq!(report.database.conversations.conflicts, 1);
+    assert_eq!(report.database.conflicts.len(), 2);
+
+    let key = learned_knowledge_key("topic", LOCAL_USER, "Nova", "greetings");
+    assert_eq!(db.get_knowledge(key).await.unwrap().as_deref(), Some("Nova likes to say hello"));
+}
+
+#[tokio::test]
+async fn test_replace_only_touches_the_archived_character() {
+    let db = populated().await;
+    let dir = tempfile::tempdir().unwrap();
+    let knowledge_base = knowledge_base(&dir).await;
+    let mut exported = export(&db, Some("Nova"), &knowledge_base).await.unwrap();
+    exported.snapshot.memories.retain(|m| m.source != "reflection");
+
+    LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap()
+        .add_memory("chat", "I moved to Lisbon", 0.8).await.unwrap();
+
+    let report = import(&db, None, &knowledge_base, exported, ImportMode::Replace).await.unwrap();
+    assert_eq!(report.database.memories.added, 1);
+
+    let nova = LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap();
+    let contents: Vec<&str> = nova.memories().iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, vec!["My cat is called Miso"]);
+
+    let echo = db.export_snapshot(Some("Echo".to_string())).await.unwrap();
+    assert_eq!(echo.conversations.len(), 1);
+    assert_eq!(echo.knowledge.len(), 1);
+}
+
+#[tokio::test]
+async fn test_full_archive_merges_the_knowledge_base() {
+    let db = populated().await;
+    let dir = tempfile::tempdir().unwrap();
+    let knowledge_base = knowledge_base(&dir).await;
+    let rust = KnowledgeEntry::new(MANUAL_SOURCE, vec!["rust".to_string()], "Rust is fast");
+    let mut conflicting = rust.clone();
+    conflicting.content = "Rust is slow".to_string();
+    knowledge_base.replace_entries(vec![rust]).await.unwrap();
+
+    let mut exported = export(&db, None, &knowledge_base).await.unwrap();
+    assert_eq!(exported.snapshot.conversations.len(), 2);
+    assert_eq!(exported.knowledge_base.as_ref().unwrap()["entries"].as_array().unwrap().len(), 1);
+
+    // Archives written before the knowledge base was versioned hold a plain list
+    exported.knowledge_base = Some(serde_json::json!([
+        {"content": "Rust is fast", "keywords": ["rust"]},
+        {"content": "Tokio runs async tasks", "keywords": ["tokio"]}
+    ]));
+    let report = import(&db, None, &knowledge_base, exported.clone(), ImportMode::Merge).await.unwrap();
+    assert_eq!(report.knowledge_base.added, 1);
+    assert_eq!(report.knowledge_base.unchanged, 1);
+    assert_eq!(knowledge_base.entries().await.len(), 2);
+
+    exported.knowledge_base = Some(serde_json::to_value(KnowledgeBaseFile::new(vec![conflicting])).unwrap());
+    let report = import(&db, None, &knowledge_base, exported, ImportMode::Merge).await.unwrap();
+    assert_eq!(report.knowledge_base.conflicts, 1);
+    assert!(knowledge_base.entries().await.iter().any(|e| e.content == "Rust is fast"));
+
+    let reloaded = KnowledgeBaseHandler::load(dir.path().join("knowledge_base.json").to_str().unwrap()).await.unwrap();
+    assert_eq!(reloaded.entries().await.len(), 2);
+}
+
+#[tokio::test]
+async fn test_unreadable_knowledge_base_leaves_the_database_unchanged() {
+    let db = populated().await;
+    let dir = tempfile::tempdir().unwrap();
+    let knowledge_base = knowledge_base(&dir).await;
+    let mut exported = export(&db, None, &knowledge_base).await.unwrap();
+    exported.snapshot.memories.clear();
+    let before = db.export_snapshot(None).await.unwrap();
+
+    for corrupt in [serde_json::json!({"version": 99, "entries": []}), serde_json::json!(42)] {
+        exported.knowledge_base = Some(corrupt);
+        let result = import(&db, None, &knowledge_base, exported.clone(), ImportMode::Replace).await;
+        assert!(matches!(result, Err(ArchiveError::KnowledgeBase(_))), "{:?}", result.err());
+        assert_eq!(db.export_snapshot(None).await.unwrap(), before);
+    }
+}
+
+#[tokio::test]
+async fn test_newer_archives_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("future.json");
+    tokio::fs::write(&path, r#"{"version": 99, "exported_at": "2030-01-01T00:00:00Z"}"#).await.unwrap();
+
+    assert!(matches!(read(&path).await, Err(ArchiveError::UnsupportedVersion(99))));
+}
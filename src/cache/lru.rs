@@ -0,0 +1,75 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// A small least-recently-used map whose entries expire after a TTL.
+pub struct LruCache<V> {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<String, (Instant, V)>,
+    /// Keys from least to most recently used.
+    order: VecDeque<String>,
+}
+
+impl<V: Clone> LruCache<V> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<V> {
+        let expired = match self.entries.get(key) {
+            Some((inserted, _)) => inserted.elapsed() >= self.ttl,
+            None => return None,
+        };
+        if expired {
+            self.remove(key);
+            return None;
+        }
+
+        self.touch(key);
+        self.entries.get(key).map(|(_, value)| value.clone())
+    }
+
+    pub fn insert(&mut self, key: String, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(key.clone(), (Instant::now(), value)).is_some() {
+            self.touch(&key);
+            return;
+        }
+
+        self.order.push_back(key);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    fn touch(&mut self, key: &str) {
+        if let Some(position) = self.order.iter().position(|k| k == key) {
+            if let Some(key) = self.order.remove(position) {
+                self.order.push_back(key);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+        self.order.retain(|k| k != key);
+    }
+}
@@ -0,0 +1,394 @@
+// src/cache/mod.rs
+pub mod lru;
+
+#[cfg(test)]
+mod tests;
+
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use serde_json::json;
+use std::env;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::completion::{
+    with_system_message, ChatMessage, Completion, CompletionError, CompletionProvider, CompletionStream,
+    GenerationParams, StreamEvent, ToolDefinition, Usage,
+};
+use crate::database::{CachedCompletion, CompletionCacheStats, Database, DatabaseError};
+use lru::LruCache;
+
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Stable hex digest of `text`: FNV-1a, so keys stay valid across Rust
+/// versions and platforms.
+pub fn stable_hash(text: &str) -> String {
+    format!("{:016x}", fnv1a(text))
+}
+
+/// 64-bit FNV-1a hash of `text`.
+pub fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Which requests the completion cache serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    /// Every request goes to the provider.
+    Off,
+    /// Only requests sent at temperature 0, whose replies are reproducible.
+    Deterministic,
+    /// Every request, whatever its temperature.
+    All,
+}
+
+impl CacheMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "off" | "false" | "0" => Some(CacheMode::Off),
+            "on" | "true" | "1" | "deterministic" => Some(CacheMode::Deterministic),
+            "all" => Some(CacheMode::All),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    pub mode: CacheMode,
+    pub ttl: Duration,
+    /// Entries kept in memory; the SQLite table is not size-limited.
+    pub capacity: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            mode: CacheMode::Off,
+            ttl: DEFAULT_TTL,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Off by default. `COMPLETION_CACHE` is `off`, `on` (temperature-0
+    /// requests only) or `all`; `COMPLETION_CACHE_TTL_SECS` and
+    /// `COMPLETION_CACHE_SIZE` override the TTL and in-memory capacity.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        defaults.clone()
+            .with_mode(env::var("COMPLETION_CACHE").ok().and_then(|v| CacheMode::parse(&v)).unwrap_or(defaults.mode))
+            .with_ttl(var("COMPLETION_CACHE_TTL_SECS").map(Duration::from_secs).unwrap_or(defaults.ttl))
+            .with_capacity(var("COMPLETION_CACHE_SIZE").map(|v| v as usize).unwrap_or(defaults.capacity))
+    }
+
+    pub fn with_mode(mut self, mode: CacheMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+}
+
+/// Cache counters for this session plus what the SQLite table holds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CacheStats {
+    pub mode: CacheMode,
+    pub memory_entries: usize,
+    pub session_hits: u64,
+    pub session_misses: u64,
+    pub stored: Option<CompletionCacheStats>,
+}
+
+struct CacheState {
+    memory: LruCache<Completion>,
+    hits: u64,
+    misses: u64,
+}
+
+/// Completion cache: an in-memory LRU in front of the `completion_cache`
+/// table. Entries are keyed by a hash of the full conversation (system
+/// prompt included) and the generation params it was sent with, and expire
+/// after the configured TTL.
+#[derive(Clone)]
+pub struct CompletionCache {
+    config: CacheConfig,
+    db: Option<Database>,
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl CompletionCache {
+    /// A cache backed by `db`, or kept in memory only without one.
+    pub fn new(config: CacheConfig, db: Option<Database>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CacheState {
+                memory: LruCache::new(config.capacity, config.ttl),
+                hits: 0,
+                misses: 0,
+            })),
+            config,
+            db,
+        }
+    }
+
+    /// Wraps `provider` so cacheable requests are answered from the cache.
+    /// Returns it unchanged when caching is off.
+    pub fn wrap(&self, provider: Arc<dyn CompletionProvider>) -> Arc<dyn CompletionProvider> {
+        if self.config.mode == CacheMode::Off {
+            return provider;
+        }
+        Arc::new(CachedProvider {
+            inner: provider,
+            cache: self.clone(),
+        })
+    }
+
+    /// The cache key for sending `messages` to `provider` with `params`, or
+    /// `None` if the request should not be cached.
+    pub fn key(
+        &self,
+        provider: &dyn CompletionProvider,
+        messages: &[ChatMessage],
+        params: &GenerationParams,
+        json_mode: bool,
+    ) -> Option<String> {
+        let params = params.clone().or(&provider.params());
+        let cacheable = match self.config.mode {
+            CacheMode::Off => false,
+            CacheMode::Deterministic => params.temperature == Some(0.0),
+            CacheMode::All => true,
+        };
+        if !cacheable {
+            return None;
+        }
+
+        let request = json!({
+            "model": params.model_or(provider.model()),
+            "params": GenerationParams { model: None, ..params.clone() },
+            "json": json_mode,
+            "messages": with_system_message(provider.system_message(), messages)
+        });
+        Some(stable_hash(&request.to_string()))
+    }
+
+    /// The cached reply for `key`. Hits report no token usage, since
+    /// nothing was sent to the model.
+    pub async fn get(&self, key: &str) -> Option<Completion> {
+        let cached = self.state.lock().unwrap().memory.get(key);
+        let cached = match (cached, &self.db) {
+            (Some(completion), _) => Some(completion),
+            (None, Some(db)) => match db.get_cached_completion(key.to_string(), self.config.ttl.as_secs()).await {
+                Ok(stored) => stored.map(|stored| {
+                    let completion = Completion::new(
+                        stored.content,
+                        Usage::new(stored.prompt_tokens, stored.completion_tokens),
+                    );
+                    self.state.lock().unwrap().memory.insert(key.to_string(), completion.clone());
+                    completion
+                }),
+                Err(e) => {
+                    log::warn!("Failed to read completion cache: {}", e);
+                    None
+                }
+            },
+            (None, None) => None,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        match cached {
+            Some(completion) => {
+                state.hits += 1;
+                Some(Completion::new(completion.content, Usage::default()))
+            }
+            None => {
+                state.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub async fn put(&self, key: String, model: &str, completion: &Completion) {
+        self.state.lock().unwrap().memory.insert(key.clone(), completion.clone());
+
+        if let Some(db) = &self.db {
+            let entry = CachedCompletion {
+                model: model.to_string(),
+                content: completion.content.clone(),
+                prompt_tokens: completion.usage.prompt_tokens,
+                completion_tokens: completion.usage.completion_tokens,
+            };
+            if let Err(e) = db.save_cached_completion(key, entry, self.config.ttl.as_secs()).await {
+                log::warn!("Failed to write completion cache: {}", e);
+            }
+        }
+    }
+
+    /// Drops every cached reply, returning how many were removed.
+    pub async fn clear(&self) -> Result<usize, DatabaseError> {
+        let in_memory = {
+            let mut state = self.state.lock().unwrap();
+            let count = state.memory.len();
+            state.memory.clear();
+            count
+        };
+
+        match &self.db {
+            Some(db) => db.clear_completion_cache().await,
+            None => Ok(in_memory),
+        }
+    }
+
+    pub async fn stats(&self) -> Result<CacheStats, DatabaseError> {
+        let stored = match &self.db {
+            Some(db) => Some(db.completion_cache_stats().await?),
+            None => None,
+        };
+
+        let state = self.state.lock().unwrap();
+        Ok(CacheStats {
+            mode: self.config.mode,
+            memory_entries: state.memory.len(),
+            session_hits: state.hits,
+            session_misses: state.misses,
+            stored,
+        })
+    }
+}
+
+/// A provider decorator that answers repeated requests from a
+/// `CompletionCache`. Requests with tools are never cached.
+pub struct CachedProvider {
+    inner: Arc<dyn CompletionProvider>,
+    cache: CompletionCache,
+}
+
+impl CachedProvider {
+    async fn cached(
+        &self,
+        messages: &[ChatMessage],
+        params: &GenerationParams,
+        json_mode: bool,
+        call: impl Future<Output = Result<Completion, CompletionError>>,
+    ) -> Result<Completion, CompletionError> {
+        let key = match self.cache.key(self.inner.as_ref(), messages, params, json_mode) {
+            Some(key) => key,
+            None => return call.await,
+        };
+        if let Some(hit) = self.cache.get(&key).await {
+            return Ok(hit);
+        }
+
+        let completion = call.await?;
+        self.cache.put(key, params.model_or(self.inner.model()), &completion).await;
+        Ok(completion)
+    }
+}
+
+#[async_trait::async_trait]
+impl CompletionProvider for CachedProvider {
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn system_message(&self) -> &str {
+        self.inner.system_message()
+    }
+
+    fn params(&self) -> GenerationParams {
+        self.inner.params()
+    }
+
+    fn context_window(&self) -> usize {
+        self.inner.context_window()
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.cached(messages, params, false, self.inner.chat(messages, params)).await
+    }
+
+    async fn chat_json(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.cached(messages, params, true, self.inner.chat_json(messages, params)).await
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.inner.supports_tools()
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<Completion, CompletionError> {
+        self.inner.chat_with_tools(messages, tools, params).await
+    }
+
+    async fn chat_with_tools_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.inner.chat_with_tools_stream(messages, tools, params).await
+    }
+
+    async fn chat_stream(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<CompletionStream, CompletionError> {
+        let key = match self.cache.key(self.inner.as_ref(), messages, params, false) {
+            Some(key) => key,
+            None => return self.inner.chat_stream(messages, params).await,
+        };
+        if let Some(hit) = self.cache.get(&key).await {
+            return Ok(Box::pin(stream::iter([
+                Ok(StreamEvent::Delta(hit.content)),
+                Ok(StreamEvent::Usage(hit.usage)),
+            ])));
+        }
+
+        let deltas = self.inner.chat_stream(messages, params).await?;
+
+        // Collect the reply as it streams past and cache it once the stream
+        // ends, unless it failed part way
+        let tally = Arc::new(Mutex::new((String::new(), None::<Usage>, false)));
+        let observed = {
+            let tally = tally.clone();
+            deltas.inspect(move |event| {
+                let mut tally = tally.lock().unwrap();
+                match event {
+                    Ok(StreamEvent::Delta(text)) => tally.0.push_str(text),
+                    Ok(StreamEvent::Usage(usage)) => tally.1 = Some(*usage),
+                    Ok(StreamEvent::ToolCalls(_)) => {}
+                    Err(_) => tally.2 = true,
+                }
+            })
+        };
+
+        let cache = self.cache.clone();
+        let model = params.model_or(self.inner.model()).to_string();
+        let prompt = with_system_message(self.inner.system_message(), messages);
+        let finish = stream::once(async move {
+            let (content, usage, failed) = std::mem::take(&mut *tally.lock().unwrap());
+            if !failed {
+                let usage = usage.unwrap_or_else(|| Usage::estimate(&prompt, &content));
+                cache.put(key, &model, &Completion::new(content, usage)).await;
+            }
+        })
+        .filter_map(|_| async { None });
+
+        Ok(Box::pin(observed.chain(finish)))
+    }
+}
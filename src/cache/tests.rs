@@ -0,0 +1,145 @@
+use super::*;
+use crate::providers::mock::mock::MockProvider;
+use futures::StreamExt;
+
+fn deterministic() -> CacheConfig {
+    CacheConfig::default().with_mode(CacheMode::Deterministic)
+}
+
+async fn cache_with_db(config: CacheConfig) -> CompletionCache {
+    CompletionCache::new(config, Some(Database::new(":memory:").await.unwrap()))
+}
+
+#[test]
+fn test_lru_evicts_least_recently_used_and_expired_entries() {
+    let mut lru = LruCache::new(2, Duration::from_secs(60));
+    lru.insert("a".to_string(), 1);
+    lru.insert("b".to_string(), 2);
+    assert_eq!(lru.get("a"), Some(1));
+    lru.insert("c".to_string(), 3);
+
+    assert_eq!(lru.get("b"), None);
+    assert_eq!(lru.get("a"), Some(1));
+    assert_eq!(lru.len(), 2);
+
+    let mut expiring = LruCache::new(2, Duration::ZERO);
+    expiring.insert("a".to_string(), 1);
+    assert_eq!(expiring.get("a"), None);
+    assert_eq!(expiring.len(), 0);
+}
+
+#[test]
+fn test_key_covers_prompt_and_generation_settings() {
+    let cache = CompletionCache::new(deterministic(), None);
+    let messages = [ChatMessage::user("Summarize the report")];
+    let defaults = GenerationParams::default();
+    let provider = MockProvider::always("ok").with_temperature(0.0).with_system_message("You are Nova.");
+
+    let key = cache.key(&provider, &messages, &defaults, false).unwrap();
+    assert_eq!(cache.key(&provider, &messages, &defaults, false).unwrap(), key);
+    assert_ne!(cache.key(&provider, &messages, &defaults, true).unwrap(), key);
+    assert_ne!(cache.key(&provider, &[ChatMessage::user("Summarize it")], &defaults, false).unwrap(), key);
+    assert_ne!(cache.key(&provider, &messages, &defaults.clone().with_max_tokens(100), false).unwrap(), key);
+
+    let other_system = MockProvider::always("ok").with_temperature(0.0).with_system_message("You are Sage.");
+    assert_ne!(cache.key(&other_system, &messages, &defaults, false).unwrap(), key);
+
+    let other_model = MockProvider::always("ok").with_temperature(0.0).with_system_message("You are Nova.").with_model("gpt-4o");
+    assert_ne!(cache.key(&other_model, &messages, &defaults, false).unwrap(), key);
+
+    // Sampled requests are only cached in `all` mode, whether the
+    // temperature comes from the provider or the call
+    let sampled = MockProvider::always("ok").with_temperature(0.7);
+    assert!(cache.key(&sampled, &messages, &defaults, false).is_none());
+    assert!(cache.key(&provider, &messages, &defaults.clone().with_temperature(0.7), false).is_none());
+    assert!(cache.key(&sampled, &messages, &defaults.clone().with_temperature(0.0), false).is_some());
+    let all = CompletionCache::new(CacheConfig::default().with_mode(CacheMode::All), None);
+    assert!(all.key(&sampled, &messages, &defaults, false).is_some());
+}
+
+#[tokio::test]
+async fn test_repeated_deterministic_request_is_served_from_cache() {
+    let cache = cache_with_db(deterministic()).await;
+    let mock = Arc::new(MockProvider::new(["First answer", "Second answer"]).with_temperature(0.0));
+    let provider = cache.wrap(mock.clone());
+    let messages = [ChatMessage::user("Summarize the report")];
+
+    let first = provider.complete_chat(&messages).await.unwrap();
+    let second = provider.complete_chat(&messages).await.unwrap();
+
+    assert_eq!(first.content, "First answer");
+    assert_eq!(second.content, "First answer");
+    assert_eq!(second.usage, Usage::default());
+    assert_eq!(mock.requests().len(), 1);
+
+    let stats = cache.stats().await.unwrap();
+    assert_eq!((stats.session_hits, stats.session_misses), (1, 1));
+    assert_eq!(stats.stored.unwrap().entries, 1);
+}
+
+#[tokio::test]
+async fn test_cache_survives_restart_through_sqlite() {
+    let db = Database::new(":memory:").await.unwrap();
+    let messages = [ChatMessage::user("Summarize the report")];
+
+    let first = CompletionCache::new(deterministic(), Some(db.clone()));
+    first.wrap(Arc::new(MockProvider::always("Stored answer").with_temperature(0.0)))
+        .complete_chat(&messages)
+        .await
+        .unwrap();
+
+    let restarted = CompletionCache::new(deterministic(), Some(db));
+    let mock = Arc::new(MockProvider::new(Vec::<String>::new()).with_temperature(0.0));
+    let answer = restarted.wrap(mock.clone()).complete_chat(&messages).await.unwrap();
+
+    assert_eq!(answer.content, "Stored answer");
+    assert!(mock.requests().is_empty());
+    let stored = restarted.stats().await.unwrap().stored.unwrap();
+    assert_eq!(stored.hits, 1);
+    assert!(stored.tokens_saved > 0);
+}
+
+#[tokio::test]
+async fn test_sampled_requests_and_disabled_cache_go_to_the_provider() {
+    let messages = [ChatMessage::user("Write a tweet")];
+
+    let sampled = Arc::new(MockProvider::new(["One", "Two"]).with_temperature(0.9));
+    let provider = cache_with_db(deterministic()).await.wrap(sampled.clone());
+    provider.complete_chat(&messages).await.unwrap();
+    assert_eq!(provider.complete_chat(&messages).await.unwrap().content, "Two");
+
+    let off = Arc::new(MockProvider::new(["One", "Two"]).with_temperature(0.0));
+    let provider = cache_with_db(CacheConfig::default()).await.wrap(off.clone());
+    provider.complete_chat(&messages).await.unwrap();
+    assert_eq!(provider.complete_chat(&messages).await.unwrap().content, "Two");
+}
+
+#[tokio::test]
+async fn test_streamed_reply_is_cached_once_complete() {
+    let cache = cache_with_db(deterministic()).await;
+    let mock = Arc::new(MockProvider::new(["Streamed answer"]).with_temperature(0.0));
+    let provider = cache.wrap(mock.clone());
+    let messages = [ChatMessage::user("Explain lifetimes")];
+
+    let collect = |events: Vec<Result<StreamEvent, CompletionError>>| {
+        events.into_iter().filter_map(|e| e.unwrap().as_delta().map(String::from)).collect::<String>()
+    };
+    let first: Vec<_> = provider.complete_stream(&messages).await.unwrap().collect().await;
+    let second: Vec<_> = provider.complete_stream(&messages).await.unwrap().collect().await;
+
+    assert_eq!(collect(first), "Streamed answer");
+    assert_eq!(collect(second), "Streamed answer");
+    assert_eq!(mock.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_clear_empties_memory_and_table() {
+    let cache = cache_with_db(deterministic()).await;
+    let mock = Arc::new(MockProvider::new(["One", "Two"]).with_temperature(0.0));
+    let provider = cache.wrap(mock.clone());
+    let messages = [ChatMessage::user("Summarize the report")];
+
+    provider.complete_chat(&messages).await.unwrap();
+    assert_eq!(cache.clear().await.unwrap(), 1);
+    assert_eq!(provider.complete_chat(&messages).await.unwrap().content, "Two");
+}
@@ -0,0 +1,70 @@
+use crate::archive;
+use crate::database::{Database, ImportMode};
+use crate::embeddings::VectorStore;
+use crate::knowledge_base::knowledge_base::KnowledgeBaseHandler;
+use colored::Colorize;
+
+const USAGE: &str = "Usage: export <file> [--all] | import <file> [--replace]";
+
+/// Handles `export` and `import`. Returns whether anything was imported,
+/// in which case what is loaded for the character is out of date.
+pub async fn handle_command(
+    input: &str,
+    db: &Database,
+    vectors: &VectorStore,
+    knowledge_base: &KnowledgeBaseHandler,
+    character: &str,
+) -> Result<bool, String> {
+    let mut words = input.split_whitespace();
+    let command = words.next();
+    let (flags, paths): (Vec<&str>, Vec<&str>) = words.partition(|word| word.starts_with("--"));
+    let [path] = paths[..] else {
+        println!("{}", USAGE);
+        return Ok(false);
+    };
+
+    match command {
+        Some("export") => {
+            let scope = if flags.contains(&"--all") { None } else { Some(character) };
+            let exported = archive::export(db, scope, knowledge_base).await
+                .map_err(|e| format!("Failed to export: {}", e))?;
+            archive::write(path, &exported).await
+                .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+            let snapshot = &exported.snapshot;
+            println!("📦 Exported {} to {}: {} conversation(s), {} memories, {} learned fact(s), {} document insight(s)",
+                scope.unwrap_or("every character"),
+                path.cyan(),
+                snapshot.conversations.len(),
+                snapshot.memories.len(),
+                snapshot.knowledge.len(),
+                snapshot.document_insights.len()
+            );
+            Ok(false)
+        }
+        Some("import") => {
+            let mode = if flags.contains(&"--replace") { ImportMode::Replace } else { ImportMode::Merge };
+            let imported = archive::read(path).await
+                .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let scope = imported.character.clone().unwrap_or_else(|| "every character".to_string());
+            let report = archive::import(db, Some(vectors), knowledge_base, imported, mode).await
+                .map_err(|e| format!("Failed to import {}: {}", path, e))?;
+
+            let verb = if mode == ImportMode::Replace { "Replaced" } else { "Merged" };
+            println!("\n📦 {}", format!("{} {} from {}:", verb, scope, path).bright_cyan());
+            for line in report.lines() {
+                if line.starts_with("Conflict") {
+                    println!("  {}", line.yellow());
+                } else {
+                    println!("  {}", line);
+                }
+            }
+            println!();
+            Ok(true)
+        }
+        _ => {
+            println!("{}", USAGE);
+            Ok(false)
+        }
+    }
+}
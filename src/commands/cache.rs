@@ -0,0 +1,43 @@
+use crate::cache::{CacheMode, CompletionCache};
+use colored::Colorize;
+
+pub async fn handle_command(input: &str, cache: &CompletionCache) -> Result<(), String> {
+    match input.split_whitespace().nth(1) {
+        Some("stats") => {
+            let stats = cache.stats().await
+                .map_err(|e| format!("Failed to load cache stats: {}", e))?;
+
+            println!("\n🗄️ {}", "Completion cache:".bright_cyan());
+            let mode = match stats.mode {
+                CacheMode::Off => "off (set COMPLETION_CACHE=on to enable)",
+                CacheMode::Deterministic => "on (temperature 0 requests)",
+                CacheMode::All => "all requests",
+            };
+            println!("  Mode: {}", mode);
+            println!("  This session: {} hits, {} misses, {} entries in memory",
+                stats.session_hits.to_string().cyan(),
+                stats.session_misses.to_string().cyan(),
+                stats.memory_entries.to_string().cyan()
+            );
+            if let Some(stored) = stats.stored {
+                println!("  Stored: {} entries, {} hits, {} tokens saved",
+                    stored.entries.to_string().cyan(),
+                    stored.hits.to_string().cyan(),
+                    stored.tokens_saved.to_string().bright_green()
+                );
+            }
+            println!();
+            Ok(())
+        }
+        Some("clear") => {
+            let removed = cache.clear().await
+                .map_err(|e| format!("Failed to clear cache: {}", e))?;
+            println!("🧹 Removed {} cached completion(s)", removed);
+            Ok(())
+        }
+        _ => {
+            println!("Usage: cache stats | cache clear");
+            Ok(())
+        }
+    }
+}
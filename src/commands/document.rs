@@ -1,18 +1,31 @@
-use crate::providers::deepseek::deepseek::DeepSeekProvider;
-use crate::providers::document::DocumentProcessor;
+use crate::providers::document::{DocumentLibrary, DocumentProcessor, Section};
 use crate::providers::document::insights::Insight;
-use crate::completion::CompletionProvider;
+use crate::providers::document::library::{cited, citation, format_passages, Passage, PASSAGE_LIMIT};
+use crate::completion::{ChatMessage, CompletionProvider};
+use crate::tokens;
 use crate::memory::{ShortTermMemory, LongTermMemory};
 use crate::database::Database;
+use crate::cache::stable_hash;
+use crate::embeddings::VectorStore;
+use crate::embeddings::store::INSIGHTS_NAMESPACE;
+use crate::learning::Learner;
 use colored::Colorize;
 use std::path::Path;
+use std::sync::Arc;
 
+/// Insights recalled into a `doc chat` prompt.
+const SIMILAR_INSIGHTS: usize = 5;
+
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_command(
     input: &str, 
-    provider: &DeepSeekProvider,
+    provider: &Arc<dyn CompletionProvider>,
+    insight_provider: &Arc<dyn CompletionProvider>,
     memory: &mut ShortTermMemory,
     long_term_memory: &mut LongTermMemory,
-    db: &Database
+    db: &Database,
+    vectors: &VectorStore,
+    learner: &Learner
 ) -> Result<(), String> {
     let parts: Vec<&str> = input.split_whitespace().collect();
     if parts.len() < 2 {
@@ -23,36 +36,52 @@ pub async fn handle_command(
         println!("  doc ocr <image_path>      - Extract text from image");
         println!("  doc batch <folder_path>   - Process multiple files");
         println!("  doc info <file_path>      - Show file information");
+        println!("  doc ingest <path>         - Index a file or folder for doc chat");
+        println!("  doc list                  - List indexed documents");
+        println!("  doc forget <file_path>    - Drop a document from the index");
+        println!("  doc chat <question>       - Ask about the indexed documents");
         return Ok(());
     }
 
     let command = parts[1];
+    let library = DocumentLibrary::new(db.clone(), vectors.clone());
+    if command == "list" {
+        return list_documents(&library).await;
+    }
     let file_path = parts.get(2).ok_or("Missing file path")?;
 
     match command {
         "analyze" => {
             println!("📄 Analyzing document: {}", file_path.bright_yellow());
-            
-            let insights = process_document(file_path, provider).await?;
+
+            let mut processor = DocumentProcessor::new(insight_provider.clone())
+                .map_err(|e| e.to_string())?;
+            let sections = processor.extract_sections(file_path)
+                .map_err(|e| format!("Failed to process document: {}", e))?;
+            ingest(&library, file_path, &sections).await;
+            let insights = processor.extract_insights(&sections).await
+                .map_err(|e| format!("Failed to process document: {}", e))?;
 
             // Store document context in memory
+            let insight_list = insights.iter()
+                .map(|i| format!("• {}", i.text))
+                .collect::<Vec<_>>()
+                .join("\n");
             memory.add_interaction(
                 &format!("Document being discussed: {}", file_path),
-                &format!("Document insights:\n{}", 
-                    insights.iter()
-                        .map(|i| format!("• {}", i.text))
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                )
+                &format!("Document insights:\n{}", insight_list)
             );
 
             // Store high-relevance insights in long-term memory
             for insight in &insights {
                 if insight.relevance > 0.7 {
-                    long_term_memory.add_memory(
-                        &format!("Document: {}", file_path),
-                        &format!("Key insight: {}", insight.text)
-                    );
+                    if let Err(e) = long_term_memory.add_memory(
+                        &format!("document:{}", file_path),
+                        &insight.text,
+                        insight.relevance
+                    ).await {
+                        eprintln!("Warning: Failed to save memory: {}", e);
+                    }
                 }
             }
 
@@ -68,22 +97,29 @@ pub async fn handle_command(
                 }
             }
 
+            // Embed the insights so later questions can find them by meaning
+            let items = insights.iter()
+                .map(|i| (format!("{}#{}", file_path, stable_hash(&i.text)), i.text.clone()))
+                .collect();
+            if let Err(e) = vectors.index(INSIGHTS_NAMESPACE, items).await {
+                eprintln!("Warning: Failed to embed insights: {}", e);
+            }
+
             // Get character-specific analysis
             let analysis_prompt = format!(
                 "{}\n\nAs this character, analyze these document insights and provide your unique perspective. \
                 Consider your personality traits and expertise when providing this analysis. \
                 Be creative and stay true to your character's style. \
                 After your analysis, invite further questions about the document:\n\n{}",
-                provider.get_system_message(),
-                insights.iter()
-                    .map(|i| format!("• {}", i.text))
-                    .collect::<Vec<_>>()
-                    .join("\n")
+                provider.system_message(),
+                insight_list
             );
 
             let analysis = provider.complete(&analysis_prompt).await
                 .map_err(|e| format!("Failed to generate analysis: {}", e))?;
 
+            learner.observe(&format!("Insights from {}:\n{}", file_path, insight_list), &analysis);
+
             println!("\n📊 Analysis Results:");
             println!("{}", analysis.bright_green());
             println!("\n💭 You can now ask questions about the document or request more specific analysis.");
@@ -93,44 +129,71 @@ pub async fn handle_command(
             // Get the chat query from remaining parts
             let query = parts[2..].join(" ");
             
-            // Build context from memory
-            let context = memory.get_interactions()
-                .iter()
-                .take(5)
-                .map(|(input, response)| format!("User: {}\nAssistant: {}", input, response))
-                .collect::<Vec<_>>()
-                .join("\n\n");
-
-            // Create chat prompt with context
-            let chat_prompt = format!(
-                "{}\n\nPrevious context:\n{}\n\nUser question about the document: {}\n\n\
-                Answer the question based on the document context while maintaining your character's personality.",
-                provider.get_system_message(),
-                context,
-                query
+            // Replay recent document context as prior chat turns, with the
+            // passages and insights closest to the question
+            let mut system = format!(
+                "{}\n\nAnswer the user's questions about the document being discussed \
+                while maintaining your character's personality.",
+                provider.system_message()
             );
+            let passages = library.search(&query, PASSAGE_LIMIT).await.unwrap_or_else(|e| {
+                eprintln!("Warning: Failed to search documents: {}", e);
+                Vec::new()
+            });
+            if !passages.is_empty() {
+                system.push_str("\n\n");
+                system.push_str(&format_passages(&passages));
+            }
+            match vectors.search(INSIGHTS_NAMESPACE, &query, SIMILAR_INSIGHTS).await {
+                Ok(similar) if !similar.is_empty() => {
+                    system.push_str("\n\nRelevant document insights:\n");
+                    system.push_str(&similar.iter().map(|m| format!("• {}", m.content)).collect::<Vec<_>>().join("\n"));
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Warning: Failed to search insights: {}", e),
+            }
+            let mut messages = vec![ChatMessage::system(system)];
+            for conv in memory.recent(5) {
+                messages.push(ChatMessage::user(conv.user_input.as_str()));
+                messages.push(ChatMessage::assistant(conv.ai_response.as_str()));
+            }
+            messages.push(ChatMessage::user(query.as_str()));
 
-            let response = provider.complete(&chat_prompt).await
+            let messages = tokens::fit_to_context(provider.as_ref(), &messages);
+            let response = provider.complete_messages(&messages).await
                 .map_err(|e| format!("Failed to get response: {}", e))?;
 
             // Store the interaction
             memory.add_interaction(&query, &response);
+            learner.observe(&query, &response);
 
             println!("\n💬 Response:");
             println!("{}", response.bright_green());
+            print_sources(&response, &passages);
+            Ok(())
+        },
+        "ingest" => ingest_path(file_path, insight_provider, &library).await,
+        "forget" => {
+            let removed = library.remove(file_path).await
+                .map_err(|e| format!("Failed to forget {}: {}", file_path, e))?;
+            if removed == 0 {
+                println!("{} is not indexed.", file_path);
+            } else {
+                println!("🧹 Forgot {} ({} passages)", file_path, removed);
+            }
             Ok(())
         },
         "summary" => {
             println!("📝 Generating summary for: {}", file_path.bright_yellow());
             
-            let insights = process_document(file_path, provider).await?;
+            let insights = process_document(file_path, insight_provider).await?;
 
             // Create a personality-aware summary prompt
             let summary_prompt = format!(
                 "{}\n\nAs this character, provide a concise summary of these document insights. \
                 Use your unique personality traits and communication style. \
                 Make the summary reflect your character's perspective and expertise:\n\n{}",
-                provider.get_system_message(), // Include character's personality
+                provider.system_message(), // Include character's personality
                 insights.iter()
                     .map(|i| format!("• {}", i.text))
                     .collect::<Vec<_>>()
@@ -147,7 +210,7 @@ pub async fn handle_command(
         "extract" => {
             println!("📄 Extracting text from: {}", file_path.bright_yellow());
             
-            let insights = process_document(file_path, provider).await?;
+            let insights = process_document(file_path, insight_provider).await?;
 
             println!("\n📝 Extracted Text:");
             for insight in insights {
@@ -155,20 +218,21 @@ pub async fn handle_command(
             }
             Ok(())
         },
-        "ocr" => process_image(file_path, provider).await,
-        "batch" => process_batch(file_path, provider).await,
+        "ocr" => process_image(file_path, provider, insight_provider).await,
+        "batch" => process_batch(file_path, insight_provider, &library).await,
         "info" => show_file_info(file_path).await,
         _ => Err(format!("Unknown document command: {}", command))
     }
 }
 
-async fn process_image(file_path: &str, provider: &DeepSeekProvider) -> Result<(), String> {
+async fn process_image(
+    file_path: &str,
+    provider: &Arc<dyn CompletionProvider>,
+    insight_provider: &Arc<dyn CompletionProvider>,
+) -> Result<(), String> {
     println!("🔍 Processing image: {}", file_path.bright_yellow());
     
-    let api_key = provider.get_api_key().to_string();
-    let system_message = provider.get_system_message().to_string();
-    let mut processor = DocumentProcessor::new(api_key, system_message)
-        .await
+    let mut processor = DocumentProcessor::new(insight_provider.clone())
         .map_err(|e| e.to_string())?;
 
     let insights = processor.process_document(file_path).await
@@ -177,7 +241,7 @@ async fn process_image(file_path: &str, provider: &DeepSeekProvider) -> Result<(
     // Create a personality-aware OCR analysis prompt
     let analysis_prompt = format!(
         "{}\n\nAs this character, analyze this OCR text and provide insights in your unique style:\n\n{}",
-        provider.get_system_message(),
+        provider.system_message(),
         insights.iter()
             .map(|i| i.text.as_str())
             .collect::<Vec<_>>()
@@ -192,7 +256,7 @@ async fn process_image(file_path: &str, provider: &DeepSeekProvider) -> Result<(
     Ok(())
 }
 
-async fn process_batch(folder_path: &str, provider: &DeepSeekProvider) -> Result<(), String> {
+async fn process_batch(folder_path: &str, provider: &Arc<dyn CompletionProvider>, library: &DocumentLibrary) -> Result<(), String> {
     use tokio::fs;
     use indicatif::{ProgressBar, ProgressStyle};
 
@@ -206,10 +270,7 @@ async fn process_batch(folder_path: &str, provider: &DeepSeekProvider) -> Result
         .template("{spinner:.green} [{elapsed_precise}] {msg}")
         .unwrap());
 
-    let api_key = provider.get_api_key().to_string();
-    let system_message = provider.get_system_message().to_string();
-    let mut processor = DocumentProcessor::new(api_key, system_message)
-        .await
+    let mut processor = DocumentProcessor::new(provider.clone())
         .map_err(|e| e.to_string())?;
 
     while let Some(entry) = entries.next_entry().await
@@ -218,8 +279,12 @@ async fn process_batch(folder_path: &str, provider: &DeepSeekProvider) -> Result
         let path = entry.path();
         if path.is_file() {
             pb.set_message(format!("Processing {}", path.display()));
-            if let Ok(insights) = processor.process_document(path.to_str().unwrap()).await {
-                println!("\n📄 {}: {} insights", path.display(), insights.len());
+            let file_path = path.to_str().unwrap();
+            if let Ok(sections) = processor.extract_sections(file_path) {
+                ingest(library, file_path, &sections).await;
+                if let Ok(insights) = processor.extract_insights(&sections).await {
+                    println!("\n📄 {}: {} insights", path.display(), insights.len());
+                }
             }
             pb.inc(1);
         }
@@ -246,12 +311,80 @@ async fn show_file_info(file_path: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Indexes `file_path` for `doc chat`, or every supported file in it when it
+/// is a folder. No model is called.
+async fn ingest_path(
+    file_path: &str,
+    provider: &Arc<dyn CompletionProvider>,
+    library: &DocumentLibrary,
+) -> Result<(), String> {
+    let mut processor = DocumentProcessor::new(provider.clone())
+        .map_err(|e| e.to_string())?;
+
+    let path = Path::new(file_path);
+    if !path.is_dir() {
+        let sections = processor.extract_sections(file_path)
+            .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+        ingest(library, file_path, &sections).await;
+        return Ok(());
+    }
+
+    let mut entries = tokio::fs::read_dir(path).await
+        .map_err(|e| format!("Failed to read directory: {}", e))?;
+    while let Some(entry) = entries.next_entry().await
+        .map_err(|e| format!("Failed to read entry: {}", e))?
+    {
+        let path = entry.path();
+        let Some(file_path) = path.to_str().filter(|_| path.is_file()) else {
+            continue;
+        };
+        match processor.extract_sections(file_path) {
+            Ok(sections) => ingest(library, file_path, &sections).await,
+            Err(e) => println!("  Skipped {}: {}", file_path, e),
+        }
+    }
+    Ok(())
+}
+
+/// Stores the chunks of a document, warning rather than failing when that
+/// goes wrong.
+async fn ingest(library: &DocumentLibrary, file_path: &str, sections: &[Section]) {
+    match library.ingest(file_path, sections).await {
+        Ok(count) => println!("🧩 Indexed {} passages of {}", count.to_string().cyan(), file_path),
+        Err(e) => eprintln!("Warning: Failed to index {}: {}", file_path, e),
+    }
+}
+
+async fn list_documents(library: &DocumentLibrary) -> Result<(), String> {
+    let documents = library.documents().await
+        .map_err(|e| format!("Failed to list documents: {}", e))?;
+    if documents.is_empty() {
+        println!("No documents are indexed yet. Use doc ingest <path> or doc analyze <file>.");
+        return Ok(());
+    }
+    println!("\n📚 {}", "Indexed documents:".bright_cyan());
+    for document in documents {
+        println!("  {} {}", document.path, format!("({} passages, {})", document.chunks, document.ingested_at).dimmed());
+    }
+    println!();
+    Ok(())
+}
+
+/// Lists the passages `reply` cites.
+fn print_sources(reply: &str, passages: &[Passage]) {
+    let cited = cited(reply, passages);
+    if cited.is_empty() {
+        return;
+    }
+    println!("\n📚 {}", "Sources:".bright_cyan());
+    for (number, passage) in cited {
+        println!("  [{}] {}", number, citation(&passage.chunk).dimmed());
+    }
+}
+
 // Helper function to process document
-async fn process_document(file_path: &str, provider: &DeepSeekProvider) -> Result<Vec<Insight>, String> {
-    let api_key = provider.get_api_key().to_string();
-    let system_message = provider.get_system_message().to_string();
-    let mut processor = DocumentProcessor::new(api_key, system_message)
-        .await
+async fn process_document(file_path: &str, provider: &Arc<dyn CompletionProvider>) -> Result<Vec<Insight>, String> {
+    let mut processor = DocumentProcessor::new(provider.clone())
         .map_err(|e| e.to_string())?;
 
     processor.process_document(file_path).await
@@ -0,0 +1,180 @@
+use crate::knowledge_base::knowledge_base::{EntryUpdate, KnowledgeBaseHandler, KnowledgeEntry, MANUAL_SOURCE};
+use colored::Colorize;
+
+const USAGE: &str = "Usage: kb list [tag] | kb show <id> | kb add <text> [--keywords a,b] [--tags a,b] | \
+    kb edit <id> [text] [--keywords a,b] [--tags a,b] | kb delete <id> | kb search <text> | \
+    kb tag <id> <tag> [-tag] ... | kb import <folder>";
+
+/// Results shown by `kb search`.
+const SEARCH_LIMIT: usize = 10;
+
+pub async fn handle_command(input: &str, knowledge_base: &KnowledgeBaseHandler) -> Result<(), String> {
+    let mut parts = input.splitn(3, char::is_whitespace);
+    let argument = parts.nth(2).map(str::trim).unwrap_or("");
+
+    match input.split_whitespace().nth(1) {
+        Some("list") => {
+            let tag = (!argument.is_empty()).then(|| argument.to_lowercase());
+            let entries: Vec<KnowledgeEntry> = knowledge_base.entries().await
+                .into_iter()
+                .filter(|entry| tag.as_ref().is_none_or(|tag| entry.tags.contains(tag)))
+                .collect();
+            if entries.is_empty() {
+                println!("The knowledge base has no entries{}.", tag.map(|t| format!(" tagged {}", t)).unwrap_or_default());
+                return Ok(());
+            }
+            println!("\n📖 {}", "Knowledge base:".bright_cyan());
+            for entry in &entries {
+                let first_line = entry.content.lines().next().unwrap_or("");
+                println!("  {} {}", entry.id.cyan(), preview(first_line, 70));
+                println!("        {}", labels(entry).dimmed());
+            }
+            println!();
+            Ok(())
+        }
+        Some("show") if !argument.is_empty() => {
+            let entry = knowledge_base.get(argument).await.ok_or_else(|| no_entry(argument))?;
+            println!("\n📖 {}", entry.id.bright_cyan());
+            println!("  {}", labels(&entry).dimmed());
+            println!("  {}", format!("added {} · updated {}",
+                entry.created_at.format("%Y-%m-%d %H:%M"),
+                entry.updated_at.format("%Y-%m-%d %H:%M")
+            ).dimmed());
+            println!("\n{}\n", entry.content);
+            Ok(())
+        }
+        Some("add") if !argument.is_empty() => {
+            let (text, keywords, tags) = split_options(argument);
+            if text.is_empty() {
+                return Err("Usage: kb add <text> [--keywords a,b] [--tags a,b]".to_string());
+            }
+            let mut entry = KnowledgeEntry::new(MANUAL_SOURCE, keywords.unwrap_or_default(), &text);
+            entry.tags = tags.unwrap_or_default();
+            let entry = knowledge_base.add(entry).await
+                .map_err(|e| format!("Failed to save the knowledge base: {}", e))?;
+            println!("📖 Added entry {}", entry.id.cyan());
+            Ok(())
+        }
+        Some("edit") if !argument.is_empty() => {
+            let (id, rest) = argument.split_once(char::is_whitespace).unwrap_or((argument, ""));
+            let (text, keywords, tags) = split_options(rest);
+            let update = EntryUpdate {
+                content: (!text.is_empty()).then_some(text),
+                keywords,
+                tags,
+            };
+            if update == EntryUpdate::default() {
+                return Err("Usage: kb edit <id> [text] [--keywords a,b] [--tags a,b]".to_string());
+            }
+            let entry = knowledge_base.update(id, update).await
+                .map_err(|e| format!("Failed to save the knowledge base: {}", e))?
+                .ok_or_else(|| no_entry(id))?;
+            println!("📖 Updated entry {}", entry.id.cyan());
+            Ok(())
+        }
+        Some("delete") if !argument.is_empty() => {
+            let entry = knowledge_base.delete(argument).await
+                .map_err(|e| format!("Failed to save the knowledge base: {}", e))?
+                .ok_or_else(|| no_entry(argument))?;
+            println!("🧹 Deleted entry {}", entry.id);
+            Ok(())
+        }
+        Some("search") if !argument.is_empty() => {
+            let hits = knowledge_base.search(argument, SEARCH_LIMIT).await;
+            if hits.is_empty() {
+                println!("No entries match \"{}\".", argument);
+                return Ok(());
+            }
+            println!("\n🔎 {}", format!("Entries for \"{}\":", argument).bright_cyan());
+            for hit in hits {
+                println!("  {} {} {}", hit.id.cyan(), format!("({:.2})", hit.score).dimmed(), hit.snippet);
+            }
+            println!();
+            Ok(())
+        }
+        Some("tag") if !argument.is_empty() => {
+            let mut words = argument.split_whitespace();
+            let id = words.next().unwrap_or_default();
+            let (remove, add): (Vec<String>, Vec<String>) = words.map(str::to_string).partition(|tag| tag.starts_with('-'));
+            let remove: Vec<String> = remove.iter().map(|tag| tag.trim_start_matches('-').to_string()).collect();
+            if add.is_empty() && remove.is_empty() {
+                return Err("Usage: kb tag <id> <tag> [-tag] ...".to_string());
+            }
+            let entry = knowledge_base.tag(id, &add, &remove).await
+                .map_err(|e| format!("Failed to save the knowledge base: {}", e))?
+                .ok_or_else(|| no_entry(id))?;
+            println!("🏷️ {} is tagged {}", entry.id.cyan(), if entry.tags.is_empty() { "with nothing".to_string() } else { entry.tags.join(", ") });
+            Ok(())
+        }
+        Some("import") if !argument.is_empty() => {
+            let report = knowledge_base.import_markdown(argument).await
+                .map_err(|e| format!("Failed to import {}: {}", argument, e))?;
+            println!("📥 Imported {}: {} added, {} updated, {} skipped",
+                argument,
+                report.added.len().to_string().cyan(),
+                report.updated.len().to_string().cyan(),
+                report.skipped.len()
+            );
+            for skipped in &report.skipped {
+                println!("  {}", skipped.yellow());
+            }
+            Ok(())
+        }
+        _ => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    }
+}
+
+fn no_entry(id: &str) -> String {
+    format!("No knowledge base entry {} (or the id prefix matches several)", id)
+}
+
+/// Source, keywords and tags of `entry` on one line.
+fn labels(entry: &KnowledgeEntry) -> String {
+    let mut labels = vec![entry.source.clone()];
+    if !entry.keywords.is_empty() {
+        labels.push(format!("keywords: {}", entry.keywords.join(", ")));
+    }
+    if !entry.tags.is_empty() {
+        labels.push(format!("tags: {}", entry.tags.join(", ")));
+    }
+    labels.join(" · ")
+}
+
+fn preview(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max_chars).collect::<String>().trim_end())
+    }
+}
+
+/// Splits `--keywords a,b` and `--tags a,b` off the text they follow.
+fn split_options(input: &str) -> (String, Option<Vec<String>>, Option<Vec<String>>) {
+    let mut text = Vec::new();
+    let mut keywords = None;
+    let mut tags = None;
+    let mut words = input.split_whitespace();
+    while let Some(word) = words.next() {
+        let target = match word {
+            "--keywords" => &mut keywords,
+            "--tags" => &mut tags,
+            _ => {
+                text.push(word);
+                continue;
+            }
+        };
+        let list: Vec<String> = words
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect();
+        *target = Some(list);
+    }
+    (text.join(" "), keywords, tags)
+}
+
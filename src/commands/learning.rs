@@ -0,0 +1,43 @@
+use crate::learning::Learner;
+use colored::Colorize;
+
+const USAGE: &str = "Usage: learn summary | learn forget <topic>";
+
+pub async fn handle_command(input: &str, learner: &Learner) -> Result<(), String> {
+    let mut parts = input.splitn(3, char::is_whitespace);
+    let argument = parts.nth(2).map(str::trim).unwrap_or("");
+
+    // Include what is still being learned from the last exchanges
+    learner.flush().await;
+    let manager = learner.manager();
+
+    match input.split_whitespace().nth(1) {
+        None | Some("summary") => {
+            let facts = manager.facts().await
+                .map_err(|e| format!("Failed to load learned facts: {}", e))?;
+            if facts.is_empty() {
+                println!("Nothing learned yet; facts are picked up from chats, documents and research.");
+                return Ok(());
+            }
+            println!("\n🎓 {}", "Learned facts:".bright_cyan());
+            for (topic, facts) in &facts {
+                println!("  {} {}", topic.cyan(), format!("({})", facts.len()).dimmed());
+                for fact in facts {
+                    println!("    • {} {}", fact.statement(), format!("{:.2}", fact.confidence).dimmed());
+                }
+            }
+            let total: usize = facts.iter().map(|(_, facts)| facts.len()).sum();
+            println!("\n  {} facts about {} topics\n", total.to_string().cyan(), facts.len().to_string().cyan());
+            Ok(())
+        }
+        Some("forget") if !argument.is_empty() => {
+            match manager.forget_topic(argument).await
+                .map_err(|e| format!("Failed to forget {}: {}", argument, e))? {
+                Some(topic) => println!("🧹 Forgot what was learned about {}", topic.cyan()),
+                None => println!("Nothing was learned about {}.", argument),
+            }
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
@@ -0,0 +1,127 @@
+use crate::database::{Database, MemoryRecord};
+use crate::memory::importance;
+use crate::memory::{LongTermMemory, ShortTermMemory};
+use chrono::Utc;
+use colored::Colorize;
+
+const USAGE: &str = "Usage: memory stats | memory summary | memory clear | memory list | memory search <text> | \
+    memory remember <text> | memory pin <id> | memory unpin <id> | memory share <id> | memory unshare <id> | \
+    memory forget <id> | memory reflect";
+
+pub async fn handle_command(
+    input: &str,
+    memory: &mut ShortTermMemory,
+    long_term_memory: &mut LongTermMemory,
+    db: &Database,
+    session: &str,
+) -> Result<(), String> {
+    let mut parts = input.splitn(3, char::is_whitespace);
+    let argument = parts.nth(2).map(str::trim).unwrap_or("");
+
+    match input.split_whitespace().nth(1) {
+        None | Some("stats") => {
+            println!("\n🧠 {}", "Short-term memory:".bright_cyan());
+            for line in memory.get_memory_stats().lines().skip(1) {
+                println!("  {}", line.trim_start_matches("- "));
+            }
+            let memories = long_term_memory.memories();
+            println!("\n📚 {}", "Long-term memory:".bright_cyan());
+            println!("  {} memories, {} pinned",
+                memories.len().to_string().cyan(),
+                memories.iter().filter(|m| m.pinned).count().to_string().cyan()
+            );
+            println!();
+            Ok(())
+        }
+        Some("summary") => {
+            if memory.summary().is_empty() {
+                println!("Nothing has been summarized yet; older turns are condensed once the conversation grows.");
+            } else {
+                println!("\n📝 {}", "Conversation summary:".bright_cyan());
+                println!("{}", memory.summary());
+                println!();
+            }
+            Ok(())
+        }
+        Some("clear") => {
+            db.delete_summary(session.to_string()).await
+                .map_err(|e| format!("Failed to clear conversation summary: {}", e))?;
+            let removed = memory.conversation_count();
+            memory.clear();
+            println!("🧹 Forgot {} conversation(s)", removed);
+            Ok(())
+        }
+        Some("list") => {
+            print_memories(long_term_memory.character(), long_term_memory.memories());
+            Ok(())
+        }
+        Some("search") if !argument.is_empty() => {
+            let found = long_term_memory.search(argument, 20).await
+                .map_err(|e| format!("Failed to search memories: {}", e))?;
+            print_memories(long_term_memory.character(), &found);
+            Ok(())
+        }
+        Some("remember") if !argument.is_empty() => {
+            let id = long_term_memory.add_memory("user", argument, 0.8).await
+                .map_err(|e| format!("Failed to save memory: {}", e))?;
+            println!("💾 Remembered as #{}", id);
+            Ok(())
+        }
+        Some(command @ ("pin" | "unpin" | "share" | "unshare" | "forget")) => {
+            let id: i64 = argument.trim_start_matches('#').parse()
+                .map_err(|_| format!("Usage: memory {} <id>", command))?;
+            let found = match command {
+                "forget" => long_term_memory.forget(id).await,
+                "share" | "unshare" => long_term_memory.set_shared(id, command == "share").await,
+                _ => long_term_memory.set_pinned(id, command == "pin").await,
+            }
+            .map_err(|e| format!("Failed to update memory: {}", e))?;
+
+            if !found {
+                return Err(format!("No memory #{} of yours for {}", id, long_term_memory.character()));
+            }
+            match command {
+                "pin" => println!("📌 Memory #{} will be recalled in every chat", id),
+                "unpin" => println!("Memory #{} unpinned", id),
+                "share" => println!("🌐 Memory #{} is now recalled for every user of {}", id, long_term_memory.character()),
+                "unshare" => println!("Memory #{} is private again", id),
+                _ => println!("🧹 Forgot memory #{}", id),
+            }
+            Ok(())
+        }
+        _ => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    }
+}
+
+/// Lists `memories` with their source, current strength after decay and
+/// the memories they were derived from.
+pub fn print_memories(character: &str, memories: &[MemoryRecord]) {
+    if memories.is_empty() {
+        println!("No memories found for {}.", character);
+        return;
+    }
+
+    let now = Utc::now();
+    println!("\n📚 {}", format!("{}'s memories:", character).bright_cyan());
+    for memory in memories {
+        let pin = if memory.pinned { "📌 " } else { "" };
+        let shared = if memory.shared { "🌐 " } else { "" };
+        println!("  #{:<4} {}{}{}", memory.id.to_string().cyan(), pin, shared, memory.content);
+        let derived = if memory.derived_from.is_empty() {
+            String::new()
+        } else {
+            let ids: Vec<String> = memory.derived_from.iter().map(|id| format!("#{}", id)).collect();
+            format!(" · from {}", ids.join(", "))
+        };
+        println!("        {} · strength {:.2} · {}{}",
+            memory.source.dimmed(),
+            importance::strength(memory, now),
+            memory.created_at.dimmed(),
+            derived.dimmed()
+        );
+    }
+    println!();
+}
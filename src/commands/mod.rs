@@ -1,26 +1,57 @@
 use colored::Colorize;
-use crate::providers::deepseek::deepseek::DeepSeekProvider;
+use futures::StreamExt;
+use std::io::Write;
+use std::sync::Arc;
+use crate::providers::registry::{ProviderRegistry, TASK_CHAT_LEARNING, TASK_CHAT_SUMMARY, TASK_DOC_INSIGHTS, TASK_MEMORY_REFLECTION};
 use crate::personality::PersonalityProfile;
 use crate::providers::twitter::manager::ConversationManager;
 use crate::providers::web_crawler::crawler_manager::WebCrawlerManager;
-use crate::completion::CompletionProvider;
+use crate::completion::{ChatMessage, Completion, CompletionProvider, ParamsProvider, StreamEvent, Usage};
+use crate::tokens;
 use crate::memory::{ShortTermMemory, LongTermMemory};
-use crate::database::Database;
+use crate::memory::long_term::{format_memories, RECALL_LIMIT};
+use crate::memory::reflection::{ReflectionConfig, Reflector};
+use crate::memory::summary::{summary_section, Summarizer, SummaryConfig};
+use crate::database::{Database, DEFAULT_DB_PATH, LOCAL_USER};
+use crate::usage::{cost_usd, BudgetLimits, Feature, UsageLedger};
+use crate::cache::{CacheConfig, CompletionCache};
+use crate::embeddings::{self, VectorStore};
+use crate::knowledge_base::knowledge_base::{cited, format_citations, KnowledgeBaseHandler, KnowledgeHit, KNOWLEDGE_LIMIT};
+use crate::learning::{format_facts, Learner, LearningConfig, LearningQueue};
+use crate::tools::{AnalyzeDocumentTool, FetchUrlTool, SearchInsightsTool, ToolRegistry};
 
 mod character;
 mod twitter;
 mod web;
 mod system;
 mod document;
+mod usage_report;
+mod cache;
+mod providers;
+mod memory;
+mod archive;
+mod knowledge;
+mod learning;
+
+#[cfg(test)]
+mod tests;
 
 pub struct CommandHandler {
     twitter_manager: Option<ConversationManager>,
     web_crawler: Option<WebCrawlerManager>,
-    deepseek_provider: DeepSeekProvider,
+    registry: ProviderRegistry,
+    provider: Arc<dyn CompletionProvider>,
     personality: PersonalityProfile,
     memory: ShortTermMemory,
     db: Database,
+    usage: UsageLedger,
+    cache: CompletionCache,
+    vectors: VectorStore,
+    summarizer: Summarizer,
+    reflector: Reflector,
     long_term_memory: LongTermMemory,
+    knowledge_base: KnowledgeBaseHandler,
+    learning: LearningQueue,
 }
 
 impl CommandHandler {
@@ -28,19 +59,44 @@ impl CommandHandler {
         personality: PersonalityProfile,
         twitter_manager: Option<ConversationManager>,
         web_crawler: Option<WebCrawlerManager>,
-        deepseek_provider: DeepSeekProvider,
+        registry: ProviderRegistry,
+        knowledge_base: KnowledgeBaseHandler,
     ) -> Result<Self, String> {
-        let db = Database::new("agent.db")
+        let db = Database::new(DEFAULT_DB_PATH)
             .await
             .map_err(|e| format!("Failed to initialize database: {}", e))?;
 
+        let provider = registry.for_character(&personality)
+            .map_err(|e| format!("Failed to initialize AI provider: {}", e))?;
+
+        let vectors = VectorStore::new(db.clone(), embeddings::from_env());
+        let long_term_memory = LongTermMemory::load(db.clone(), LOCAL_USER, &personality.name)
+            .await
+            .map_err(|e| format!("Failed to load memories: {}", e))?
+            .with_vectors(vectors.clone());
+
+        // Pick up where the last session with this character left off
+        let mut memory = ShortTermMemory::new();
+        if let Some(stored) = db.get_summary(cli_session(&personality.name)).await
+            .map_err(|e| format!("Failed to load conversation summary: {}", e))? {
+            memory.set_summary(stored.summary);
+        }
+
         Ok(Self {
             twitter_manager,
             web_crawler,
-            deepseek_provider,
+            registry,
+            provider,
             personality,
-            memory: ShortTermMemory::new(),
-            long_term_memory: LongTermMemory::new(),
+            memory,
+            long_term_memory,
+            usage: UsageLedger::new(db.clone(), BudgetLimits::from_env()),
+            cache: CompletionCache::new(CacheConfig::from_env(), Some(db.clone())),
+            vectors,
+            summarizer: Summarizer::new(SummaryConfig::from_env()),
+            reflector: Reflector::new(ReflectionConfig::from_env()),
+            learning: LearningQueue::spawn(db.clone(), knowledge_base.clone(), LearningConfig::from_env()),
+            knowledge_base,
             db,
         })
     }
@@ -55,6 +111,12 @@ impl CommandHandler {
         // Handle single-word commands first
         match input.to_lowercase().as_str() {
             "help" | "exit" | "quit" => return self.handle_system_command(input).await,
+            "usage" => return usage_report::handle_command(input, &self.usage).await,
+            "cache" => return cache::handle_command(input, &self.cache).await,
+            "providers" => return providers::handle_command(&self.registry),
+            "memory" => return self.handle_memory_command(input).await,
+            "kb" => return knowledge::handle_command(input, &self.knowledge_base).await,
+            "learn" => return learning::handle_command(input, &self.learner()).await,
             "chars" | "characters" | "load" => return self.handle_character_command(input).await,
             _ => {}
         }
@@ -64,14 +126,44 @@ impl CommandHandler {
             return self.handle_character_command(input).await;
         }
 
+        if input.starts_with("usage ") {
+            return usage_report::handle_command(input, &self.usage).await;
+        }
+
+        if input.starts_with("cache ") {
+            return cache::handle_command(input, &self.cache).await;
+        }
+
+        if input.starts_with("memory ") {
+            return self.handle_memory_command(input).await;
+        }
+
+        if input.starts_with("kb ") {
+            return knowledge::handle_command(input, &self.knowledge_base).await;
+        }
+
+        if input.starts_with("learn ") {
+            return learning::handle_command(input, &self.learner()).await;
+        }
+
+        if input.starts_with("export ") || input.starts_with("import ") {
+            return self.handle_archive_command(input).await;
+        }
+
         // Document commands
         if input.starts_with("doc ") {
+            let provider = self.provider_for(Feature::Doc)?;
+            let insight_provider = self.provider_for_task(Feature::Doc, TASK_DOC_INSIGHTS)?;
+            let learner = self.learner();
             return document::handle_command(
                 input, 
-                &self.deepseek_provider,
+                &provider,
+                &insight_provider,
                 &mut self.memory,
                 &mut self.long_term_memory,
-                &self.db
+                &self.db,
+                &self.vectors,
+                &learner
             ).await;
         }
 
@@ -95,6 +187,11 @@ impl CommandHandler {
             return self.handle_web_command(input).await;
         }
 
+        // Follow-up questions about analyzed pages and research
+        if let Some(rest) = input.strip_prefix("web ") {
+            return self.handle_web_command(rest.trim()).await;
+        }
+
         // Default to chat completion if no command matches
         self.handle_chat(input).await
     }
@@ -114,58 +211,309 @@ impl CommandHandler {
     }
 
     async fn handle_web_command(&mut self, input: &str) -> Result<(), String> {
+        let feature = if input.starts_with("research") { Feature::Research } else { Feature::Web };
+        let provider = self.provider_for(feature)?;
+        let learner = self.learner();
         web::handle_command(
             input, 
             &mut self.web_crawler, 
-            &self.deepseek_provider,
+            provider.as_ref(),
             &mut self.memory,
             &mut self.long_term_memory,
+            &self.vectors,
+            &learner,
         ).await
     }
 
     async fn handle_character_command(&mut self, input: &str) -> Result<(), String> {
         let result = character::handle_command(input, &mut self.personality);
         if result.is_ok() {
-            // Update DeepSeek provider with new personality
-            if let Err(e) = self.deepseek_provider.update_personality(
-                self.personality.generate_system_prompt()
-            ).await {
-                return Err(format!("Failed to update personality: {}", e));
+            // Rebuild the provider so it picks up the new personality (and its configured backend)
+            self.provider = self.registry.for_character(&self.personality)
+                .map_err(|e| format!("Failed to update personality: {}", e))?;
+
+            if self.long_term_memory.character() != self.personality.name {
+                self.reload_character_state().await?;
             }
         }
         result
     }
 
+    /// Loads the long-term memories and conversation summary of the current
+    /// character from the database.
+    async fn reload_character_state(&mut self) -> Result<(), String> {
+        self.long_term_memory = LongTermMemory::load(self.db.clone(), LOCAL_USER, &self.personality.name)
+            .await
+            .map_err(|e| format!("Failed to load memories: {}", e))?
+            .with_vectors(self.vectors.clone());
+
+        let summary = self.db.get_summary(cli_session(&self.personality.name)).await
+            .map_err(|e| format!("Failed to load conversation summary: {}", e))?;
+        self.memory.set_summary(summary.map(|s| s.summary).unwrap_or_default());
+        Ok(())
+    }
+
+    /// The provider configured for `feature`'s command, reusing the chat
+    /// provider unless the registry routes that command elsewhere. Calls made
+    /// through it are recorded in the usage ledger, and repeated requests are
+    /// served from the completion cache when it is enabled.
+    fn provider_for(&self, feature: Feature) -> Result<Arc<dyn CompletionProvider>, String> {
+        self.provider_for_task(feature, feature.command())
+    }
+
+    /// Like `provider_for`, for a task routed on its own (e.g. `doc.insights`),
+    /// falling back to the route of its command. Requests use the task's
+    /// generation params over the character's.
+    fn provider_for_task(&self, feature: Feature, task: &str) -> Result<Arc<dyn CompletionProvider>, String> {
+        let name = self.registry.name_for_command(task, &self.personality);
+        let provider = if name == self.registry.name_for_character(&self.personality) {
+            self.provider.clone()
+        } else {
+            self.registry
+                .build(name, self.personality.generate_system_prompt())
+                .map(Arc::from)
+                .map_err(|e| format!("Failed to initialize {} provider: {}", name, e))?
+        };
+        let provider = ParamsProvider::wrap(provider, self.registry.params_for_command(task, &self.personality));
+
+        let metered = Arc::new(self.usage.meter(provider, feature, self.personality.name.clone()));
+        Ok(self.cache.wrap(metered))
+    }
+
+    /// Learns from the local user's exchanges with the current character,
+    /// extracting facts with the `chat.learning` route, or offline when its
+    /// provider cannot be built.
+    fn learner(&self) -> Learner {
+        let provider = self.provider_for_task(Feature::Chat, TASK_CHAT_LEARNING)
+            .map_err(|e| log::warn!("Learning without a model: {}", e))
+            .ok();
+        self.learning.learner(LOCAL_USER, &self.personality.name, provider)
+    }
+
+    /// Waits for learning from the last exchanges to finish.
+    pub async fn finish_learning(&self) {
+        self.learning.flush().await;
+    }
+
     async fn handle_system_command(&mut self, input: &str) -> Result<(), String> {
+        if !input.eq_ignore_ascii_case("help") {
+            // Exiting ends the process, so finish learning first
+            self.finish_learning().await;
+        }
         system::handle_command(input)
     }
 
     async fn handle_chat(&mut self, input: &str) -> Result<(), String> {
         // Count input tokens
-        let input_tokens = input.split_whitespace().count();
+        let input_tokens = tokens::count_tokens(input);
         println!("📥 Input tokens: {}", input_tokens.to_string().cyan());
 
-        // Get response from AI
-        match self.deepseek_provider.complete(input).await {
-            Ok(response) => {
-                let response_tokens = response.split_whitespace().count();
-                self.print_response("", &response, input_tokens, response_tokens);
-                Ok(())
+        let provider = self.provider_for(Feature::Chat)?;
+        let learner = self.learner();
+        let mut system = provider.system_message().to_string();
+        let recalled = self.long_term_memory.recall(input, RECALL_LIMIT).await;
+        if !recalled.is_empty() {
+            system.push_str(&format!("\n\nThings you remember:\n{}", format_memories(&recalled)));
+        }
+        let learned = learner.relevant_context(input).await;
+        if !learned.is_empty() {
+            system.push_str(&format!("\n\nThings you have learned:\n{}", format_facts(&learned)));
+        }
+        let notes = self.knowledge_base.search(input, KNOWLEDGE_LIMIT).await;
+        if !notes.is_empty() {
+            system.push_str(&format!("\n\n{}", format_citations(&notes)));
+        }
+        if let Some(summary) = summary_section(self.memory.summary()) {
+            system.push_str(&format!("\n\n{}", summary));
+        }
+        let context = self.memory.get_context(input);
+        if !context.is_empty() {
+            system.push_str(&format!("\n\nEarlier in this conversation:\n{}", context));
+        }
+        let messages = [ChatMessage::system(system), ChatMessage::user(input)];
+        let messages = tokens::fit_to_context(provider.as_ref(), &messages);
+
+        // Let the model use tools when it can; otherwise stream the reply as it is generated
+        let reply = if provider.supports_tools() {
+            self.chat_with_tools(provider.as_ref(), &messages).await?
+        } else {
+            Self::stream_reply(provider.as_ref(), &messages).await?
+        };
+        self.memory.add_interaction(input, &reply.content);
+        learner.observe(input, &reply.content);
+        Self::print_sources(&reply.content, &notes);
+
+        self.print_token_usage(&reply.usage, cost_usd(provider.model(), &reply.usage));
+        self.summarize_history().await;
+
+        if let Err(e) = self.long_term_memory.observe("chat", input).await {
+            log::warn!("Failed to save memory: {}", e);
+        }
+        match self.reflect(false).await {
+            Ok(derived) if !derived.is_empty() => {
+                println!("💭 {}", format!("Formed {} new reflection(s); see `memory list`", derived.len()).dimmed());
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("{}", e),
+        }
+        Ok(())
+    }
+
+    /// Lets the character draw conclusions from what it has recently
+    /// remembered: when enough has piled up, or whenever there is anything
+    /// new if `force` is set. Returns the ids of the new memories.
+    async fn reflect(&mut self, force: bool) -> Result<Vec<i64>, String> {
+        let provider = self.provider_for_task(Feature::Chat, TASK_MEMORY_REFLECTION)?;
+        self.reflector
+            .reflect(&self.db, provider.as_ref(), &mut self.long_term_memory, force)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Folds the oldest conversations into the running summary once the
+    /// session outgrows its history budget, and saves the summary so the
+    /// next session with this character starts from it.
+    async fn summarize_history(&mut self) {
+        let turns: Vec<(String, String)> = self.memory
+            .recent(usize::MAX)
+            .map(|c| (c.user_input.clone(), c.ai_response.clone()))
+            .collect();
+        let fold = self.summarizer.turns_to_fold(self.memory.summary(), &turns);
+        if fold == 0 {
+            return;
+        }
+
+        let summary = match self.provider_for_task(Feature::Chat, TASK_CHAT_SUMMARY) {
+            Ok(provider) => self.summarizer.fold(provider.as_ref(), self.memory.summary(), &turns[..fold]).await,
+            Err(e) => {
+                log::warn!("Failed to summarize conversation: {}", e);
+                return;
+            }
+        };
+        match summary {
+            Ok(summary) => {
+                if let Err(e) = self.db.save_summary(cli_session(&self.personality.name), summary.clone(), 0).await {
+                    log::warn!("Failed to save conversation summary: {}", e);
+                }
+                self.memory.fold_oldest(fold, summary);
+            }
+            Err(e) => log::warn!("Failed to summarize conversation: {}", e),
+        }
+    }
+
+    async fn handle_memory_command(&mut self, input: &str) -> Result<(), String> {
+        if input.split_whitespace().nth(1) == Some("reflect") {
+            let derived = self.reflect(true).await?;
+            if derived.is_empty() {
+                println!("Nothing new to reflect on.");
+            } else {
+                let memories: Vec<_> = self.long_term_memory
+                    .memories()
+                    .iter()
+                    .filter(|m| derived.contains(&m.id))
+                    .cloned()
+                    .collect();
+                memory::print_memories(self.long_term_memory.character(), &memories);
+            }
+            return Ok(());
+        }
+
+        let session = cli_session(&self.personality.name);
+        memory::handle_command(input, &mut self.memory, &mut self.long_term_memory, &self.db, &session).await
+    }
+
+    async fn handle_archive_command(&mut self, input: &str) -> Result<(), String> {
+        let imported = archive::handle_command(input, &self.db, &self.vectors, &self.knowledge_base, &self.personality.name).await?;
+        if imported {
+            self.reload_character_state().await?;
+        }
+        Ok(())
+    }
+
+    /// Tools the chat model may call: fetching pages (when the crawler is
+    /// enabled), analyzing documents and searching saved insights.
+    fn tools(&self) -> Result<ToolRegistry, String> {
+        let mut tools = ToolRegistry::new();
+        if let Some(crawler) = &self.web_crawler {
+            tools.register(Arc::new(FetchUrlTool::new(crawler.clone())));
+        }
+        tools.register(Arc::new(AnalyzeDocumentTool::new(self.provider_for_task(Feature::Doc, TASK_DOC_INSIGHTS)?)));
+        tools.register(Arc::new(SearchInsightsTool::new(self.db.clone())));
+        Ok(tools)
+    }
+
+    async fn chat_with_tools(&self, provider: &dyn CompletionProvider, messages: &[ChatMessage]) -> Result<Completion, String> {
+        let run = self.tools()?
+            .run(
+                provider,
+                messages,
+                |delta| {
+                    print!("{}", delta.truecolor(255, 236, 179));
+                    std::io::stdout().flush().ok();
+                },
+                |call| {
+                    println!("🔧 {} {}", call.function.name.bright_blue(), call.function.arguments.dimmed());
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to get AI response: {}", e))?;
+        println!();
+
+        Ok(Completion::new(run.completion.content, run.usage))
+    }
+
+    async fn stream_reply(provider: &dyn CompletionProvider, messages: &[ChatMessage]) -> Result<Completion, String> {
+        let mut stream = provider
+            .complete_stream(messages)
+            .await
+            .map_err(|e| format!("Failed to get AI response: {}", e))?;
+
+        let mut response = String::new();
+        let mut usage = None;
+        while let Some(event) = stream.next().await {
+            match event.map_err(|e| format!("Failed to get AI response: {}", e))? {
+                StreamEvent::Delta(delta) => {
+                    print!("{}", delta.truecolor(255, 236, 179));
+                    std::io::stdout().flush().ok();
+                    response.push_str(&delta);
+                }
+                StreamEvent::Usage(reported) => usage = Some(reported),
+                // Only offered tools can be called, and none were
+                StreamEvent::ToolCalls(_) => {}
             }
-            Err(e) => Err(format!("Failed to get AI response: {}", e))
+        }
+        println!();
+
+        let usage = usage.unwrap_or_else(|| Usage::estimate(messages, &response));
+        Ok(Completion::new(response, usage))
+    }
+
+    /// Lists the knowledge base entries the reply cites.
+    fn print_sources(reply: &str, notes: &[KnowledgeHit]) {
+        let cited = cited(reply, notes);
+        if cited.is_empty() {
+            return;
+        }
+        println!("\n📚 {}", "Sources:".bright_cyan());
+        for (number, hit) in cited {
+            println!("  [{}] {} · {}", number, hit.id.dimmed(), hit.snippet);
         }
     }
 
-    fn print_response(&self, _character_name: &str, response: &str, input_tokens: usize, response_tokens: usize) {
-        println!("{}", response.truecolor(255, 236, 179));
-        
-        println!("\n📊 Tokens: 📥 Input: {} | 📤 Response: {} | 📈 Total: {}", 
-            input_tokens.to_string().cyan(),
-            response_tokens.to_string().cyan(),
-            (input_tokens + response_tokens).to_string().cyan()
+    fn print_token_usage(&self, usage: &Usage, cost_usd: f64) {
+        println!("\n📊 Tokens: 📥 Input: {} | 📤 Response: {} | 📈 Total: {} | 💰 ${:.4}", 
+            usage.prompt_tokens.to_string().cyan(),
+            usage.completion_tokens.to_string().cyan(),
+            usage.total_tokens().to_string().cyan(),
+            cost_usd
         );
         println!();
     }
 }
 
+/// Key of the CLI conversation summary kept for `character`.
+fn cli_session(character: &str) -> String {
+    format!("cli:{}", character)
+}
+
 pub use document::handle_command as handle_document_command;
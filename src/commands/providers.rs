@@ -0,0 +1,52 @@
+use crate::providers::registry::ProviderRegistry;
+use crate::providers::router::CircuitState;
+use colored::Colorize;
+
+pub fn handle_command(registry: &ProviderRegistry) -> Result<(), String> {
+    println!("\n🔀 {}", "Providers:".bright_cyan());
+    let default = registry.default_name();
+    let fallbacks = registry.fallbacks(default);
+    if fallbacks.is_empty() {
+        println!("  Default: {}", default.bright_yellow());
+    } else {
+        println!("  Default: {} → {}", default.bright_yellow(), fallbacks.join(" → "));
+    }
+
+    let routes = registry.routes();
+    if !routes.is_empty() {
+        println!("  Routes:");
+        for (task, name) in routes {
+            let fallbacks = registry.fallbacks(name);
+            if fallbacks.is_empty() {
+                println!("    {:<14} {}", task, name.bright_yellow());
+            } else {
+                println!("    {:<14} {} → {}", task, name.bright_yellow(), fallbacks.join(" → "));
+            }
+        }
+    }
+
+    let health = registry.health().snapshot();
+    if health.is_empty() {
+        println!("  Health: nothing has gone through a fallback chain yet");
+    } else {
+        println!("  Health:");
+        for provider in health {
+            let state = match provider.state {
+                CircuitState::Closed => "ok".bright_green(),
+                CircuitState::Open => "down".bright_red(),
+                CircuitState::HalfOpen => "recovering".yellow(),
+            };
+            println!("    {:<14} {:<10} {} ok, {} failed",
+                provider.name,
+                state,
+                provider.successes.to_string().cyan(),
+                provider.failures.to_string().cyan()
+            );
+            if let (CircuitState::Open | CircuitState::HalfOpen, Some(error)) = (provider.state, &provider.last_error) {
+                println!("      last error: {}", error.dimmed());
+            }
+        }
+    }
+    println!();
+    Ok(())
+}
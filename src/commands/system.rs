@@ -31,11 +31,46 @@ pub fn handle_command(input: &str) -> Result<(), String> {
             println!("  analyze <url>    - Analyze webpage content");
             println!("  research <topic> - Research a topic");
             println!("  links <url>      - Extract links from webpage");
+            println!("  web chat <text>  - Ask about analyzed pages and research");
             println!();
 
             println!("⚙️ {}", "System Commands:".bright_green());
-            println!("  help  - Show this help menu");
-            println!("  exit  - Exit the program");
+            println!("  help          - Show this help menu");
+            println!("  usage [days]  - Show token usage and spend (default 7 days)");
+            println!("  cache stats   - Show completion cache hits and size");
+            println!("  cache clear   - Remove all cached completions");
+            println!("  providers     - Show provider routes and health");
+            println!("  memory stats  - Show what the chat remembers this session");
+            println!("  memory summary - Show the summary of older conversation turns");
+            println!("  memory clear  - Forget this session's conversation");
+            println!();
+
+            println!("🧠 {}", "Memory Commands:".bright_cyan());
+            println!("  memory list             - List the character's long-term memories");
+            println!("  memory search <text>    - Search long-term memories");
+            println!("  memory remember <text>  - Save a long-term memory");
+            println!("  memory pin <id>         - Recall a memory in every chat");
+            println!("  memory unpin <id>       - Stop always recalling a memory");
+            println!("  memory share <id>       - Recall a memory for every user of the character");
+            println!("  memory unshare <id>     - Keep a memory to yourself again");
+            println!("  memory forget <id>      - Delete a memory");
+            println!("  memory reflect          - Draw conclusions from recent memories");
+            println!("  learn summary           - List the facts learned from chats, documents and research");
+            println!("  learn forget <topic>    - Forget what was learned about a topic");
+            println!("  export <file> [--all]   - Save this character's data (or everything) to an archive");
+            println!("  import <file> [--replace] - Merge an archive in, or replace what it covers");
+            println!("  exit          - Exit the program");
+            println!();
+
+            println!("📖 {}", "Knowledge Base Commands:".bright_cyan());
+            println!("  kb list [tag]             - List entries, optionally only those with a tag");
+            println!("  kb show <id>              - Show an entry");
+            println!("  kb add <text> [--keywords a,b] [--tags a,b] - Add an entry");
+            println!("  kb edit <id> [text] [--keywords a,b] [--tags a,b] - Change an entry");
+            println!("  kb delete <id>            - Delete an entry");
+            println!("  kb search <text>          - Rank entries against a query");
+            println!("  kb tag <id> <tag> [-tag]  - Add tags, or remove those prefixed with -");
+            println!("  kb import <folder>        - Add the Markdown files in a folder");
             println!();
 
             println!("\n📄 {}", "Document Commands:".bright_cyan());
@@ -45,6 +80,10 @@ pub fn handle_command(input: &str) -> Result<(), String> {
             println!("  doc ocr <image>      - Extract text from image");
             println!("  doc batch <folder>   - Process multiple files");
             println!("  doc info <file>      - Show file information");
+            println!("  doc ingest <path>    - Index a file or folder for doc chat");
+            println!("  doc list             - List indexed documents");
+            println!("  doc forget <file>    - Drop a document from the index");
+            println!("  doc chat <question>  - Ask about indexed documents, with citations");
 
             Ok(())
         }
@@ -0,0 +1,123 @@
+use super::{document, knowledge, learning};
+use crate::knowledge_base::knowledge_base::KnowledgeBaseHandler;
+use crate::completion::{ChatMessage, CompletionProvider};
+use crate::database::{Database, LOCAL_USER};
+use crate::embeddings::{HashEmbedder, VectorStore};
+use crate::learning::{Learner, LearningConfig, LearningQueue};
+use crate::memory::{LongTermMemory, ShortTermMemory};
+use crate::providers::mock::mock::MockProvider;
+use std::io::Write;
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+
+/// A learner for the local user and Nova, learning offline into `db`, with
+/// a knowledge base kept in `dir`.
+async fn learner(db: &Database, dir: &tempfile::TempDir) -> Learner {
+    let path = dir.path().join("knowledge_base.json");
+    let knowledge_base = KnowledgeBaseHandler::load(path.to_str().unwrap()).await.unwrap();
+    LearningQueue::spawn(db.clone(), knowledge_base, LearningConfig::default()).learner(LOCAL_USER, "Nova", None)
+}
+
+#[tokio::test]
+async fn test_doc_analyze_then_chat_uses_document_context() {
+    let mut file = NamedTempFile::with_suffix(".txt").unwrap();
+    writeln!(file, "The launch is scheduled for March.").unwrap();
+    let path = file.path().to_str().unwrap().to_string();
+
+    let mock = Arc::new(MockProvider::new([
+        r#"{"insights": [{"text": "Launch is in March", "relevance": 0.9}]}"#,
+        "A spring launch, how exciting!",
+        "The launch is planned for March.",
+    ]).with_system_message("You are Nova."));
+    let provider: Arc<dyn CompletionProvider> = mock.clone();
+    let mut memory = ShortTermMemory::new();
+    let db = Database::new(":memory:").await.unwrap();
+    let mut long_term_memory = LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap();
+    let vectors = VectorStore::new(db.clone(), Arc::new(HashEmbedder::default()));
+    let dir = tempfile::tempdir().unwrap();
+    let learner = learner(&db, &dir).await;
+
+    document::handle_command(&format!("doc analyze {}", path), &provider, &provider, &mut memory, &mut long_term_memory, &db, &vectors, &learner)
+        .await
+        .unwrap();
+    assert_eq!(memory.conversation_count(), 1);
+    assert!(mock.last_prompt().unwrap().contains("• Launch is in March"));
+    let remembered = &long_term_memory.memories()[0];
+    assert_eq!(remembered.source, format!("document:{}", path));
+    assert_eq!(remembered.content, "Launch is in March");
+
+    document::handle_command("doc chat when is the launch?", &provider, &provider, &mut memory, &mut long_term_memory, &db, &vectors, &learner)
+        .await
+        .unwrap();
+
+    let chat = mock.requests().pop().unwrap();
+    assert!(chat[0].content.starts_with("You are Nova."));
+    let name = file.path().file_name().unwrap().to_str().unwrap();
+    assert!(chat[0].content.contains(&format!("[1] ({} line 1)\nThe launch is scheduled for March.", name)));
+    assert_eq!(chat[1], ChatMessage::user(format!("Document being discussed: {}", path)));
+    assert!(chat[2].content.contains("Launch is in March"));
+    assert_eq!(chat[3], ChatMessage::user("when is the launch?"));
+    assert_eq!(memory.recent(1).next().unwrap().ai_response, "The launch is planned for March.");
+
+    // Both exchanges were learned from
+    learner.flush().await;
+    let facts = learner.manager().facts().await.unwrap();
+    let statements: Vec<String> = facts.iter().flat_map(|(_, facts)| facts.iter().map(|f| f.statement())).collect();
+    assert_eq!(statements, vec!["Launch is in March", "launch is planned for March"]);
+}
+
+#[tokio::test]
+async fn test_doc_chat_surfaces_provider_errors() {
+    let provider: Arc<dyn CompletionProvider> = Arc::new(MockProvider::new(Vec::<String>::new()));
+    let mut memory = ShortTermMemory::new();
+    let db = Database::new(":memory:").await.unwrap();
+    let mut long_term_memory = LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap();
+    let vectors = VectorStore::new(db.clone(), Arc::new(HashEmbedder::default()));
+    let dir = tempfile::tempdir().unwrap();
+    let learner = learner(&db, &dir).await;
+
+    let err = document::handle_command("doc chat hello", &provider, &provider, &mut memory, &mut long_term_memory, &db, &vectors, &learner)
+        .await
+        .unwrap_err();
+    assert!(err.starts_with("Failed to get response"));
+    assert_eq!(memory.conversation_count(), 0);
+}
+
+#[tokio::test]
+async fn test_kb_add_reads_keywords_and_tags() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("knowledge_base.json");
+    let knowledge_base = KnowledgeBaseHandler::load(path.to_str().unwrap()).await.unwrap();
+
+    knowledge::handle_command("kb add Tokio runs async tasks --keywords tokio,async --tags rust", &knowledge_base)
+        .await
+        .unwrap();
+
+    let entries = knowledge_base.entries().await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].content, "Tokio runs async tasks");
+    assert_eq!(entries[0].keywords, vec!["tokio", "async"]);
+    assert_eq!(entries[0].tags, vec!["rust"]);
+
+    let id = entries[0].id.clone();
+    let err = knowledge::handle_command("kb edit nope --tags x", &knowledge_base).await.unwrap_err();
+    assert!(err.starts_with("No knowledge base entry nope"));
+    knowledge::handle_command(&format!("kb delete {}", id), &knowledge_base).await.unwrap();
+    assert!(knowledge_base.entries().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_learn_forget_drops_a_topic() {
+    let db = Database::new(":memory:").await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let learner = learner(&db, &dir).await;
+    learner.observe("Tell me about Tokio", "Tokio runs async tasks on a thread pool.");
+
+    learning::handle_command("learn summary", &learner).await.unwrap();
+    assert_eq!(learner.manager().facts().await.unwrap().len(), 1);
+    learning::handle_command("learn forget Tokio", &learner).await.unwrap();
+    assert!(learner.manager().facts().await.unwrap().is_empty());
+
+    let err = learning::handle_command("learn forget", &learner).await.unwrap_err();
+    assert!(err.starts_with("Usage: learn"));
+}
@@ -0,0 +1,51 @@
+use crate::database::UsageTotals;
+use crate::usage::UsageLedger;
+use colored::Colorize;
+
+const DEFAULT_DAYS: u32 = 7;
+
+pub async fn handle_command(input: &str, ledger: &UsageLedger) -> Result<(), String> {
+    let days = match input.split_whitespace().nth(1) {
+        Some(days) => days.parse::<u32>()
+            .ok()
+            .filter(|days| *days > 0)
+            .ok_or("Usage: usage [days]")?,
+        None => DEFAULT_DAYS,
+    };
+
+    let report = ledger.report(days).await
+        .map_err(|e| format!("Failed to load usage: {}", e))?;
+
+    println!("\n💰 {}", format!("Usage for the last {} day(s):", days).bright_cyan());
+    println!("  Requests: {} | 📥 Input: {} | 📤 Response: {} | Cost: {}",
+        report.requests.to_string().cyan(),
+        report.prompt_tokens.to_string().cyan(),
+        report.completion_tokens.to_string().cyan(),
+        format!("${:.4}", report.cost_usd).bright_green()
+    );
+    match report.daily_budget_usd {
+        Some(budget) => println!("  Today: ${:.4} of ${:.2} daily budget", report.spent_today_usd, budget),
+        None => println!("  Today: ${:.4} (no daily budget set)", report.spent_today_usd),
+    }
+
+    print_totals("By day", &report.by_day);
+    print_totals("By character", &report.by_character);
+    print_totals("By feature", &report.by_feature);
+    println!();
+    Ok(())
+}
+
+fn print_totals(title: &str, totals: &[UsageTotals]) {
+    println!("\n  {}", title.bright_yellow());
+    if totals.is_empty() {
+        println!("    (no usage recorded)");
+    }
+    for total in totals {
+        println!("    {:<24} {:>5} calls {:>9} tokens  ${:.4}",
+            total.key,
+            total.requests,
+            total.prompt_tokens + total.completion_tokens,
+            total.cost_usd
+        );
+    }
+}
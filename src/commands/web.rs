@@ -1,15 +1,23 @@
 use crate::providers::web_crawler::crawler_manager::WebCrawlerManager;
-use crate::completion::CompletionProvider;
-use crate::providers::deepseek::deepseek::DeepSeekProvider;
+use crate::completion::{ChatMessage, CompletionProvider};
+use crate::tokens;
 use crate::memory::{ShortTermMemory, LongTermMemory};
+use crate::embeddings::VectorStore;
+use crate::embeddings::store::RESEARCH_NAMESPACE;
+use crate::learning::Learner;
 use colored::Colorize;
 
+/// Research findings recalled into a `chat` follow-up prompt.
+const SIMILAR_FINDINGS: usize = 5;
+
 pub async fn handle_command(
     input: &str,
     crawler: &mut Option<WebCrawlerManager>,
-    provider: &DeepSeekProvider,
+    provider: &dyn CompletionProvider,
     memory: &mut ShortTermMemory,
     long_term_memory: &mut LongTermMemory,
+    vectors: &VectorStore,
+    learner: &Learner,
 ) -> Result<(), String> {
     if let Some(crawler) = crawler {
         match input {
@@ -30,14 +38,16 @@ pub async fn handle_command(
                     &format!("Content:\n{}", content)
                 );
 
-                // Create personality-aware analysis prompt
-                let analysis_prompt = format!(
+                // Create personality-aware analysis prompt, trimming the page to fit the model
+                let analysis_prompt = |content: &str| format!(
                     "{}\n\nAs this character, analyze this webpage content and provide your unique perspective. \
                     Consider your personality traits and expertise when providing this analysis. \
                     Be creative and stay true to your character's style:\n\n{}",
-                    provider.get_system_message(),
+                    provider.system_message(),
                     content
                 );
+                let content = tokens::fit_text_for(provider, &analysis_prompt(""), &content);
+                let analysis_prompt = analysis_prompt(&content);
 
                 let analysis = provider.complete(&analysis_prompt).await
                     .map_err(|e| format!("Failed to analyze content: {}", e))?;
@@ -47,6 +57,7 @@ pub async fn handle_command(
                     &format!("Analysis of webpage: {}", url),
                     &analysis
                 );
+                learner.observe(&format!("Content of {}:\n{}", url, content), &analysis);
 
                 println!("\n📊 Analysis Results for {}:", url.bright_yellow());
                 println!("{}", analysis.truecolor(255, 236, 179));
@@ -72,8 +83,17 @@ pub async fn handle_command(
                     &format!("Research findings:\n{}", results.join("\n"))
                 );
 
+                // Embed the findings so follow-up questions can find them by meaning
+                let items = results.iter()
+                    .enumerate()
+                    .map(|(i, finding)| (format!("{}#{}", topic, i), finding.clone()))
+                    .collect();
+                if let Err(e) = vectors.index(RESEARCH_NAMESPACE, items).await {
+                    eprintln!("Warning: Failed to embed research findings: {}", e);
+                }
+
                 // Create personality-aware research prompt with better structure
-                let research_prompt = format!(
+                let research_prompt = |results: &str| format!(
                     "{}\n\n\
                     As this character, analyze and synthesize the research about '{}'in your unique style. \
                     Structure your response in these sections:\n\
@@ -82,10 +102,12 @@ pub async fn handle_command(
                     Keep each section focused and concise. \
                     Stay true to your character's expertise and communication style.\n\n\
                     Research content (1 - 5 points) and then make summarize,short and concise with your style:\n{}", 
-                    provider.get_system_message(),
+                    provider.system_message(),
                     topic,
-                    results.join("\n")
+                    results
                 );
+                let results = tokens::fit_text_for(provider, &research_prompt(""), &results.join("\n"));
+                let research_prompt = research_prompt(&results);
 
                 let analysis = provider.complete(&research_prompt).await
                     .map_err(|e| format!("Failed to synthesize research: {}", e))?;
@@ -95,6 +117,7 @@ pub async fn handle_command(
                     &format!("Research analysis: {}", topic),
                     &analysis
                 );
+                learner.observe(&format!("Research findings about {}:\n{}", topic, results), &analysis);
 
                 println!("\n📚 Research Results for '{}':", topic.bright_yellow());
                 println!("{}", analysis.truecolor(255, 236, 179));
@@ -125,31 +148,35 @@ pub async fn handle_command(
             s if s.starts_with("chat ") => {
                 let query = s.trim_start_matches("chat ").trim();
                 
-                // Get recent context from memory
-                let context = memory.get_interactions()
-                    .iter()
-                    .take(5)
-                    .map(|(input, response)| format!("Context: {}\nResponse: {}", input, response))
-                    .collect::<Vec<_>>()
-                    .join("\n\n");
-
-                // Create chat prompt with context
-                let chat_prompt = format!(
-                    "{}\n\n\
-                    Previous context:\n{}\n\n\
-                    User question: {}\n\n\
-                    Answer the question based on the previous context while maintaining your character's personality. \
-                    Keep your response focused and relevant to the topic being discussed.",
-                    provider.get_system_message(),
-                    context,
-                    query
+                // Replay recent web context as prior chat turns, with the
+                // research findings closest to the question
+                let mut system = format!(
+                    "{}\n\nAnswer the user's questions based on the previous context while maintaining \
+                    your character's personality. Keep your response focused and relevant to the topic being discussed.",
+                    provider.system_message()
                 );
+                match vectors.search(RESEARCH_NAMESPACE, query, SIMILAR_FINDINGS).await {
+                    Ok(similar) if !similar.is_empty() => {
+                        system.push_str("\n\nRelevant research findings:\n");
+                        system.push_str(&similar.iter().map(|m| format!("• {}", m.content)).collect::<Vec<_>>().join("\n"));
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Warning: Failed to search research findings: {}", e),
+                }
+                let mut messages = vec![ChatMessage::system(system)];
+                for conv in memory.recent(5) {
+                    messages.push(ChatMessage::user(conv.user_input.as_str()));
+                    messages.push(ChatMessage::assistant(conv.ai_response.as_str()));
+                }
+                messages.push(ChatMessage::user(query));
 
-                let response = provider.complete(&chat_prompt).await
+                let messages = tokens::fit_to_context(provider, &messages);
+                let response = provider.complete_messages(&messages).await
                     .map_err(|e| format!("Failed to get response: {}", e))?;
 
                 // Store the chat interaction
                 memory.add_interaction(query, &response);
+                learner.observe(query, &response);
 
                 println!("\n💬 Response:");
                 println!("{}", response.bright_green());
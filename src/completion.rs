@@ -1,17 +1,119 @@
 // src/completion.rs
+use futures::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::tokens;
 
 #[derive(Debug)]
 pub enum CompletionError {
     ApiError(String),
+    Config(String),
+    /// HTTP 429. `retry_after` comes from the `Retry-After` header when present.
+    RateLimited { retry_after: Option<Duration>, message: String },
+    /// HTTP 401/403: missing, invalid or unauthorized API key.
+    AuthFailed(String),
+    /// The prompt plus requested output exceeds the model's context window.
+    ContextTooLong(String),
+    /// HTTP 5xx from the provider.
+    ServerError { status: u16, message: String },
+    /// A successful response whose body could not be understood.
+    MalformedResponse(String),
+    /// No response within the configured request timeout.
+    Timeout,
+    /// Refused locally because a daily spending limit has been reached.
+    BudgetExceeded(String),
+    /// Every provider in a fallback chain is failing or has its circuit open.
+    Unavailable(String),
     Other(Box<dyn Error + Send + Sync>), // Ensure the inner error is Send + Sync
 }
 
+impl CompletionError {
+    /// Classifies a non-success HTTP response from a provider.
+    pub fn from_status(status: u16, body: &str, retry_after: Option<Duration>) -> Self {
+        let message = error_message(body);
+        match status {
+            429 => CompletionError::RateLimited { retry_after, message },
+            401 | 403 => CompletionError::AuthFailed(message),
+            400 | 413 if is_context_length_error(&message) => CompletionError::ContextTooLong(message),
+            500..=599 => CompletionError::ServerError { status, message },
+            _ => CompletionError::ApiError(format!("Request failed with status {}: {}", status, message)),
+        }
+    }
+
+    /// Whether repeating the same request may succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            CompletionError::RateLimited { .. } | CompletionError::ServerError { .. } | CompletionError::Timeout
+        )
+    }
+
+    /// Whether the error says the provider is unhealthy rather than that the
+    /// request was bad, so another provider could answer it.
+    pub fn is_provider_failure(&self) -> bool {
+        matches!(
+            self,
+            CompletionError::RateLimited { .. }
+                | CompletionError::ServerError { .. }
+                | CompletionError::Timeout
+                | CompletionError::AuthFailed(_)
+                | CompletionError::MalformedResponse(_)
+                | CompletionError::Other(_)
+        )
+    }
+
+    /// How long the provider asked us to wait before retrying, if it said.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            CompletionError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Pulls the human-readable message out of the usual `{"error": {"message": ...}}`
+/// or `{"error": "..."}` bodies, falling back to the raw body.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| {
+            let error = json.get("error")?;
+            error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .or_else(|| error.as_str())
+                .map(String::from)
+        })
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+fn is_context_length_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["context length", "context_length", "maximum context", "context window", "too many tokens"]
+        .iter()
+        .any(|needle| message.contains(needle))
+}
+
 impl fmt::Display for CompletionError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CompletionError::ApiError(msg) => write!(f, "API Error: {}", msg),
+            CompletionError::Config(msg) => write!(f, "Configuration Error: {}", msg),
+            CompletionError::RateLimited { retry_after: Some(delay), message } => {
+                write!(f, "Rate limited (retry after {}s): {}", delay.as_secs(), message)
+            }
+            CompletionError::RateLimited { retry_after: None, message } => write!(f, "Rate limited: {}", message),
+            CompletionError::AuthFailed(msg) => write!(f, "Authentication failed: {}", msg),
+            CompletionError::ContextTooLong(msg) => write!(f, "Context too long: {}", msg),
+            CompletionError::ServerError { status, message } => write!(f, "Server error ({}): {}", status, message),
+            CompletionError::MalformedResponse(msg) => write!(f, "Malformed response: {}", msg),
+            CompletionError::Timeout => write!(f, "Request timed out"),
+            CompletionError::BudgetExceeded(msg) => write!(f, "Budget exceeded: {}", msg),
+            CompletionError::Unavailable(msg) => write!(f, "No provider available: {}", msg),
             CompletionError::Other(err) => write!(f, "Error: {}", err),
         }
     }
@@ -21,13 +123,487 @@ impl Error for CompletionError {}
 
 impl From<reqwest::Error> for CompletionError {
     fn from(err: reqwest::Error) -> Self {
-        CompletionError::Other(Box::new(err))
+        if err.is_timeout() {
+            CompletionError::Timeout
+        } else if err.is_decode() {
+            CompletionError::MalformedResponse(err.to_string())
+        } else {
+            CompletionError::Other(Box::new(err))
+        }
+    }
+}
+
+impl From<serde_json::Error> for CompletionError {
+    fn from(err: serde_json::Error) -> Self {
+        CompletionError::MalformedResponse(err.to_string())
+    }
+}
+
+/// Who authored a message in a chat conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    /// The result of a tool call, answering the assistant turn that requested it.
+    Tool,
+}
+
+/// A single turn of a chat conversation, in the OpenAI-style `messages` format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    /// Tools the assistant asked to run in this turn.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// For `Role::Tool` turns, the call this is the result of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    /// The assistant turn of `completion`, tool calls included.
+    pub fn from_completion(completion: &Completion) -> Self {
+        Self {
+            tool_calls: completion.tool_calls.clone(),
+            ..Self::assistant(completion.content.clone())
+        }
+    }
+
+    /// The output of the tool call `call_id`, to send back to the model.
+    pub fn tool_result(call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(call_id.into()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+}
+
+/// A function the model asked to call, in the OpenAI `tool_calls` format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+/// Name and JSON-encoded arguments of a requested call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    pub arguments: String,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+impl ToolCall {
+    pub fn new(id: impl Into<String>, name: impl Into<String>, arguments: &serde_json::Value) -> Self {
+        Self {
+            id: id.into(),
+            kind: function_type(),
+            function: FunctionCall {
+                name: name.into(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+}
+
+/// A tool offered to the model: its name, what it does, and a JSON schema
+/// for its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+impl ToolDefinition {
+    /// The entry for the OpenAI-compatible `tools` request field.
+    pub fn to_openai(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters
+            }
+        })
+    }
+}
+
+/// Prepends `system_message` unless the conversation already starts with a system turn.
+pub fn with_system_message(system_message: &str, messages: &[ChatMessage]) -> Vec<ChatMessage> {
+    let mut full = Vec::with_capacity(messages.len() + 1);
+    if messages.first().map(|m| m.role) != Some(Role::System) {
+        full.push(ChatMessage::system(system_message));
+    }
+    full.extend_from_slice(messages);
+    full
+}
+
+/// Token counts for one request, as reported by the provider's `usage` field
+/// or estimated with the local tokenizer when it reports none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+impl Usage {
+    pub fn new(prompt_tokens: usize, completion_tokens: usize) -> Self {
+        Self { prompt_tokens, completion_tokens }
+    }
+
+    /// Counts both sides locally, for providers that do not report usage.
+    pub fn estimate(messages: &[ChatMessage], response: &str) -> Self {
+        Self::new(tokens::count_message_tokens(messages), tokens::count_tokens(response))
+    }
+
+    pub fn total_tokens(&self) -> usize {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// A finished assistant reply with its token usage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub content: String,
+    pub usage: Usage,
+    /// Tools the model wants run before it answers; empty for a final reply.
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl Completion {
+    pub fn new(content: String, usage: Usage) -> Self {
+        Self { content, usage, tool_calls: Vec::new() }
+    }
+}
+
+/// One item of a streamed reply: a text delta, the usage report that
+/// providers send once generation has finished, or (only from
+/// `chat_with_tools_stream`) the tool calls the model asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    Delta(String),
+    Usage(Usage),
+    ToolCalls(Vec<ToolCall>),
+}
+
+impl StreamEvent {
+    pub fn as_delta(&self) -> Option<&str> {
+        match self {
+            StreamEvent::Delta(text) => Some(text),
+            StreamEvent::Usage(_) | StreamEvent::ToolCalls(_) => None,
+        }
+    }
+}
+
+/// Incremental assistant output.
+pub type CompletionStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, CompletionError>> + Send>>;
+
+/// Sampling settings for a request. Unset fields fall back to the layer
+/// below: the call, then the command, then the character's `"generation"`
+/// profile entry, then the provider's own defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationParams {
+    /// Overrides the provider's model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl GenerationParams {
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// These params, with every unset field taken from `defaults`.
+    pub fn or(self, defaults: &GenerationParams) -> Self {
+        Self {
+            model: self.model.or_else(|| defaults.model.clone()),
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            max_tokens: self.max_tokens.or(defaults.max_tokens),
+            frequency_penalty: self.frequency_penalty.or(defaults.frequency_penalty),
+            presence_penalty: self.presence_penalty.or(defaults.presence_penalty),
+            stop: if self.stop.is_empty() { defaults.stop.clone() } else { self.stop },
+            seed: self.seed.or(defaults.seed),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The model to send the request to.
+    pub fn model_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.model.as_deref().unwrap_or(default)
+    }
+}
+
+#[async_trait::async_trait]
+pub trait CompletionProvider: Send + Sync {
+    /// Model identifier requests are sent to.
+    fn model(&self) -> &str;
+
+    /// System prompt used when the caller does not supply one.
+    fn system_message(&self) -> &str;
+
+    /// Generation settings sent when a call does not override them.
+    fn params(&self) -> GenerationParams {
+        GenerationParams::default()
+    }
+
+    /// Maximum reply length requested from the model, if the provider sets one.
+    fn max_tokens(&self) -> Option<u32> {
+        self.params().max_tokens
+    }
+
+    /// Total tokens the model accepts for prompt and reply together.
+    fn context_window(&self) -> usize {
+        tokens::context_window(self.model())
+    }
+
+    /// Sends a full conversation to the model and returns the assistant reply
+    /// with its token usage. Fields set in `params` override the provider's
+    /// defaults for this request only.
+    ///
+    /// If `messages` does not start with a system message, the provider's own
+    /// system prompt is used.
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError>;
+
+    /// Like `chat`, asking the backend to constrain the reply to a single
+    /// JSON object where it supports that (OpenAI's `response_format`,
+    /// Ollama's `format`). See `structured::JsonCompletion` for parsing.
+    async fn chat_json(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.chat(messages, params).await
+    }
+
+    /// Whether `chat_with_tools` actually offers the tools to the model.
+    fn supports_tools(&self) -> bool {
+        false
+    }
+
+    /// Like `chat`, offering `tools` the model may ask to call instead of
+    /// answering. Providers without tool support ignore them.
+    async fn chat_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        _tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<Completion, CompletionError> {
+        self.chat(messages, params).await
+    }
+
+    /// Streams the assistant reply as it is generated.
+    ///
+    /// Providers without native streaming yield the whole reply as one delta.
+    async fn chat_stream(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<CompletionStream, CompletionError> {
+        let completion = self.chat(messages, params).await?;
+        Ok(Box::pin(stream::iter([
+            Ok(StreamEvent::Delta(completion.content)),
+            Ok(StreamEvent::Usage(completion.usage)),
+        ])))
+    }
+
+    /// Like `chat_with_tools`, streaming the reply as it is generated. The
+    /// calls the model asks for arrive in one `ToolCalls` event once it has
+    /// finished.
+    ///
+    /// Providers without tool support stream through `chat_stream`; those
+    /// without native streaming of tool calls yield the whole reply at once.
+    async fn chat_with_tools_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<CompletionStream, CompletionError> {
+        if !self.supports_tools() {
+            return self.chat_stream(messages, params).await;
+        }
+        let completion = self.chat_with_tools(messages, tools, params).await?;
+        Ok(Box::pin(stream::iter(completion_events(completion).into_iter().map(Ok))))
+    }
+
+    /// `chat` with the provider's default params.
+    async fn complete_chat(&self, messages: &[ChatMessage]) -> Result<Completion, CompletionError> {
+        self.chat(messages, &GenerationParams::default()).await
+    }
+
+    /// `chat_json` with the provider's default params.
+    async fn complete_chat_json(&self, messages: &[ChatMessage]) -> Result<Completion, CompletionError> {
+        self.chat_json(messages, &GenerationParams::default()).await
+    }
+
+    /// `chat_with_tools` with the provider's default params.
+    async fn complete_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+    ) -> Result<Completion, CompletionError> {
+        self.chat_with_tools(messages, tools, &GenerationParams::default()).await
+    }
+
+    /// `chat_with_tools_stream` with the provider's default params.
+    async fn complete_with_tools_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+    ) -> Result<CompletionStream, CompletionError> {
+        self.chat_with_tools_stream(messages, tools, &GenerationParams::default()).await
+    }
+
+    /// `chat_stream` with the provider's default params.
+    async fn complete_stream(&self, messages: &[ChatMessage]) -> Result<CompletionStream, CompletionError> {
+        self.chat_stream(messages, &GenerationParams::default()).await
+    }
+
+    /// Like `complete_chat`, without the usage.
+    async fn complete_messages(&self, messages: &[ChatMessage]) -> Result<String, CompletionError> {
+        Ok(self.complete_chat(messages).await?.content)
+    }
+
+    /// Convenience wrapper for a single user prompt.
+    async fn complete(&self, prompt: &str) -> Result<String, CompletionError> {
+        self.complete_messages(&[ChatMessage::user(prompt)]).await
+    }
+}
+
+/// The events a streamed `completion` would have produced: its text, then
+/// its tool calls if there are any, then its usage.
+pub fn completion_events(completion: Completion) -> Vec<StreamEvent> {
+    let mut events = Vec::new();
+    if !completion.content.is_empty() {
+        events.push(StreamEvent::Delta(completion.content));
+    }
+    if !completion.tool_calls.is_empty() {
+        events.push(StreamEvent::ToolCalls(completion.tool_calls));
+    }
+    events.push(StreamEvent::Usage(completion.usage));
+    events
+}
+
+/// A provider decorator that layers fixed `GenerationParams` over the
+/// wrapped provider's defaults, e.g. a character's or a command's settings.
+/// Params passed to a call still take precedence.
+pub struct ParamsProvider {
+    inner: Arc<dyn CompletionProvider>,
+    params: GenerationParams,
+}
+
+impl ParamsProvider {
+    /// Wraps `provider`, or returns it unchanged when `params` sets nothing.
+    pub fn wrap(provider: Arc<dyn CompletionProvider>, params: GenerationParams) -> Arc<dyn CompletionProvider> {
+        if params.is_empty() {
+            return provider;
+        }
+        Arc::new(Self { inner: provider, params })
+    }
+
+    fn layered(&self, params: &GenerationParams) -> GenerationParams {
+        params.clone().or(&self.params)
     }
 }
 
 #[async_trait::async_trait]
-pub trait CompletionProvider {
-    type Error: Error + Send + Sync + 'static;
+impl CompletionProvider for ParamsProvider {
+    fn model(&self) -> &str {
+        self.params.model_or(self.inner.model())
+    }
+
+    fn system_message(&self) -> &str {
+        self.inner.system_message()
+    }
+
+    fn params(&self) -> GenerationParams {
+        self.params.clone().or(&self.inner.params())
+    }
+
+    fn context_window(&self) -> usize {
+        match &self.params.model {
+            Some(model) => tokens::context_window(model),
+            None => self.inner.context_window(),
+        }
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.inner.chat(messages, &self.layered(params)).await
+    }
+
+    async fn chat_json(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.inner.chat_json(messages, &self.layered(params)).await
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.inner.supports_tools()
+    }
 
-    async fn complete(&self, prompt: &str) -> Result<String, Self::Error>;
+    async fn chat_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<Completion, CompletionError> {
+        self.inner.chat_with_tools(messages, tools, &self.layered(params)).await
+    }
+
+    async fn chat_stream(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<CompletionStream, CompletionError> {
+        self.inner.chat_stream(messages, &self.layered(params)).await
+    }
+
+    async fn chat_with_tools_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.inner.chat_with_tools_stream(messages, tools, &self.layered(params)).await
+    }
 }
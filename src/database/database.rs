@@ -12,6 +12,245 @@ pub enum DatabaseError {
     Connection(String),
 }
 
+/// Default location of the agent's SQLite database.
+pub const DEFAULT_DB_PATH: &str = "data/agent.db";
+
+/// User of the command line, who also owns everything stored before
+/// conversations and memories were kept per user.
+pub const LOCAL_USER: &str = "local";
+
+/// One completion call, as stored in the usage ledger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageEntry {
+    pub model: String,
+    pub feature: String,
+    pub character: String,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub latency_ms: u64,
+    pub cost_usd: f64,
+}
+
+/// How `usage_totals` groups the ledger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageGroup {
+    Day,
+    Character,
+    Feature,
+}
+
+impl UsageGroup {
+    fn column(&self) -> &'static str {
+        match self {
+            UsageGroup::Day => "date(timestamp)",
+            UsageGroup::Character => "character",
+            UsageGroup::Feature => "feature",
+        }
+    }
+}
+
+/// Aggregated ledger rows sharing one day, character or feature.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct UsageTotals {
+    pub key: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// A reply stored in the completion cache.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedCompletion {
+    pub model: String,
+    pub content: String,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+/// What the completion cache table holds and how often it has been used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct CompletionCacheStats {
+    pub entries: u64,
+    pub hits: u64,
+    /// Prompt and completion tokens that hits did not have to send again.
+    pub tokens_saved: u64,
+}
+
+/// A long-term memory as stored in the `memories` table.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MemoryRecord {
+    pub id: i64,
+    /// The user it was learned from; only they can see it unless it is shared.
+    pub user_id: String,
+    pub character: String,
+    /// Where the memory came from, e.g. `user` or `document:notes.md`.
+    pub source: String,
+    pub content: String,
+    pub importance: f32,
+    /// Pinned memories are recalled into every prompt.
+    pub pinned: bool,
+    /// Shared memories are recalled for every user of the character.
+    pub shared: bool,
+    pub created_at: String,
+    pub accessed_at: String,
+    /// Ids of the memories this one was derived from, e.g. by reflection.
+    pub derived_from: Vec<i64>,
+}
+
+/// A chat between one user and one character, as stored in the `sessions`
+/// table.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub user_id: String,
+    pub character: String,
+    pub created_at: String,
+    pub last_active_at: String,
+}
+
+/// One exchange from the `conversations` table.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ConversationRecord {
+    pub id: i64,
+    pub timestamp: String,
+    pub user_input: String,
+    pub ai_response: String,
+    pub personality: String,
+    pub user_id: String,
+    /// `None` for exchanges stored before conversations had sessions.
+    pub session_id: Option<String>,
+}
+
+/// A row of the `knowledge_base` table.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct KnowledgeRecord {
+    pub key: String,
+    pub value: String,
+    pub timestamp: String,
+}
+
+/// A row of the `document_insights` table.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DocumentInsightRecord {
+    pub document_path: String,
+    pub insight_text: String,
+    pub relevance: f32,
+    pub insight_type: String,
+    pub timestamp: String,
+}
+
+/// A passage of an ingested document, as stored in `document_chunks`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DocumentChunk {
+    /// Row id, 0 until the chunk is stored.
+    pub id: i64,
+    pub document_path: String,
+    /// Position of the chunk within its document.
+    pub chunk_index: usize,
+    /// 1-based page of a PDF.
+    pub page: Option<u32>,
+    /// Worksheet of a spreadsheet.
+    pub sheet: Option<String>,
+    /// First and last 1-based line of the chunk within its page or sheet,
+    /// where a sheet has a line per row.
+    pub start_line: usize,
+    pub end_line: usize,
+    pub content: String,
+}
+
+/// A document whose chunks are stored.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct IngestedDocument {
+    pub path: String,
+    pub chunks: usize,
+    pub ingested_at: String,
+}
+
+/// Everything stored about one character (or all of them) that is worth
+/// moving to another machine. Caches, usage and embeddings are left out;
+/// embeddings are rebuilt on import.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub sessions: Vec<Session>,
+    pub conversations: Vec<ConversationRecord>,
+    pub memories: Vec<MemoryRecord>,
+    /// Knowledge learned from conversations.
+    pub knowledge: Vec<KnowledgeRecord>,
+    /// Document insights belong to no character; they are always included.
+    pub document_insights: Vec<DocumentInsightRecord>,
+}
+
+/// How an import treats what is already stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Add what is missing and keep existing data where the two disagree.
+    #[default]
+    Merge,
+    /// Drop the stored data in the snapshot's scope first.
+    Replace,
+}
+
+/// What an import did with one kind of record.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct ImportCounts {
+    pub added: usize,
+    /// Already stored as they are.
+    pub unchanged: usize,
+    /// Clashing with what is stored, which was kept.
+    pub conflicts: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub sessions: ImportCounts,
+    pub conversations: ImportCounts,
+    pub memories: ImportCounts,
+    pub knowledge: ImportCounts,
+    pub document_insights: ImportCounts,
+    /// What each conflict was about.
+    pub conflicts: Vec<String>,
+    /// Id, character and content of the memories added, to be embedded.
+    #[serde(skip)]
+    pub new_memories: Vec<(i64, String, String)>,
+}
+
+/// Key of knowledge of `kind` (e.g. `fact` or `topic`) about `name`,
+/// learned from `user_id`'s conversations with `character`.
+pub fn learned_knowledge_key(kind: &str, user_id: &str, character: &str, name: &str) -> String {
+    format!("{}:{}:{}:{}", kind, user_id, character, name)
+}
+
+/// The character a `learned_knowledge_key` belongs to.
+pub fn learned_knowledge_character(key: &str) -> Option<&str> {
+    let mut parts = key.splitn(4, ':');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some("fact" | "insight" | "topic"), Some(_), Some(character), Some(_)) => Some(character),
+        _ => None,
+    }
+}
+
+/// The running summary of a session's older turns.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ConversationSummary {
+    pub session: String,
+    pub summary: String,
+    /// Id of the newest stored conversation folded into the summary, or 0
+    /// when its turns are not stored in `conversations`.
+    pub covered_until: i64,
+    pub updated_at: String,
+}
+
+/// An embedded text as stored in the `embeddings` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredEmbedding {
+    pub key: String,
+    pub content: String,
+    pub vector: Vec<f32>,
+}
+
 #[derive(Clone)]
 pub struct Database {
     conn: Arc<Connection>,
@@ -52,7 +291,107 @@ impl Database {
                     insight_text TEXT NOT NULL,
                     relevance REAL NOT NULL,
                     insight_type TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS usage_ledger (
+                    id INTEGER PRIMARY KEY,
+                    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    model TEXT NOT NULL,
+                    feature TEXT NOT NULL,
+                    character TEXT NOT NULL,
+                    prompt_tokens INTEGER NOT NULL,
+                    completion_tokens INTEGER NOT NULL,
+                    latency_ms INTEGER NOT NULL,
+                    cost_usd REAL NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_usage_ledger_timestamp ON usage_ledger (timestamp);
+                CREATE TABLE IF NOT EXISTS completion_cache (
+                    key TEXT PRIMARY KEY,
+                    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    model TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    prompt_tokens INTEGER NOT NULL,
+                    completion_tokens INTEGER NOT NULL,
+                    hits INTEGER NOT NULL DEFAULT 0
+                );
+                CREATE TABLE IF NOT EXISTS memories (
+                    id INTEGER PRIMARY KEY,
+                    character TEXT NOT NULL,
+                    source TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    importance REAL NOT NULL,
+                    pinned INTEGER NOT NULL DEFAULT 0,
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    accessed_at DATETIME DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE INDEX IF NOT EXISTS idx_memories_character ON memories (character);
+                CREATE TABLE IF NOT EXISTS memory_links (
+                    memory_id INTEGER NOT NULL,
+                    source_id INTEGER NOT NULL,
+                    PRIMARY KEY (memory_id, source_id)
+                );
+                CREATE TABLE IF NOT EXISTS memory_reflections (
+                    user_id TEXT NOT NULL,
+                    character TEXT NOT NULL,
+                    last_memory_id INTEGER NOT NULL,
+                    reflected_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (user_id, character)
+                );
+                CREATE TABLE IF NOT EXISTS sessions (
+                    id TEXT PRIMARY KEY,
+                    user_id TEXT NOT NULL,
+                    character TEXT NOT NULL,
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    last_active_at DATETIME DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions (user_id);
+                CREATE TABLE IF NOT EXISTS conversation_summaries (
+                    session TEXT PRIMARY KEY,
+                    summary TEXT NOT NULL,
+                    covered_until INTEGER NOT NULL DEFAULT 0,
+                    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE TABLE IF NOT EXISTS embeddings (
+                    namespace TEXT NOT NULL,
+                    key TEXT NOT NULL,
+                    model TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    vector BLOB NOT NULL,
+                    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (namespace, key, model)
+                );
+                CREATE TABLE IF NOT EXISTS document_chunks (
+                    id INTEGER PRIMARY KEY,
+                    document_path TEXT NOT NULL,
+                    chunk_index INTEGER NOT NULL,
+                    page INTEGER,
+                    sheet TEXT,
+                    start_line INTEGER NOT NULL,
+                    end_line INTEGER NOT NULL,
+                    content TEXT NOT NULL,
+                    ingested_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    UNIQUE (document_path, chunk_index)
                 );"
+            )?;
+
+            // Columns added since the tables were first created; rows stored
+            // before then belong to the local user
+            let added = [
+                ("conversations", "user_id", "TEXT NOT NULL DEFAULT 'local'"),
+                ("conversations", "session_id", "TEXT"),
+                ("memories", "user_id", "TEXT NOT NULL DEFAULT 'local'"),
+                ("memories", "shared", "INTEGER NOT NULL DEFAULT 0"),
+            ];
+            for (table, column, definition) in added {
+                let exists = conn
+                    .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))?
+                    .exists([column])?;
+                if !exists {
+                    conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+                }
+            }
+            conn.execute_batch(
+                "CREATE INDEX IF NOT EXISTS idx_conversations_session ON conversations (session_id);
+                 CREATE INDEX IF NOT EXISTS idx_memories_user ON memories (user_id, character);"
             )
         })
         .await?;
@@ -61,24 +400,180 @@ impl Database {
         Ok(())
     }
 
+    /// Stores one exchange of `session` and marks the session active.
     pub async fn save_conversation(
         &self,
+        session: &Session,
         user_input: String,
         ai_response: String,
-        personality: String,
     ) -> Result<(), DatabaseError> {
+        let session = session.clone();
         self.conn
             .call(move |conn| {
                 conn.execute(
-                    "INSERT INTO conversations (user_input, ai_response, personality) VALUES (?1, ?2, ?3)",
-                    [&user_input, &ai_response, &personality],
-                )
+                    "INSERT INTO conversations (user_input, ai_response, personality, user_id, session_id)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    [&user_input, &ai_response, &session.character, &session.user_id, &session.id],
+                )?;
+                conn.execute("UPDATE sessions SET last_active_at = CURRENT_TIMESTAMP WHERE id = ?1", [&session.id])
             })
             .await?;
         
         Ok(())
     }
 
+    /// Conversations of session `session_id` newer than id `after`, oldest first.
+    pub async fn get_session_conversations(
+        &self,
+        session_id: String,
+        after: i64,
+    ) -> Result<Vec<ConversationRecord>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, timestamp, user_input, ai_response, personality, user_id, session_id
+                     FROM conversations
+                     WHERE session_id = ?1 AND id > ?2
+                     ORDER BY id"
+                )?;
+                let rows = stmt.query_map((&session_id, after), |row| {
+                    Ok(ConversationRecord {
+                        id: row.get(0)?,
+                        timestamp: row.get(1)?,
+                        user_input: row.get(2)?,
+                        ai_response: row.get(3)?,
+                        personality: row.get(4)?,
+                        user_id: row.get(5)?,
+                        session_id: row.get(6)?,
+                    })
+                })?;
+
+                let mut conversations = Vec::new();
+                for row in rows {
+                    conversations.push(row?);
+                }
+
+                Ok(conversations)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// The session `id` of `user_id` with `character`, started now if there
+    /// is no such session yet (with a new id when `id` is `None`). Returns
+    /// `None` when the id is taken by another user or character. `user_id`
+    /// is not verified here; callers must only pass ids that are unguessable
+    /// or authenticated.
+    pub async fn open_session(
+        &self,
+        id: Option<String>,
+        user_id: String,
+        character: String,
+    ) -> Result<Option<Session>, DatabaseError> {
+        let id = id.unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()));
+        let owner = (user_id.clone(), character.clone());
+        let result = self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR IGNORE INTO sessions (id, user_id, character) VALUES (?1, ?2, ?3)",
+                    [&id, &user_id, &character],
+                )?;
+                let mut stmt = conn.prepare(
+                    "SELECT id, user_id, character, created_at, last_active_at FROM sessions WHERE id = ?1"
+                )?;
+                let mut rows = stmt.query_map([&id], |row| {
+                    Ok(Session {
+                        id: row.get(0)?,
+                        user_id: row.get(1)?,
+                        character: row.get(2)?,
+                        created_at: row.get(3)?,
+                        last_active_at: row.get(4)?,
+                    })
+                })?;
+                rows.next().transpose()
+            })
+            .await?;
+
+        Ok(result.filter(|session| (&session.user_id, &session.character) == (&owner.0, &owner.1)))
+    }
+
+    /// Sessions of `user_id`, most recently active first.
+    pub async fn get_sessions(&self, user_id: String) -> Result<Vec<Session>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, user_id, character, created_at, last_active_at
+                     FROM sessions
+                     WHERE user_id = ?1
+                     ORDER BY last_active_at DESC, created_at DESC"
+                )?;
+                let rows = stmt.query_map([&user_id], |row| {
+                    Ok(Session {
+                        id: row.get(0)?,
+                        user_id: row.get(1)?,
+                        character: row.get(2)?,
+                        created_at: row.get(3)?,
+                        last_active_at: row.get(4)?,
+                    })
+                })?;
+
+                let mut sessions = Vec::new();
+                for row in rows {
+                    sessions.push(row?);
+                }
+
+                Ok(sessions)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    pub async fn get_summary(&self, session: String) -> Result<Option<ConversationSummary>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT session, summary, covered_until, updated_at FROM conversation_summaries WHERE session = ?1"
+                )?;
+                let mut rows = stmt.query_map([&session], |row| {
+                    Ok(ConversationSummary {
+                        session: row.get(0)?,
+                        summary: row.get(1)?,
+                        covered_until: row.get(2)?,
+                        updated_at: row.get(3)?,
+                    })
+                })?;
+                rows.next().transpose()
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Replaces the summary of `session`.
+    pub async fn save_summary(&self, session: String, summary: String, covered_until: i64) -> Result<(), DatabaseError> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO conversation_summaries (session, summary, covered_until, updated_at)
+                     VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
+                    (&session, &summary, covered_until),
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_summary(&self, session: String) -> Result<(), DatabaseError> {
+        self.conn
+            .call(move |conn| conn.execute("DELETE FROM conversation_summaries WHERE session = ?1", [&session]))
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn save_knowledge(
         &self,
         key: String,
@@ -144,6 +639,46 @@ impl Database {
         Ok(result)
     }
 
+    /// The knowledge whose keys start with `prefix`, by key.
+    pub async fn get_knowledge_with_prefix(&self, prefix: String) -> Result<Vec<KnowledgeRecord>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                // Compared as a substring, since keys may hold LIKE wildcards
+                let mut stmt = conn.prepare(
+                    "SELECT key, value, timestamp
+                     FROM knowledge_base
+                     WHERE substr(key, 1, length(?1)) = ?1
+                     ORDER BY key"
+                )?;
+                let rows = stmt.query_map([&prefix], |row| {
+                    Ok(KnowledgeRecord {
+                        key: row.get(0)?,
+                        value: row.get(1)?,
+                        timestamp: row.get(2)?,
+                    })
+                })?;
+
+                let mut records = Vec::new();
+                for row in rows {
+                    records.push(row?);
+                }
+                Ok(records)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Deletes the knowledge stored under `key`, returning whether there was
+    /// any.
+    pub async fn delete_knowledge(&self, key: String) -> Result<bool, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| conn.execute("DELETE FROM knowledge_base WHERE key = ?1", [&key]))
+            .await?;
+
+        Ok(result > 0)
+    }
+
     pub async fn save_document_insight(
         &self,
         document_path: String,
@@ -181,7 +716,7 @@ impl Database {
                     Ok((
                         row.get::<_, String>(0)?,
                         row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?.parse::<f32>().unwrap_or(0.0),
+                        row.get::<_, f64>(2)? as f32,
                         row.get::<_, String>(3)?,
                     ))
                 })?;
@@ -217,7 +752,7 @@ impl Database {
                     Ok((
                         row.get::<_, String>(0)?,
                         row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?.parse::<f32>().unwrap_or(0.0),
+                        row.get::<_, f64>(2)? as f32,
                     ))
                 })?;
 
@@ -246,7 +781,7 @@ impl Database {
                     Ok((
                         row.get::<_, String>(0)?,
                         row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?.parse::<f32>().unwrap_or(0.0),
+                        row.get::<_, f64>(2)? as f32,
                         row.get::<_, String>(3)?,
                     ))
                 })?;
@@ -262,4 +797,907 @@ impl Database {
             
         Ok(result)
     }
+
+    /// Stores `chunks` as the content of `document_path`, dropping the chunks
+    /// stored for it before. Returns the ids of the dropped chunks and the new
+    /// chunks with their ids.
+    pub async fn replace_document_chunks(
+        &self,
+        document_path: String,
+        chunks: Vec<DocumentChunk>,
+    ) -> Result<(Vec<i64>, Vec<DocumentChunk>), DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut removed = Vec::new();
+                {
+                    let mut stmt = tx.prepare("SELECT id FROM document_chunks WHERE document_path = ?1")?;
+                    for id in stmt.query_map([&document_path], |row| row.get::<_, i64>(0))? {
+                        removed.push(id?);
+                    }
+                }
+                tx.execute("DELETE FROM document_chunks WHERE document_path = ?1", [&document_path])?;
+                let mut stored = Vec::with_capacity(chunks.len());
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO document_chunks (document_path, chunk_index, page, sheet, start_line, end_line, content)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+                    )?;
+                    for mut chunk in chunks {
+                        stmt.execute((
+                            &document_path,
+                            chunk.chunk_index as i64,
+                            chunk.page,
+                            &chunk.sheet,
+                            chunk.start_line as i64,
+                            chunk.end_line as i64,
+                            &chunk.content,
+                        ))?;
+                        chunk.id = tx.last_insert_rowid();
+                        chunk.document_path = document_path.clone();
+                        stored.push(chunk);
+                    }
+                }
+                tx.commit()?;
+                Ok((removed, stored))
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Every stored chunk, by document and position.
+    pub async fn get_all_document_chunks(&self) -> Result<Vec<DocumentChunk>, DatabaseError> {
+        let result = self.conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, document_path, chunk_index, page, sheet, start_line, end_line, content
+                     FROM document_chunks
+                     ORDER BY document_path, chunk_index"
+                )?;
+                let rows = stmt.query_map([], |row| {
+                    Ok(DocumentChunk {
+                        id: row.get(0)?,
+                        document_path: row.get(1)?,
+                        chunk_index: row.get::<_, i64>(2)? as usize,
+                        page: row.get(3)?,
+                        sheet: row.get(4)?,
+                        start_line: row.get::<_, i64>(5)? as usize,
+                        end_line: row.get::<_, i64>(6)? as usize,
+                        content: row.get(7)?,
+                    })
+                })?;
+
+                let mut chunks = Vec::new();
+                for row in rows {
+                    chunks.push(row?);
+                }
+                Ok(chunks)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// The documents with stored chunks, most recently ingested first.
+    pub async fn list_ingested_documents(&self) -> Result<Vec<IngestedDocument>, DatabaseError> {
+        let result = self.conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT document_path, COUNT(*), MAX(ingested_at)
+                     FROM document_chunks
+                     GROUP BY document_path
+                     ORDER BY MAX(ingested_at) DESC, document_path"
+                )?;
+                let rows = stmt.query_map([], |row| {
+                    Ok(IngestedDocument {
+                        path: row.get(0)?,
+                        chunks: row.get::<_, i64>(1)? as usize,
+                        ingested_at: row.get(2)?,
+                    })
+                })?;
+
+                let mut documents = Vec::new();
+                for row in rows {
+                    documents.push(row?);
+                }
+                Ok(documents)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Drops the chunks of `document_path`, returning their ids.
+    pub async fn delete_document_chunks(&self, document_path: String) -> Result<Vec<i64>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut removed = Vec::new();
+                {
+                    let mut stmt = conn.prepare("SELECT id FROM document_chunks WHERE document_path = ?1")?;
+                    for id in stmt.query_map([&document_path], |row| row.get::<_, i64>(0))? {
+                        removed.push(id?);
+                    }
+                }
+                conn.execute("DELETE FROM document_chunks WHERE document_path = ?1", [&document_path])?;
+                Ok(removed)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    pub async fn record_usage(&self, entry: UsageEntry) -> Result<(), DatabaseError> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO usage_ledger
+                        (model, feature, character, prompt_tokens, completion_tokens, latency_ms, cost_usd)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    (
+                        &entry.model,
+                        &entry.feature,
+                        &entry.character,
+                        entry.prompt_tokens as i64,
+                        entry.completion_tokens as i64,
+                        entry.latency_ms as i64,
+                        entry.cost_usd,
+                    ),
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Ledger totals for the last `days` days (UTC, today included), grouped by `group`.
+    pub async fn usage_totals(&self, group: UsageGroup, days: u32) -> Result<Vec<UsageTotals>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {column}, COUNT(*), SUM(prompt_tokens), SUM(completion_tokens), SUM(cost_usd)
+                     FROM usage_ledger
+                     WHERE date(timestamp) > date('now', ?1)
+                     GROUP BY {column}
+                     ORDER BY {column}",
+                    column = group.column()
+                ))?;
+
+                let rows = stmt.query_map([format!("-{} days", days)], |row| {
+                    Ok(UsageTotals {
+                        key: row.get::<_, String>(0)?,
+                        requests: row.get::<_, i64>(1)? as u64,
+                        prompt_tokens: row.get::<_, i64>(2)? as u64,
+                        completion_tokens: row.get::<_, i64>(3)? as u64,
+                        cost_usd: row.get::<_, f64>(4)?,
+                    })
+                })?;
+
+                let mut totals = Vec::new();
+                for row in rows {
+                    totals.push(row?);
+                }
+
+                Ok(totals)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Spend recorded today (UTC), optionally for a single feature.
+    pub async fn spend_today(&self, feature: Option<String>) -> Result<f64, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                conn.query_row(
+                    "SELECT COALESCE(SUM(cost_usd), 0.0) FROM usage_ledger
+                     WHERE date(timestamp) = date('now') AND (?1 IS NULL OR feature = ?1)",
+                    [feature],
+                    |row| row.get::<_, f64>(0),
+                )
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// The cached reply for `key` if it is younger than `max_age_secs`,
+    /// counting the hit.
+    pub async fn get_cached_completion(
+        &self,
+        key: String,
+        max_age_secs: u64,
+    ) -> Result<Option<CachedCompletion>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT model, content, prompt_tokens, completion_tokens FROM completion_cache
+                     WHERE key = ?1 AND timestamp > datetime('now', ?2)"
+                )?;
+                let mut rows = stmt.query_map((&key, format!("-{} seconds", max_age_secs)), |row| {
+                    Ok(CachedCompletion {
+                        model: row.get::<_, String>(0)?,
+                        content: row.get::<_, String>(1)?,
+                        prompt_tokens: row.get::<_, i64>(2)? as usize,
+                        completion_tokens: row.get::<_, i64>(3)? as usize,
+                    })
+                })?;
+
+                let cached = rows.next().transpose()?;
+                if cached.is_some() {
+                    conn.execute("UPDATE completion_cache SET hits = hits + 1 WHERE key = ?1", [&key])?;
+                }
+                Ok(cached)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Stores a reply under `key`, replacing any previous one, and drops
+    /// entries older than `max_age_secs`.
+    pub async fn save_cached_completion(
+        &self,
+        key: String,
+        entry: CachedCompletion,
+        max_age_secs: u64,
+    ) -> Result<(), DatabaseError> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "DELETE FROM completion_cache WHERE timestamp <= datetime('now', ?1)",
+                    [format!("-{} seconds", max_age_secs)],
+                )?;
+                conn.execute(
+                    "INSERT OR REPLACE INTO completion_cache
+                        (key, model, content, prompt_tokens, completion_tokens)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    (
+                        &key,
+                        &entry.model,
+                        &entry.content,
+                        entry.prompt_tokens as i64,
+                        entry.completion_tokens as i64,
+                    ),
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Empties the completion cache, returning how many entries were removed.
+    pub async fn clear_completion_cache(&self) -> Result<usize, DatabaseError> {
+        let result = self.conn
+            .call(|conn| conn.execute("DELETE FROM completion_cache", []))
+            .await?;
+
+        Ok(result)
+    }
+
+    pub async fn completion_cache_stats(&self) -> Result<CompletionCacheStats, DatabaseError> {
+        let result = self.conn
+            .call(|conn| {
+                conn.query_row(
+                    "SELECT COUNT(*), COALESCE(SUM(hits), 0), COALESCE(SUM(hits * (prompt_tokens + completion_tokens)), 0)
+                     FROM completion_cache",
+                    [],
+                    |row| Ok(CompletionCacheStats {
+                        entries: row.get::<_, i64>(0)? as u64,
+                        hits: row.get::<_, i64>(1)? as u64,
+                        tokens_saved: row.get::<_, i64>(2)? as u64,
+                    }),
+                )
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Stores a long-term memory `user_id` gave `character`, linked to the
+    /// memories it was derived from, returning its id.
+    pub async fn save_memory(
+        &self,
+        user_id: String,
+        character: String,
+        source: String,
+        content: String,
+        importance: f32,
+        derived_from: Vec<i64>,
+    ) -> Result<i64, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute(
+                    "INSERT INTO memories (user_id, character, source, content, importance) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    (&user_id, &character, &source, &content, importance as f64),
+                )?;
+                let id = tx.last_insert_rowid();
+                {
+                    let mut stmt = tx.prepare("INSERT OR IGNORE INTO memory_links (memory_id, source_id) VALUES (?1, ?2)")?;
+                    for source_id in derived_from {
+                        stmt.execute((id, source_id))?;
+                    }
+                }
+                tx.commit()?;
+                Ok(id)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Every memory of `character` visible to `user_id`, i.e. their own and
+    /// shared ones: pinned first, then most important and newest.
+    pub async fn get_memories(&self, user_id: String, character: String) -> Result<Vec<MemoryRecord>, DatabaseError> {
+        self.query_memories(Some(user_id), Some(character), None, -1).await
+    }
+
+    /// Up to `limit` memories visible to `user_id` whose content or source
+    /// contains `query`, in `get_memories` order.
+    pub async fn search_memories(
+        &self,
+        user_id: String,
+        character: String,
+        query: String,
+        limit: i64,
+    ) -> Result<Vec<MemoryRecord>, DatabaseError> {
+        self.query_memories(Some(user_id), Some(character), Some(format!("%{}%", query)), limit).await
+    }
+
+    /// Memories matching every filter given; with no user, those of all users.
+    async fn query_memories(
+        &self,
+        user_id: Option<String>,
+        character: Option<String>,
+        pattern: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<MemoryRecord>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, user_id, character, source, content, importance, pinned, shared, created_at, accessed_at,
+                            (SELECT GROUP_CONCAT(source_id) FROM memory_links WHERE memory_id = memories.id)
+                     FROM memories
+                     WHERE (?1 IS NULL OR character = ?1) AND (?2 IS NULL OR user_id = ?2 OR shared = 1)
+                       AND (?3 IS NULL OR content LIKE ?3 OR source LIKE ?3)
+                     ORDER BY pinned DESC, importance DESC, created_at DESC, id DESC
+                     LIMIT ?4"
+                )?;
+                let rows = stmt.query_map((&character, &user_id, &pattern, limit), |row| {
+                    Ok(MemoryRecord {
+                        id: row.get(0)?,
+                        user_id: row.get(1)?,
+                        character: row.get(2)?,
+                        source: row.get(3)?,
+                        content: row.get(4)?,
+                        importance: row.get::<_, f64>(5)? as f32,
+                        pinned: row.get(6)?,
+                        shared: row.get(7)?,
+                        created_at: row.get(8)?,
+                        accessed_at: row.get(9)?,
+                        derived_from: row
+                            .get::<_, Option<String>>(10)?
+                            .unwrap_or_default()
+                            .split(',')
+                            .filter_map(|id| id.parse().ok())
+                            .collect(),
+                    })
+                })?;
+
+                let mut memories = Vec::new();
+                for row in rows {
+                    memories.push(row?);
+                }
+
+                Ok(memories)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Marks the memories with these ids as recalled just now and moves
+    /// their importance `boost` of the way towards 1.
+    pub async fn touch_memories(&self, ids: Vec<i64>, boost: f32) -> Result<(), DatabaseError> {
+        self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "UPDATE memories
+                     SET accessed_at = CURRENT_TIMESTAMP, importance = importance + (1.0 - importance) * ?2
+                     WHERE id = ?1"
+                )?;
+                for id in ids {
+                    stmt.execute((id, boost as f64))?;
+                }
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Pins or unpins a memory `user_id` gave `character`, returning whether
+    /// it exists.
+    pub async fn set_memory_pinned(
+        &self,
+        user_id: String,
+        character: String,
+        id: i64,
+        pinned: bool,
+    ) -> Result<bool, DatabaseError> {
+        self.update_memory_flag("pinned", user_id, character, id, pinned).await
+    }
+
+    /// Shares a memory `user_id` gave `character` with every other user of
+    /// the character, or makes it private again, returning whether it exists.
+    pub async fn set_memory_shared(
+        &self,
+        user_id: String,
+        character: String,
+        id: i64,
+        shared: bool,
+    ) -> Result<bool, DatabaseError> {
+        self.update_memory_flag("shared", user_id, character, id, shared).await
+    }
+
+    async fn update_memory_flag(
+        &self,
+        column: &'static str,
+        user_id: String,
+        character: String,
+        id: i64,
+        value: bool,
+    ) -> Result<bool, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                conn.execute(
+                    &format!("UPDATE memories SET {} = ?1 WHERE id = ?2 AND user_id = ?3 AND character = ?4", column),
+                    (value, id, &user_id, &character),
+                )
+            })
+            .await?;
+
+        Ok(result > 0)
+    }
+
+    /// Deletes a memory `user_id` gave `character`, returning whether it existed.
+    pub async fn delete_memory(&self, user_id: String, character: String, id: i64) -> Result<bool, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let deleted = conn.execute(
+                    "DELETE FROM memories WHERE id = ?1 AND user_id = ?2 AND character = ?3",
+                    (id, &user_id, &character),
+                )?;
+                if deleted > 0 {
+                    conn.execute("DELETE FROM memory_links WHERE memory_id = ?1", [id])?;
+                }
+                Ok(deleted)
+            })
+            .await?;
+
+        Ok(result > 0)
+    }
+
+    /// Id of the newest memory `user_id` gave `character` already considered
+    /// by a reflection pass, or 0 before the first one.
+    pub async fn get_reflection_cursor(&self, user_id: String, character: String) -> Result<i64, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT last_memory_id FROM memory_reflections WHERE user_id = ?1 AND character = ?2"
+                )?;
+                let mut rows = stmt.query_map([&user_id, &character], |row| row.get::<_, i64>(0))?;
+                rows.next().transpose()
+            })
+            .await?;
+
+        Ok(result.unwrap_or(0))
+    }
+
+    pub async fn save_reflection_cursor(
+        &self,
+        user_id: String,
+        character: String,
+        last_memory_id: i64,
+    ) -> Result<(), DatabaseError> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO memory_reflections (user_id, character, last_memory_id, reflected_at)
+                     VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
+                    (&user_id, &character, last_memory_id),
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Stores `entries` embedded with `model` under `namespace`, replacing
+    /// earlier vectors for the same keys. Vectors are kept as little-endian
+    /// `f32` blobs.
+    pub async fn save_embeddings(
+        &self,
+        namespace: String,
+        model: String,
+        entries: Vec<StoredEmbedding>,
+    ) -> Result<(), DatabaseError> {
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT OR REPLACE INTO embeddings (namespace, key, model, content, vector)
+                         VALUES (?1, ?2, ?3, ?4, ?5)"
+                    )?;
+                    for entry in entries {
+                        let vector: Vec<u8> = entry.vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+                        stmt.execute((&namespace, &entry.key, &model, &entry.content, vector))?;
+                    }
+                }
+                tx.commit()
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Every vector embedded with `model` under `namespace`.
+    pub async fn get_embeddings(&self, namespace: String, model: String) -> Result<Vec<StoredEmbedding>, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT key, content, vector FROM embeddings WHERE namespace = ?1 AND model = ?2"
+                )?;
+                let rows = stmt.query_map([&namespace, &model], |row| {
+                    let bytes = row.get::<_, Vec<u8>>(2)?;
+                    Ok(StoredEmbedding {
+                        key: row.get(0)?,
+                        content: row.get(1)?,
+                        vector: bytes
+                            .chunks_exact(4)
+                            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                            .collect(),
+                    })
+                })?;
+
+                let mut embeddings = Vec::new();
+                for row in rows {
+                    embeddings.push(row?);
+                }
+
+                Ok(embeddings)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Drops the vectors of `key` in `namespace`, whatever model made them.
+    pub async fn delete_embedding(&self, namespace: String, key: String) -> Result<(), DatabaseError> {
+        self.conn
+            .call(move |conn| conn.execute("DELETE FROM embeddings WHERE namespace = ?1 AND key = ?2", [&namespace, &key]))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Everything stored about `character`, or about every character when
+    /// it is `None`.
+    pub async fn export_snapshot(&self, character: Option<String>) -> Result<Snapshot, DatabaseError> {
+        let memories = self.query_memories(None, character.clone(), None, -1).await?;
+        let mut snapshot = self.conn
+            .call(move |conn| {
+                let mut snapshot = Snapshot::default();
+
+                let mut stmt = conn.prepare(
+                    "SELECT id, user_id, character, created_at, last_active_at
+                     FROM sessions
+                     WHERE ?1 IS NULL OR character = ?1
+                     ORDER BY created_at, id"
+                )?;
+                let rows = stmt.query_map([&character], |row| {
+                    Ok(Session {
+                        id: row.get(0)?,
+                        user_id: row.get(1)?,
+                        character: row.get(2)?,
+                        created_at: row.get(3)?,
+                        last_active_at: row.get(4)?,
+                    })
+                })?;
+                for row in rows {
+                    snapshot.sessions.push(row?);
+                }
+
+                let mut stmt = conn.prepare(
+                    "SELECT id, timestamp, user_input, ai_response, personality, user_id, session_id
+                     FROM conversations
+                     WHERE ?1 IS NULL OR personality = ?1
+                     ORDER BY id"
+                )?;
+                let rows = stmt.query_map([&character], |row| {
+                    Ok(ConversationRecord {
+                        id: row.get(0)?,
+                        timestamp: row.get(1)?,
+                        user_input: row.get(2)?,
+                        ai_response: row.get(3)?,
+                        personality: row.get(4)?,
+                        user_id: row.get(5)?,
+                        session_id: row.get(6)?,
+                    })
+                })?;
+                for row in rows {
+                    snapshot.conversations.push(row?);
+                }
+
+                let mut stmt = conn.prepare("SELECT key, value, timestamp FROM knowledge_base ORDER BY id")?;
+                let rows = stmt.query_map([], |row| {
+                    Ok(KnowledgeRecord {
+                        key: row.get(0)?,
+                        value: row.get(1)?,
+                        timestamp: row.get(2)?,
+                    })
+                })?;
+                for row in rows {
+                    let record = row?;
+                    // Knowledge not learned with a particular character only goes into full exports
+                    if character.is_none() || learned_knowledge_character(&record.key) == character.as_deref() {
+                        snapshot.knowledge.push(record);
+                    }
+                }
+
+                let mut stmt = conn.prepare(
+                    "SELECT document_path, insight_text, relevance, insight_type, timestamp
+                     FROM document_insights
+                     ORDER BY id"
+                )?;
+                let rows = stmt.query_map([], |row| {
+                    Ok(DocumentInsightRecord {
+                        document_path: row.get(0)?,
+                        insight_text: row.get(1)?,
+                        relevance: row.get::<_, f64>(2)? as f32,
+                        insight_type: row.get(3)?,
+                        timestamp: row.get(4)?,
+                    })
+                })?;
+                for row in rows {
+                    snapshot.document_insights.push(row?);
+                }
+
+                Ok(snapshot)
+            })
+            .await?;
+
+        snapshot.memories = memories;
+        snapshot.memories.sort_by_key(|m| m.id);
+        Ok(snapshot)
+    }
+
+    /// Stores `snapshot`, which holds `character` (or every character when
+    /// it is `None`), in one transaction. Memory ids are assigned afresh and
+    /// the links between them carried over. With `ImportMode::Replace` the
+    /// stored data in that scope is dropped first; document insights are
+    /// only replaced by a snapshot of every character.
+    pub async fn import_snapshot(
+        &self,
+        snapshot: Snapshot,
+        character: Option<String>,
+        mode: ImportMode,
+    ) -> Result<ImportReport, DatabaseError> {
+        let result = self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut report = ImportReport { mode, ..Default::default() };
+
+                if mode == ImportMode::Replace {
+                    tx.execute(
+                        "DELETE FROM conversation_summaries
+                         WHERE ?1 IS NULL OR session = 'cli:' || ?1
+                            OR session IN (SELECT id FROM sessions WHERE character = ?1)",
+                        [&character],
+                    )?;
+                    tx.execute("DELETE FROM conversations WHERE ?1 IS NULL OR personality = ?1", [&character])?;
+                    tx.execute("DELETE FROM sessions WHERE ?1 IS NULL OR character = ?1", [&character])?;
+                    tx.execute(
+                        "DELETE FROM memory_links
+                         WHERE memory_id IN (SELECT id FROM memories WHERE ?1 IS NULL OR character = ?1)",
+                        [&character],
+                    )?;
+                    tx.execute("DELETE FROM memories WHERE ?1 IS NULL OR character = ?1", [&character])?;
+                    tx.execute("DELETE FROM memory_reflections WHERE ?1 IS NULL OR character = ?1", [&character])?;
+                    tx.execute(
+                        "DELETE FROM embeddings
+                         WHERE namespace = 'memories:' || ?1 OR (?1 IS NULL AND namespace LIKE 'memories:%')",
+                        [&character],
+                    )?;
+
+                    let keys = {
+                        let mut stmt = tx.prepare("SELECT key FROM knowledge_base")?;
+                        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+                        let mut keys = Vec::new();
+                        for row in rows {
+                            let key = row?;
+                            if character.is_none() || learned_knowledge_character(&key) == character.as_deref() {
+                                keys.push(key);
+                            }
+                        }
+                        keys
+                    };
+                    for key in keys {
+                        tx.execute("DELETE FROM knowledge_base WHERE key = ?1", [&key])?;
+                    }
+                    if character.is_none() {
+                        tx.execute("DELETE FROM document_insights", [])?;
+                    }
+                }
+
+                let mut refused = std::collections::HashSet::new();
+                for session in &snapshot.sessions {
+                    let owner = {
+                        let mut stmt = tx.prepare("SELECT user_id, character FROM sessions WHERE id = ?1")?;
+                        let mut rows = stmt.query_map([&session.id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+                        rows.next().transpose()?
+                    };
+                    match owner {
+                        None => {
+                            tx.execute(
+                                "INSERT INTO sessions (id, user_id, character, created_at, last_active_at)
+                                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                                [&session.id, &session.user_id, &session.character, &session.created_at, &session.last_active_at],
+                            )?;
+                            report.sessions.added += 1;
+                        }
+                        Some((user_id, with)) if user_id == session.user_id && with == session.character => {
+                            report.sessions.unchanged += 1;
+                        }
+                        Some(_) => {
+                            report.sessions.conflicts += 1;
+                            report.conflicts.push(format!(
+                                "Session {} already belongs to another user or character; skipped it and its conversations",
+                                session.id
+                            ));
+                            refused.insert(session.id.clone());
+                        }
+                    }
+                }
+
+                for conversation in &snapshot.conversations {
+                    if conversation.session_id.as_ref().is_some_and(|id| refused.contains(id)) {
+                        report.conversations.conflicts += 1;
+                        continue;
+                    }
+                    let exists = tx
+                        .prepare(
+                            "SELECT 1 FROM conversations
+                             WHERE timestamp = ?1 AND user_input = ?2 AND ai_response = ?3
+                               AND personality = ?4 AND user_id = ?5 AND session_id IS ?6"
+                        )?
+                        .exists((
+                            &conversation.timestamp,
+                            &conversation.user_input,
+                            &conversation.ai_response,
+                            &conversation.personality,
+                            &conversation.user_id,
+                            &conversation.session_id,
+                        ))?;
+                    if exists {
+                        report.conversations.unchanged += 1;
+                        continue;
+                    }
+                    tx.execute(
+                        "INSERT INTO conversations (timestamp, user_input, ai_response, personality, user_id, session_id)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        (
+                            &conversation.timestamp,
+                            &conversation.user_input,
+                            &conversation.ai_response,
+                            &conversation.personality,
+                            &conversation.user_id,
+                            &conversation.session_id,
+                        ),
+                    )?;
+                    report.conversations.added += 1;
+                }
+
+                // Sources come before the memories derived from them, so links can be remapped
+                let mut memories = snapshot.memories;
+                memories.sort_by_key(|m| m.id);
+                let mut ids = std::collections::HashMap::new();
+                for memory in &memories {
+                    let existing = {
+                        let mut stmt = tx.prepare(
+                            "SELECT id FROM memories WHERE user_id = ?1 AND character = ?2 AND content = ?3"
+                        )?;
+                        let mut rows = stmt.query_map([&memory.user_id, &memory.character, &memory.content], |row| row.get::<_, i64>(0))?;
+                        rows.next().transpose()?
+                    };
+                    if let Some(id) = existing {
+                        ids.insert(memory.id, id);
+                        report.memories.unchanged += 1;
+                        continue;
+                    }
+                    tx.execute(
+                        "INSERT INTO memories
+                            (user_id, character, source, content, importance, pinned, shared, created_at, accessed_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                        (
+                            &memory.user_id,
+                            &memory.character,
+                            &memory.source,
+                            &memory.content,
+                            memory.importance as f64,
+                            memory.pinned,
+                            memory.shared,
+                            &memory.created_at,
+                            &memory.accessed_at,
+                        ),
+                    )?;
+                    let id = tx.last_insert_rowid();
+                    ids.insert(memory.id, id);
+                    report.new_memories.push((id, memory.character.clone(), memory.content.clone()));
+                    report.memories.added += 1;
+                }
+                for memory in &memories {
+                    for source in &memory.derived_from {
+                        if let (Some(id), Some(source_id)) = (ids.get(&memory.id), ids.get(source)) {
+                            tx.execute(
+                                "INSERT OR IGNORE INTO memory_links (memory_id, source_id) VALUES (?1, ?2)",
+                                (id, source_id),
+                            )?;
+                        }
+                    }
+                }
+
+                for record in &snapshot.knowledge {
+                    let stored = {
+                        let mut stmt = tx.prepare("SELECT value FROM knowledge_base WHERE key = ?1")?;
+                        let mut rows = stmt.query_map([&record.key], |row| row.get::<_, String>(0))?;
+                        rows.next().transpose()?
+                    };
+                    match stored {
+                        None => {
+                            tx.execute(
+                                "INSERT INTO knowledge_base (key, value, timestamp) VALUES (?1, ?2, ?3)",
+                                [&record.key, &record.value, &record.timestamp],
+                            )?;
+                            report.knowledge.added += 1;
+                        }
+                        Some(value) if value == record.value => report.knowledge.unchanged += 1,
+                        Some(_) => {
+                            report.knowledge.conflicts += 1;
+                            report.conflicts.push(format!("Kept the stored value of knowledge '{}'", record.key));
+                        }
+                    }
+                }
+
+                for insight in &snapshot.document_insights {
+                    let exists = tx
+                        .prepare("SELECT 1 FROM document_insights WHERE document_path = ?1 AND insight_text = ?2")?
+                        .exists([&insight.document_path, &insight.insight_text])?;
+                    if exists {
+                        report.document_insights.unchanged += 1;
+                        continue;
+                    }
+                    tx.execute(
+                        "INSERT INTO document_insights (document_path, insight_text, relevance, insight_type, timestamp)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        (
+                            &insight.document_path,
+                            &insight.insight_text,
+                            insight.relevance as f64,
+                            &insight.insight_type,
+                            &insight.timestamp,
+                        ),
+                    )?;
+                    report.document_insights.added += 1;
+                }
+
+                tx.commit()?;
+                Ok(report)
+            })
+            .await?;
+
+        Ok(result)
+    }
 }
@@ -0,0 +1,67 @@
+use crate::cache::fnv1a;
+use crate::completion::CompletionError;
+use crate::text;
+use super::EmbeddingProvider;
+
+pub const DEFAULT_DIMENSIONS: usize = 384;
+
+/// Offline embeddings: words and character trigrams hashed into a fixed
+/// number of signed buckets, then normalized. Texts sharing vocabulary (or
+/// word stems, through the trigrams) end up close together. There is no
+/// notion of synonyms, but it needs no network or model files.
+pub struct HashEmbedder {
+    dimensions: usize,
+    model: String,
+}
+
+impl Default for HashEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIMENSIONS)
+    }
+}
+
+impl HashEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+            model: format!("local-hash-{}", dimensions.max(1)),
+        }
+    }
+
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        let mut add = |feature: &str, weight: f32| {
+            let hash = fnv1a(feature);
+            let bucket = (hash % self.dimensions as u64) as usize;
+            // The top bit picks the sign, so unrelated features cancel out on average
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[bucket] += sign * weight;
+        };
+
+        for word in text::words(text) {
+            add(&word, 1.0);
+
+            let padded: Vec<char> = format!("#{}#", word).chars().collect();
+            for trigram in padded.windows(3) {
+                add(&trigram.iter().collect::<String>(), 0.5);
+            }
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingProvider for HashEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, CompletionError> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
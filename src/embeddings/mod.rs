@@ -0,0 +1,73 @@
+// src/embeddings/mod.rs
+pub mod local;
+pub mod openai;
+pub mod store;
+
+#[cfg(test)]
+mod tests;
+
+pub use local::HashEmbedder;
+pub use openai::OpenAiEmbeddings;
+pub use store::VectorStore;
+
+use std::env;
+use std::sync::Arc;
+use crate::completion::CompletionError;
+
+/// Turns text into vectors whose cosine similarity reflects how close the
+/// texts are in meaning.
+#[async_trait::async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Name of the embedding model. Vectors from different models are never
+    /// compared, so this is stored next to each one.
+    fn model(&self) -> &str;
+
+    /// One vector per text, in order.
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, CompletionError>;
+
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>, CompletionError> {
+        self.embed(&[text.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| CompletionError::MalformedResponse("No embedding returned".to_string()))
+    }
+}
+
+/// Cosine similarity of two vectors; 0 when either is empty or they differ
+/// in length.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// The embedding backend picked by `EMBEDDINGS`: `openai` for any
+/// OpenAI-compatible `/v1/embeddings` server (`EMBEDDING_BASE_URL`,
+/// `EMBEDDING_API_KEY` and `EMBEDDING_MODEL`, falling back to the
+/// `OPENAI_*` settings), or the offline hashed n-gram embedder otherwise.
+pub fn from_env() -> Arc<dyn EmbeddingProvider> {
+    let var = |name: &str| env::var(name).ok().filter(|v| !v.trim().is_empty());
+
+    match var("EMBEDDINGS").as_deref().map(str::to_lowercase).as_deref() {
+        Some("openai") => Arc::new(OpenAiEmbeddings::new(
+            var("EMBEDDING_BASE_URL")
+                .or_else(|| var("OPENAI_BASE_URL"))
+                .unwrap_or_else(|| "https://api.openai.com".to_string()),
+            var("EMBEDDING_API_KEY").or_else(|| var("OPENAI_API_KEY")),
+            var("EMBEDDING_MODEL").unwrap_or_else(|| openai::DEFAULT_MODEL.to_string()),
+        )),
+        Some(other) if other != "local" => {
+            log::warn!("Unknown EMBEDDINGS backend '{}', using local embeddings", other);
+            Arc::new(HashEmbedder::default())
+        }
+        _ => Arc::new(HashEmbedder::default()),
+    }
+}
@@ -0,0 +1,89 @@
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use crate::completion::CompletionError;
+use crate::providers::retry::{self, RetryPolicy};
+use super::EmbeddingProvider;
+
+pub const DEFAULT_MODEL: &str = "text-embedding-3-small";
+
+/// Embeddings from any server implementing the OpenAI `/v1/embeddings` API.
+pub struct OpenAiEmbeddings {
+    client: Client,
+    api_url: String,
+    api_key: Option<String>,
+    model: String,
+    retry: RetryPolicy,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAiEmbeddings {
+    pub fn new(api_url: String, api_key: Option<String>, model: String) -> Self {
+        Self {
+            client: Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            api_key,
+            model,
+            retry: RetryPolicy::from_env(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Base URLs may be given with or without the trailing `/v1`.
+    fn endpoint(&self) -> String {
+        if self.api_url.ends_with("/v1") {
+            format!("{}/embeddings", self.api_url)
+        } else {
+            format!("{}/v1/embeddings", self.api_url)
+        }
+    }
+
+    async fn send(&self, body: &serde_json::Value) -> Result<String, CompletionError> {
+        let mut request = self.client.post(self.endpoint()).json(body);
+        if let Some(api_key) = &self.api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        Ok(retry::check_status(request.send().await?).await?.text().await?)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingProvider for OpenAiEmbeddings {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, CompletionError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let body = json!({"model": self.model, "input": texts});
+        let response_text = self.retry.run(|| self.send(&body)).await?;
+        let mut response: EmbeddingResponse = serde_json::from_str(&response_text)?;
+
+        if response.data.len() != texts.len() {
+            return Err(CompletionError::MalformedResponse(format!(
+                "Expected {} embeddings, got {}",
+                texts.len(),
+                response.data.len()
+            )));
+        }
+        response.data.sort_by_key(|data| data.index);
+        Ok(response.data.into_iter().map(|data| data.embedding).collect())
+    }
+}
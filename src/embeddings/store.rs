@@ -0,0 +1,141 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use thiserror::Error;
+use crate::completion::CompletionError;
+use crate::database::{Database, DatabaseError, StoredEmbedding};
+use crate::tokens;
+use super::{cosine, EmbeddingProvider};
+
+/// Matches less similar than this are not worth putting in a prompt.
+pub const MIN_SCORE: f32 = 0.2;
+
+/// Texts are cut to this many tokens before they are embedded.
+pub const MAX_EMBED_TOKENS: usize = 512;
+
+/// Namespace of long-term memories, keyed by memory id.
+pub fn memory_namespace(character: &str) -> String {
+    format!("memories:{}", character)
+}
+
+/// Namespace of document insights.
+pub const INSIGHTS_NAMESPACE: &str = "insights";
+
+/// Namespace of web research findings.
+pub const RESEARCH_NAMESPACE: &str = "research";
+
+/// Namespace of document chunks, keyed by chunk id.
+pub const DOCUMENTS_NAMESPACE: &str = "documents";
+
+#[derive(Error, Debug)]
+pub enum VectorError {
+    #[error("Embedding failed: {0}")]
+    Embedding(#[from] CompletionError),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+}
+
+/// A stored text similar to a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorMatch {
+    pub key: String,
+    pub content: String,
+    pub score: f32,
+}
+
+/// Embedded texts grouped by namespace and persisted in the `embeddings`
+/// table. Search is a brute-force cosine scan of one namespace, which is
+/// plenty for what a single agent accumulates.
+#[derive(Clone)]
+pub struct VectorStore {
+    db: Database,
+    embedder: Arc<dyn EmbeddingProvider>,
+}
+
+impl VectorStore {
+    pub fn new(db: Database, embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        Self { db, embedder }
+    }
+
+    pub fn model(&self) -> &str {
+        self.embedder.model()
+    }
+
+    /// Embeds and stores `(key, text)` pairs, replacing earlier versions of
+    /// the same keys.
+    pub async fn index(&self, namespace: &str, items: Vec<(String, String)>) -> Result<(), VectorError> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let texts: Vec<String> = items
+            .iter()
+            .map(|(_, text)| tokens::truncate_to_tokens(text, MAX_EMBED_TOKENS))
+            .collect();
+        let vectors = self.embedder.embed(&texts).await?;
+        let entries = items
+            .into_iter()
+            .zip(vectors)
+            .map(|((key, content), vector)| StoredEmbedding { key, content, vector })
+            .collect();
+
+        self.db
+            .save_embeddings(namespace.to_string(), self.model().to_string(), entries)
+            .await?;
+        Ok(())
+    }
+
+    /// The `k` stored texts most similar to `query`, best first, leaving out
+    /// those below the minimum score.
+    pub async fn search(&self, namespace: &str, query: &str, k: usize) -> Result<Vec<VectorMatch>, VectorError> {
+        self.search_where(namespace, query, k, |_| true).await
+    }
+
+    /// Like `search`, only among the texts stored under `keys`, e.g. the
+    /// memories one user can see in a namespace shared by every user.
+    pub async fn search_keys(
+        &self,
+        namespace: &str,
+        query: &str,
+        k: usize,
+        keys: &HashSet<String>,
+    ) -> Result<Vec<VectorMatch>, VectorError> {
+        self.search_where(namespace, query, k, |key| keys.contains(key)).await
+    }
+
+    async fn search_where(
+        &self,
+        namespace: &str,
+        query: &str,
+        k: usize,
+        keep: impl Fn(&str) -> bool,
+    ) -> Result<Vec<VectorMatch>, VectorError> {
+        let stored = self.db
+            .get_embeddings(namespace.to_string(), self.model().to_string())
+            .await?;
+        if stored.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = self.embedder
+            .embed_one(&tokens::truncate_to_tokens(query, MAX_EMBED_TOKENS))
+            .await?;
+        let mut matches: Vec<VectorMatch> = stored
+            .into_iter()
+            .filter(|entry| keep(&entry.key))
+            .map(|entry| VectorMatch {
+                score: cosine(&query, &entry.vector),
+                key: entry.key,
+                content: entry.content,
+            })
+            .filter(|m| m.score >= MIN_SCORE)
+            .collect();
+        matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        matches.truncate(k);
+        Ok(matches)
+    }
+
+    pub async fn remove(&self, namespace: &str, key: &str) -> Result<(), VectorError> {
+        self.db.delete_embedding(namespace.to_string(), key.to_string()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use super::*;
+use crate::database::{Database, LOCAL_USER};
+use crate::memory::LongTermMemory;
+use crate::memory::long_term::RECALL_LIMIT;
+use crate::providers::mock_server::{MockResponse, MockServer};
+use crate::providers::retry::RetryPolicy;
+use serde_json::json;
+
+#[test]
+fn test_hash_embeddings_reflect_shared_vocabulary() {
+    let embedder = HashEmbedder::default();
+    let launch = embedder.embed_text("The product launch is planned for March");
+    let launching = embedder.embed_text("When are we launching the product?");
+    let weather = embedder.embed_text("Sunny skies and warm weather all week");
+
+    assert_eq!(launch.len(), local::DEFAULT_DIMENSIONS);
+    assert!((cosine(&launch, &launch) - 1.0).abs() < 1e-5);
+    assert!(cosine(&launch, &launching) > cosine(&launch, &weather) + 0.2);
+    assert_eq!(cosine(&launch, &[]), 0.0);
+}
+
+#[tokio::test]
+async fn test_openai_embeddings_are_returned_in_input_order() {
+    let server = MockServer::start(vec![MockResponse::json(200, json!({
+        "data": [
+            {"index": 1, "embedding": [0.0, 1.0]},
+            {"index": 0, "embedding": [1.0, 0.0]}
+        ]
+    }))]).await;
+    let embedder = OpenAiEmbeddings::new(format!("{}/v1", server.base_url), Some("key".to_string()), "embed-small".to_string())
+        .with_retry_policy(RetryPolicy::default().with_max_retries(0));
+
+    let vectors = embedder.embed(&["first".to_string(), "second".to_string()]).await.unwrap();
+    assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+
+    let request = server.requests().await.pop().unwrap();
+    let body: serde_json::Value = serde_json::from_str(request.split("\r\n\r\n").last().unwrap()).unwrap();
+    assert_eq!(body, json!({"model": "embed-small", "input": ["first", "second"]}));
+}
+
+#[tokio::test]
+async fn test_openai_embeddings_surface_http_errors() {
+    let server = MockServer::start(vec![MockResponse::json(401, json!({"error": {"message": "bad key"}}))]).await;
+    let embedder = OpenAiEmbeddings::new(server.base_url.clone(), None, "embed-small".to_string())
+        .with_retry_policy(RetryPolicy::default().with_max_retries(0));
+
+    let err = embedder.embed_one("hello").await.unwrap_err();
+    assert!(matches!(err, CompletionError::AuthFailed(_)));
+}
+
+#[tokio::test]
+async fn test_vector_store_returns_top_k_by_similarity() {
+    let db = Database::new(":memory:").await.unwrap();
+    let store = VectorStore::new(db.clone(), Arc::new(HashEmbedder::default()));
+    store.index("notes", vec![
+        ("launch".to_string(), "The product launch is planned for March".to_string()),
+        ("budget".to_string(), "The marketing budget doubled this quarter".to_string()),
+        ("weather".to_string(), "Sunny skies and warm weather all week".to_string()),
+    ]).await.unwrap();
+
+    let matches = store.search("notes", "When is the product launching?", 2).await.unwrap();
+    assert_eq!(matches[0].key, "launch");
+    assert_eq!(matches[0].content, "The product launch is planned for March");
+    assert!(matches.iter().all(|m| m.key != "weather"));
+
+    // Vectors persist, and other embedding models never see them
+    let reopened = VectorStore::new(db.clone(), Arc::new(HashEmbedder::default()));
+    assert_eq!(reopened.search("notes", "product launch", 1).await.unwrap()[0].key, "launch");
+    let other_model = VectorStore::new(db.clone(), Arc::new(HashEmbedder::new(64)));
+    assert!(other_model.search("notes", "product launch", 1).await.unwrap().is_empty());
+
+    store.remove("notes", "launch").await.unwrap();
+    let matches = store.search("notes", "product launch", 3).await.unwrap();
+    assert!(matches.iter().all(|m| m.key != "launch"));
+}
+
+#[tokio::test]
+async fn test_long_term_memory_recalls_by_similarity() {
+    let db = Database::new(":memory:").await.unwrap();
+    let store = VectorStore::new(db.clone(), Arc::new(HashEmbedder::default()));
+    let mut ltm = LongTermMemory::load(db, LOCAL_USER, "Nova").await.unwrap().with_vectors(store);
+
+    let pets = ltm.add_memory("user", "Sam's dogs love long walks", 0.6).await.unwrap();
+    ltm.add_memory("user", "Sam works as a nurse on night shifts", 0.6).await.unwrap();
+
+    // The question shares word stems with the memory, but no whole word
+    let recalled = ltm.recall("Walking the dog", RECALL_LIMIT).await;
+    assert_eq!(recalled.iter().map(|m| m.id).collect::<Vec<_>>(), vec![pets]);
+
+    ltm.forget(pets).await.unwrap();
+    assert!(ltm.recall("Walking the dog", RECALL_LIMIT).await.is_empty());
+}
+
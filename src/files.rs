@@ -0,0 +1,17 @@
+// src/files.rs
+use std::io;
+use std::path::Path;
+use tokio::fs;
+
+/// Writes `contents` next to `path` and then moves it into place, so
+/// readers see either the old file or the whole new one.
+pub async fn write_atomic(path: impl AsRef<Path>, contents: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).await?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents).await?;
+    fs::rename(&tmp, path).await
+}
@@ -0,0 +1,76 @@
+// src/knowledge_base/index.rs
+use std::collections::HashMap;
+
+/// BM25 term frequency saturation.
+const K1: f32 = 1.2;
+
+/// BM25 document length normalization.
+const B: f32 = 0.75;
+
+/// An inverted index ranking documents against a query with Okapi BM25.
+/// Documents are term lists and are referred to by their position.
+#[derive(Debug, Clone, Default)]
+pub struct Bm25Index {
+    /// Each term's documents with how often it occurs in them.
+    postings: HashMap<String, Vec<(usize, f32)>>,
+    lengths: Vec<f32>,
+    average_length: f32,
+}
+
+impl Bm25Index {
+    pub fn build(documents: &[Vec<String>]) -> Self {
+        let mut postings: HashMap<String, Vec<(usize, f32)>> = HashMap::new();
+        for (doc, terms) in documents.iter().enumerate() {
+            let mut counts: HashMap<&str, f32> = HashMap::new();
+            for term in terms {
+                *counts.entry(term.as_str()).or_default() += 1.0;
+            }
+            for (term, count) in counts {
+                postings.entry(term.to_string()).or_default().push((doc, count));
+            }
+        }
+
+        let lengths: Vec<f32> = documents.iter().map(|terms| terms.len() as f32).collect();
+        let average_length = if lengths.is_empty() {
+            0.0
+        } else {
+            lengths.iter().sum::<f32>() / lengths.len() as f32
+        };
+        Self { postings, lengths, average_length }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    /// The `limit` documents scoring highest for `query`, best first, leaving
+    /// out those sharing no term with it.
+    pub fn search(&self, query: &[String], limit: usize) -> Vec<(usize, f32)> {
+        let mut terms: Vec<&String> = query.iter().collect();
+        terms.sort();
+        terms.dedup();
+
+        let total = self.len() as f32;
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for term in terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let df = postings.len() as f32;
+            let idf = (1.0 + (total - df + 0.5) / (df + 0.5)).ln();
+            for &(doc, tf) in postings {
+                let norm = 1.0 - B + B * self.lengths[doc] / self.average_length.max(1.0);
+                *scores.entry(doc).or_default() += idf * tf * (K1 + 1.0) / (tf + K1 * norm);
+            }
+        }
+
+        let mut ranked: Vec<(usize, f32)> = scores.into_iter().filter(|(_, score)| *score > 0.0).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(limit);
+        ranked
+    }
+}
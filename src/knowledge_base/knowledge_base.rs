@@ -1,93 +1,510 @@
 // src/knowledge_base/knowledge_base.rs
-use serde::Deserialize;
-use std::fs;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
 use tokio::fs as tokio_fs;
-use serde_json;
+use tokio::sync::RwLock;
+use crate::files;
+use crate::text::tokenize;
+use crate::tokens;
+use super::index::Bm25Index;
+use super::markdown;
 
-#[derive(Debug, Deserialize, Clone)]
+/// Where the shared knowledge base is kept.
+pub const DEFAULT_KNOWLEDGE_BASE_PATH: &str = "data/knowledge_base.json";
+
+/// Entries put into a chat prompt.
+pub const KNOWLEDGE_LIMIT: usize = 3;
+
+/// Entries are cut to this many tokens in a prompt.
+const CITED_TOKENS: usize = 300;
+
+/// Longest snippet shown for a cited entry.
+const SNIPPET_CHARS: usize = 160;
+
+/// Times keywords and tags count for each time a word appears in the content.
+const KEYWORD_WEIGHT: usize = 2;
+
+/// Version of the knowledge base file written by this build.
+pub const KNOWLEDGE_BASE_VERSION: u32 = 1;
+
+/// Source of entries written by hand, including those from files that
+/// predate sources.
+pub const MANUAL_SOURCE: &str = "manual";
+
+/// Source of entries learned from conversations, one per topic.
+pub const LEARNED_SOURCE: &str = "learned";
+
+/// Source of entries imported from Markdown files.
+pub const MARKDOWN_SOURCE: &str = "markdown";
+
+#[derive(Error, Debug)]
+pub enum KnowledgeBaseError {
+    #[error("Knowledge base file error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid knowledge base: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Knowledge base version {0} is not supported (newest known is {KNOWLEDGE_BASE_VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("Knowledge base is neither a versioned file, a list of entries nor a map of topics")]
+    UnknownFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct KnowledgeEntry {
+    pub id: String,
     pub keywords: Vec<String>,
     pub content: String,
+    /// Where the entry came from, e.g. `MANUAL_SOURCE` or `LEARNED_SOURCE`.
+    pub source: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl KnowledgeEntry {
+    pub fn new(source: &str, keywords: Vec<String>, content: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            id: format!("{:016x}", rand::random::<u64>()),
+            keywords,
+            content: content.to_string(),
+            source: source.to_string(),
+            tags: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Changes to an entry; fields left out stay as they are.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct EntryUpdate {
+    pub content: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// What importing a directory of Markdown files did.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MarkdownImport {
+    /// Ids of the entries added.
+    pub added: Vec<String>,
+    /// Ids of entries from earlier imports that were brought up to date.
+    pub updated: Vec<String>,
+    /// Files left out, with why.
+    pub skipped: Vec<String>,
 }
 
+/// The knowledge base file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnowledgeBaseFile {
+    pub version: u32,
+    pub entries: Vec<KnowledgeEntry>,
+}
+
+/// An entry of the first files, a plain list of these.
+#[derive(Deserialize)]
+struct LegacyEntry {
+    #[serde(default)]
+    keywords: Vec<String>,
+    content: String,
+}
+
+impl KnowledgeBaseFile {
+    pub fn new(entries: Vec<KnowledgeEntry>) -> Self {
+        Self { version: KNOWLEDGE_BASE_VERSION, entries }
+    }
+
+    /// Reads a knowledge base in any of the shapes it has been stored in,
+    /// returning it and whether it had to be migrated. Besides the versioned
+    /// file these are a list of `{content, keywords}` entries and a map from
+    /// topic to text, as once written by the learning manager.
+    pub fn from_value(value: Value) -> Result<(Self, bool), KnowledgeBaseError> {
+        match value {
+            Value::Object(map) if map.contains_key("version") => {
+                let version = map.get("version").and_then(Value::as_u64).unwrap_or_default() as u32;
+                if version == 0 || version > KNOWLEDGE_BASE_VERSION {
+                    return Err(KnowledgeBaseError::UnsupportedVersion(version));
+                }
+                Ok((serde_json::from_value(Value::Object(map))?, false))
+            }
+            Value::Array(entries) => {
+                let entries = entries
+                    .into_iter()
+                    .map(|entry| {
+                        let legacy: LegacyEntry = serde_json::from_value(entry)?;
+                        Ok(KnowledgeEntry::new(MANUAL_SOURCE, legacy.keywords, &legacy.content))
+                    })
+                    .collect::<Result<Vec<_>, KnowledgeBaseError>>()?;
+                Ok((Self::new(entries), true))
+            }
+            Value::Object(map) => {
+                let entries = map
+                    .into_iter()
+                    .map(|(topic, content)| match content {
+                        Value::String(content) => Ok(KnowledgeEntry::new(LEARNED_SOURCE, vec![topic], &content)),
+                        _ => Err(KnowledgeBaseError::UnknownFormat),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((Self::new(entries), true))
+            }
+            _ => Err(KnowledgeBaseError::UnknownFormat),
+        }
+    }
+}
+
+/// Entries with the index over them, rebuilt whenever they change.
+struct Indexed {
+    entries: Vec<KnowledgeEntry>,
+    index: Bm25Index,
+}
+
+impl Indexed {
+    fn new(entries: Vec<KnowledgeEntry>) -> Self {
+        let documents: Vec<Vec<String>> = entries.iter().map(entry_terms).collect();
+        Self { index: Bm25Index::build(&documents), entries }
+    }
+}
+
+/// The shared knowledge base, kept in memory and written back to its file
+/// whenever it changes. Clones share the same entries.
 #[derive(Clone)]
 pub struct KnowledgeBaseHandler {
-    knowledge_base: Vec<KnowledgeEntry>,
+    state: Arc<RwLock<Indexed>>,
     file_path: String,
 }
 
 impl KnowledgeBaseHandler {
-    pub fn new(file_path: &str) -> Self {
-        // Load the knowledge base from the file
-        let knowledge_base = fs::read_to_string(file_path)
-            .map_err(|e| {
-                eprintln!("Failed to read knowledge base file: {}", e);
-                std::process::exit(1);
-            })
-            .and_then(|data| {
-                serde_json::from_str(&data).map_err(|e| {
-                    eprintln!("Failed to parse knowledge base file: {}", e);
-                    std::process::exit(1);
-                })
-            })
-            .unwrap_or_else(|_| vec![]);
+    /// Loads the knowledge base at `file_path`, starting empty when there is
+    /// none yet. Files in an older shape are migrated and rewritten, keeping
+    /// the original next to them with a `.bak` extension.
+    pub async fn load(file_path: &str) -> Result<Self, KnowledgeBaseError> {
+        let entries = match tokio_fs::read_to_string(file_path).await {
+            Ok(data) => {
+                let (file, migrated) = KnowledgeBaseFile::from_value(serde_json::from_str(&data)?)?;
+                if migrated {
+                    tokio_fs::write(format!("{}.bak", file_path), &data).await?;
+                    write_file(Path::new(file_path), &file).await?;
+                    log::info!("Migrated knowledge base {} to version {}", file_path, KNOWLEDGE_BASE_VERSION);
+                }
+                file.entries
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
 
-        Self {
-            knowledge_base,
+        Ok(Self {
+            state: Arc::new(RwLock::new(Indexed::new(entries))),
             file_path: file_path.to_string(),
-        }
+        })
     }
 
-    pub fn retrieve_information(&self, query: &str) -> String {
-        // Tokenize the query into words
-        let query_words: Vec<_> = query.split_whitespace().map(|s| s.to_lowercase()).collect();
+    pub async fn entries(&self) -> Vec<KnowledgeEntry> {
+        self.state.read().await.entries.clone()
+    }
 
-        // Find entries with matching keywords
-        let relevant_entries: Vec<_> = self
-            .knowledge_base
-            .iter()
-            .filter(|entry| {
-                entry
-                    .keywords
-                    .iter()
-                    .any(|kw| query_words.contains(&kw.to_lowercase()))
+    /// The `limit` entries most relevant to `query` by BM25 over their
+    /// content, keywords and tags, best first. Entries sharing no term with
+    /// the query are left out.
+    pub async fn search(&self, query: &str, limit: usize) -> Vec<KnowledgeHit> {
+        let terms = tokenize(query);
+        let state = self.state.read().await;
+        state.index
+            .search(&terms, limit)
+            .into_iter()
+            .map(|(position, score)| {
+                let entry = &state.entries[position];
+                KnowledgeHit {
+                    id: entry.id.clone(),
+                    content: entry.content.clone(),
+                    snippet: snippet(&entry.content, &terms),
+                    score,
+                }
             })
-            .collect();
+            .collect()
+    }
 
-        // Combine the content of relevant entries
-        relevant_entries
+    /// What has been learned about `topic`, if anything.
+    pub async fn get_entry(&self, topic: &str) -> Option<String> {
+        self.state
+            .read()
+            .await
+            .entries
             .iter()
+            .find(|entry| is_topic_entry(entry, topic))
             .map(|entry| entry.content.clone())
-            .collect::<Vec<_>>()
-            .join("\n")
     }
 
-    pub async fn get_entry(&self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
-        let content = tokio_fs::read_to_string(&self.file_path).await?;
-        let data: serde_json::Value = serde_json::from_str(&content)?;
-        
-        if let Some(value) = data.get(key) {
-            if let Some(str_value) = value.as_str() {
-                return Ok(Some(str_value.to_string()));
+    /// Sets what has been learned about `topic`.
+    pub async fn add_entry(&self, topic: &str, content: &str) -> Result<(), KnowledgeBaseError> {
+        self.modify(|entries| match entries.iter_mut().find(|entry| is_topic_entry(entry, topic)) {
+            Some(entry) => {
+                entry.content = content.to_string();
+                entry.updated_at = Utc::now();
             }
-        }
-        
-        Ok(None)
+            None => entries.push(KnowledgeEntry::new(LEARNED_SOURCE, vec![topic.to_string()], content)),
+        })
+        .await
+    }
+
+    /// The entry with `id`, or the only one whose id starts with it.
+    pub async fn get(&self, id: &str) -> Option<KnowledgeEntry> {
+        let state = self.state.read().await;
+        position(&state.entries, id).map(|i| state.entries[i].clone())
+    }
+
+    pub async fn add(&self, entry: KnowledgeEntry) -> Result<KnowledgeEntry, KnowledgeBaseError> {
+        let entry = normalized(entry);
+        self.modify(|entries| entries.push(entry.clone())).await?;
+        Ok(entry)
     }
 
-    pub async fn add_entry(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let content = tokio_fs::read_to_string(&self.file_path).await?;
-        let mut data: serde_json::Value = serde_json::from_str(&content)?;
-        
-        if let serde_json::Value::Object(ref mut map) = data {
-            map.insert(key.to_string(), serde_json::Value::String(value.to_string()));
+    /// Applies `update` to the entry `id` refers to, returning it as changed.
+    pub async fn update(&self, id: &str, update: EntryUpdate) -> Result<Option<KnowledgeEntry>, KnowledgeBaseError> {
+        self.modify(|entries| {
+            let i = position(entries, id)?;
+            entries[i] = updated(entries[i].clone(), update);
+            Some(entries[i].clone())
+        })
+        .await
+    }
+
+    /// Adds `add` to the tags of the entry `id` refers to and drops `remove`.
+    pub async fn tag(&self, id: &str, add: &[String], remove: &[String]) -> Result<Option<KnowledgeEntry>, KnowledgeBaseError> {
+        let remove: Vec<String> = remove.iter().map(|tag| tag.trim().to_lowercase()).collect();
+        self.modify(|entries| {
+            let i = position(entries, id)?;
+            let tags = entries[i].tags
+                .iter()
+                .chain(add)
+                .filter(|tag| !remove.contains(&tag.trim().to_lowercase()))
+                .cloned()
+                .collect();
+            entries[i] = updated(entries[i].clone(), EntryUpdate { tags: Some(tags), ..Default::default() });
+            Some(entries[i].clone())
+        })
+        .await
+    }
+
+    /// Removes the entry `id` refers to, returning it.
+    pub async fn delete(&self, id: &str) -> Result<Option<KnowledgeEntry>, KnowledgeBaseError> {
+        self.modify(|entries| position(entries, id).map(|i| entries.remove(i))).await
+    }
+
+    /// Adds an entry for each Markdown file in `dir`, whose front-matter
+    /// gives its keywords, tags and optionally its id; otherwise the id is
+    /// made from the file name. Importing the same files again updates the
+    /// entries they created.
+    pub async fn import_markdown(&self, dir: impl AsRef<Path>) -> Result<MarkdownImport, KnowledgeBaseError> {
+        let mut paths = Vec::new();
+        let mut listing = tokio_fs::read_dir(dir).await?;
+        while let Some(item) = listing.next_entry().await? {
+            let path = item.path();
+            let is_markdown = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"));
+            if is_markdown && item.file_type().await?.is_file() {
+                paths.push(path);
+            }
         }
-        
-        tokio_fs::write(&self.file_path, serde_json::to_string_pretty(&data)?).await?;
-        Ok(())
+        paths.sort();
+
+        let mut notes = Vec::new();
+        let mut report = MarkdownImport::default();
+        for path in paths {
+            let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            let note = markdown::parse(&tokio_fs::read_to_string(&path).await?);
+            let id = note.id.clone().unwrap_or_else(|| slug(path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default().as_ref()));
+            if note.content.is_empty() || id.is_empty() {
+                report.skipped.push(format!("{}: nothing to import", name));
+            } else {
+                notes.push((name, id, note));
+            }
+        }
+
+        self.modify(|entries| {
+            for (name, id, note) in notes {
+                match entries.iter_mut().find(|entry| entry.id == id) {
+                    Some(entry) if entry.source == MARKDOWN_SOURCE => {
+                        entry.content = note.content;
+                        entry.keywords = note.keywords;
+                        entry.tags = note.tags;
+                        entry.updated_at = Utc::now();
+                        *entry = normalized(entry.clone());
+                        report.updated.push(id);
+                    }
+                    Some(_) => report.skipped.push(format!("{}: id {} belongs to another entry", name, id)),
+                    None => {
+                        let mut entry = KnowledgeEntry::new(MARKDOWN_SOURCE, note.keywords, &note.content);
+                        entry.id = id.clone();
+                        entry.tags = note.tags;
+                        entries.push(normalized(entry));
+                        report.added.push(id);
+                    }
+                }
+            }
+        })
+        .await?;
+        Ok(report)
+    }
+
+    /// Replaces every entry with `entries`.
+    pub async fn replace_entries(&self, entries: Vec<KnowledgeEntry>) -> Result<(), KnowledgeBaseError> {
+        self.modify(|stored| *stored = entries).await
     }
 
-    pub async fn update_entry(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.add_entry(key, value).await
+    /// Changes a copy of the entries with `change` and saves it, then
+    /// re-indexes and serves it. The entries are left as they were when the
+    /// file cannot be written.
+    async fn modify<R>(&self, change: impl FnOnce(&mut Vec<KnowledgeEntry>) -> R) -> Result<R, KnowledgeBaseError> {
+        let mut state = self.state.write().await;
+        let mut entries = state.entries.clone();
+        let result = change(&mut entries);
+        let file = KnowledgeBaseFile::new(entries);
+        write_file(Path::new(&self.file_path), &file).await?;
+        *state = Indexed::new(file.entries);
+        Ok(result)
     }
-}
\ No newline at end of file
+}
+
+/// A knowledge base entry found for a query.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KnowledgeHit {
+    pub id: String,
+    pub content: String,
+    /// The sentence of the content that best matches the query.
+    pub snippet: String,
+    pub score: f32,
+}
+
+/// Entries found for a chat message as numbered notes for the system
+/// prompt, asking the model to cite the ones it uses.
+pub fn format_citations(hits: &[KnowledgeHit]) -> String {
+    let notes = hits
+        .iter()
+        .enumerate()
+        .map(|(i, hit)| format!("[{}] {}", i + 1, tokens::truncate_to_tokens(&hit.content, CITED_TOKENS)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "Notes from the knowledge base. When you use one, cite it by its number, like [1]:\n{}",
+        notes
+    )
+}
+
+/// The hits `reply` cites, with their numbers, in order.
+pub fn cited<'a>(reply: &str, hits: &'a [KnowledgeHit]) -> Vec<(usize, &'a KnowledgeHit)> {
+    hits.iter()
+        .enumerate()
+        .map(|(i, hit)| (i + 1, hit))
+        .filter(|(number, _)| reply.contains(&format!("[{}]", number)))
+        .collect()
+}
+
+/// The terms an entry is indexed under; keywords and tags count extra.
+fn entry_terms(entry: &KnowledgeEntry) -> Vec<String> {
+    let mut terms = tokenize(&entry.content);
+    let labels = tokenize(&format!("{} {}", entry.keywords.join(" "), entry.tags.join(" ")));
+    for _ in 0..KEYWORD_WEIGHT {
+        terms.extend(labels.iter().cloned());
+    }
+    terms
+}
+
+/// The sentence of `content` sharing the most terms with the query, cut to
+/// `SNIPPET_CHARS`.
+fn snippet(content: &str, terms: &[String]) -> String {
+    let best = content
+        .split_inclusive(['.', '!', '?', '\n'])
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .enumerate()
+        .max_by_key(|(i, sentence)| {
+            let words = tokenize(sentence);
+            let matches = terms.iter().filter(|term| words.contains(term)).count();
+            (matches, std::cmp::Reverse(*i))
+        })
+        .map(|(_, sentence)| sentence)
+        .unwrap_or("");
+
+    if best.chars().count() <= SNIPPET_CHARS {
+        best.to_string()
+    } else {
+        format!("{}…", best.chars().take(SNIPPET_CHARS).collect::<String>().trim_end())
+    }
+}
+
+/// Where the entry `id` refers to is: the one with that id, or else the only
+/// one whose id starts with it.
+fn position(entries: &[KnowledgeEntry], id: &str) -> Option<usize> {
+    let id = id.trim();
+    if id.is_empty() {
+        return None;
+    }
+    if let Some(i) = entries.iter().position(|entry| entry.id == id) {
+        return Some(i);
+    }
+    let mut matches = entries.iter().enumerate().filter(|(_, entry)| entry.id.starts_with(id));
+    match (matches.next(), matches.next()) {
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
+}
+
+/// `entry` with the fields `update` sets replaced.
+fn updated(mut entry: KnowledgeEntry, update: EntryUpdate) -> KnowledgeEntry {
+    if let Some(content) = update.content {
+        entry.content = content;
+    }
+    if let Some(keywords) = update.keywords {
+        entry.keywords = keywords;
+    }
+    if let Some(tags) = update.tags {
+        entry.tags = tags;
+    }
+    entry.updated_at = Utc::now();
+    normalized(entry)
+}
+
+/// `entry` with its keywords trimmed and its tags lower-cased, without
+/// blanks or duplicates.
+fn normalized(mut entry: KnowledgeEntry) -> KnowledgeEntry {
+    entry.content = entry.content.trim().to_string();
+    entry.keywords = dedup(entry.keywords.iter().map(|keyword| keyword.trim().to_string()));
+    entry.tags = dedup(entry.tags.iter().map(|tag| tag.trim().to_lowercase()));
+    entry
+}
+
+fn dedup(items: impl Iterator<Item = String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for item in items {
+        if !item.is_empty() && !unique.contains(&item) {
+            unique.push(item);
+        }
+    }
+    unique
+}
+
+/// Lower-cased letters and digits of `name`, with dashes between words.
+fn slug(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn is_topic_entry(entry: &KnowledgeEntry, topic: &str) -> bool {
+    entry.source == LEARNED_SOURCE && entry.keywords.iter().any(|keyword| keyword.eq_ignore_ascii_case(topic))
+}
+
+async fn write_file(path: &Path, file: &KnowledgeBaseFile) -> Result<(), KnowledgeBaseError> {
+    files::write_atomic(path, serde_json::to_string_pretty(file)?.as_bytes()).await?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+// src/knowledge_base/markdown.rs
+
+/// A Markdown note split into its front-matter fields and body.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkdownNote {
+    pub id: Option<String>,
+    pub keywords: Vec<String>,
+    pub tags: Vec<String>,
+    pub content: String,
+}
+
+/// Reads the `id`, `keywords` and `tags` of a front-matter block delimited by
+/// `---` lines at the top of `text`. Lists may be written inline
+/// (`keywords: [rust, async]` or `keywords: rust, async`) or as `- item`
+/// lines below the key. Other fields are ignored, and text without
+/// front-matter is all body.
+pub fn parse(text: &str) -> MarkdownNote {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut lines = text.lines();
+    if lines.next().map(str::trim_end) != Some("---") {
+        return MarkdownNote { content: text.trim().to_string(), ..Default::default() };
+    }
+
+    let mut note = MarkdownNote::default();
+    let mut current: Option<String> = None;
+    let mut body = Vec::new();
+    let mut in_front_matter = true;
+    for line in lines {
+        if !in_front_matter {
+            body.push(line);
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed == "---" || trimmed == "..." {
+            in_front_matter = false;
+        } else if let Some(item) = trimmed.strip_prefix("- ") {
+            if let Some(list) = current.as_deref().and_then(|key| list_for(&mut note, key)) {
+                list.extend(split_list(item));
+            }
+        } else if let Some((key, value)) = trimmed.split_once(':') {
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+            if key == "id" && !value.is_empty() {
+                note.id = Some(unquote(value).to_string());
+            } else if let Some(list) = list_for(&mut note, &key) {
+                list.extend(split_list(value));
+            }
+            current = Some(key);
+        }
+    }
+
+    if in_front_matter {
+        // No closing line, so it wasn't front-matter after all
+        return MarkdownNote { content: text.trim().to_string(), ..Default::default() };
+    }
+    note.content = body.join("\n").trim().to_string();
+    note
+}
+
+fn list_for<'a>(note: &'a mut MarkdownNote, key: &str) -> Option<&'a mut Vec<String>> {
+    match key {
+        "keywords" => Some(&mut note.keywords),
+        "tags" => Some(&mut note.tags),
+        _ => None,
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|item| unquote(item.trim()).to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim_matches(|c| c == '"' || c == '\'')
+}
@@ -1 +1,6 @@
+pub mod index;
 pub mod knowledge_base;
+pub mod markdown;
+
+#[cfg(test)]
+mod tests;
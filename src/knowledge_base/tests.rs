@@ -0,0 +1,195 @@
+use super::index::Bm25Index;
+use crate::text::tokenize;
+use super::knowledge_base::*;
+use super::markdown;
+use tempfile::TempDir;
+
+fn path_in(dir: &TempDir) -> String {
+    dir.path().join("knowledge_base.json").to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_entry_lists_are_migrated_and_backed_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = path_in(&dir);
+    let legacy = r#"[{"content": "Rust is fast", "keywords": ["rust"]}]"#;
+    tokio::fs::write(&path, legacy).await.unwrap();
+
+    let knowledge_base = KnowledgeBaseHandler::load(&path).await.unwrap();
+    let entries = knowledge_base.entries().await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].source, MANUAL_SOURCE);
+    assert_eq!(entries[0].keywords, vec!["rust"]);
+
+    let stored: KnowledgeBaseFile = serde_json::from_str(&tokio::fs::read_to_string(&path).await.unwrap()).unwrap();
+    assert_eq!(stored.version, KNOWLEDGE_BASE_VERSION);
+    assert_eq!(stored.entries, entries);
+    assert_eq!(tokio::fs::read_to_string(format!("{}.bak", path)).await.unwrap(), legacy);
+}
+
+#[tokio::test]
+async fn test_topic_maps_are_migrated_to_learned_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = path_in(&dir);
+    tokio::fs::write(&path, r#"{"ownership": "Each value has one owner."}"#).await.unwrap();
+
+    let knowledge_base = KnowledgeBaseHandler::load(&path).await.unwrap();
+    assert_eq!(knowledge_base.get_entry("Ownership").await.as_deref(), Some("Each value has one owner."));
+    assert_eq!(knowledge_base.entries().await[0].source, LEARNED_SOURCE);
+}
+
+#[tokio::test]
+async fn test_learned_topics_are_updated_in_place_and_persisted() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = path_in(&dir);
+
+    let knowledge_base = KnowledgeBaseHandler::load(&path).await.unwrap();
+    assert!(knowledge_base.entries().await.is_empty());
+    knowledge_base.add_entry("tokio", "An async runtime.").await.unwrap();
+    knowledge_base.add_entry("tokio", "An async runtime for Rust.").await.unwrap();
+
+    let reloaded = KnowledgeBaseHandler::load(&path).await.unwrap();
+    let entries = reloaded.entries().await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].content, "An async runtime for Rust.");
+    assert!(entries[0].updated_at >= entries[0].created_at);
+    assert!(!dir.path().join("knowledge_base.json.tmp").exists());
+}
+
+#[tokio::test]
+async fn test_unreadable_files_are_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = path_in(&dir);
+
+    tokio::fs::write(&path, "{not json").await.unwrap();
+    assert!(matches!(KnowledgeBaseHandler::load(&path).await, Err(KnowledgeBaseError::Json(_))));
+
+    tokio::fs::write(&path, r#"{"version": 7, "entries": []}"#).await.unwrap();
+    assert!(matches!(KnowledgeBaseHandler::load(&path).await, Err(KnowledgeBaseError::UnsupportedVersion(7))));
+
+    tokio::fs::write(&path, "42").await.unwrap();
+    assert!(matches!(KnowledgeBaseHandler::load(&path).await, Err(KnowledgeBaseError::UnknownFormat)));
+}
+
+#[test]
+fn test_bm25_ranks_rarer_and_denser_matches_higher() {
+    let documents: Vec<Vec<String>> = [
+        "rust compiles to fast native code",
+        "tokio schedules async tasks for rust programs",
+        "python is an interpreted language",
+    ]
+    .iter()
+    .map(|text| tokenize(text))
+    .collect();
+    let index = Bm25Index::build(&documents);
+
+    let ranked = index.search(&tokenize("async rust"), 10);
+    assert_eq!(ranked.iter().map(|(doc, _)| *doc).collect::<Vec<_>>(), vec![1, 0]);
+    assert!(index.search(&tokenize("the and of"), 10).is_empty());
+}
+
+#[tokio::test]
+async fn test_search_returns_ranked_hits_with_snippets() {
+    let dir = tempfile::tempdir().unwrap();
+    let knowledge_base = KnowledgeBaseHandler::load(&path_in(&dir)).await.unwrap();
+    let mut tokio = KnowledgeEntry::new(MANUAL_SOURCE, vec!["tokio".to_string()], "Tokio is a runtime. It schedules async tasks on a thread pool.");
+    tokio.tags = vec!["async".to_string()];
+    let rust = KnowledgeEntry::new(MANUAL_SOURCE, vec!["rust".to_string()], "Rust is a systems language with async support.");
+    knowledge_base.replace_entries(vec![rust.clone(), tokio.clone()]).await.unwrap();
+
+    let hits = knowledge_base.search("how are async tasks scheduled?", 5).await;
+    assert_eq!(hits.iter().map(|hit| hit.id.as_str()).collect::<Vec<_>>(), vec![tokio.id.as_str(), rust.id.as_str()]);
+    assert_eq!(hits[0].snippet, "It schedules async tasks on a thread pool.");
+    assert!(hits[0].score > hits[1].score);
+
+    let notes = format_citations(&hits);
+    assert!(notes.contains("[1] Tokio is a runtime."));
+    let cited = cited("Tokio runs them on a pool [1].", &hits);
+    assert_eq!(cited.len(), 1);
+    assert_eq!(cited[0].0, 1);
+}
+
+#[test]
+fn test_markdown_front_matter_gives_keywords_and_tags() {
+    let note = markdown::parse("---\nid: tokio-intro\nkeywords: [tokio, \"async runtime\"]\ntags:\n  - Rust\n  - guides\ntitle: ignored\n---\n# Tokio\n\nTokio runs async tasks.\n");
+    assert_eq!(note.id.as_deref(), Some("tokio-intro"));
+    assert_eq!(note.keywords, vec!["tokio", "async runtime"]);
+    assert_eq!(note.tags, vec!["Rust", "guides"]);
+    assert_eq!(note.content, "# Tokio\n\nTokio runs async tasks.");
+
+    let plain = markdown::parse("Just text\n---\nmore");
+    assert_eq!(plain.content, "Just text\n---\nmore");
+    assert!(plain.keywords.is_empty());
+}
+
+#[tokio::test]
+async fn test_markdown_folders_are_imported_and_reimported() {
+    let dir = tempfile::tempdir().unwrap();
+    let notes = dir.path().join("notes");
+    std::fs::create_dir(&notes).unwrap();
+    std::fs::write(notes.join("Async Rust.md"), "---\nkeywords: async, rust\ntags: [Guide]\n---\nFutures are lazy.").unwrap();
+    std::fs::write(notes.join("empty.md"), "---\nkeywords: nothing\n---\n").unwrap();
+    std::fs::write(notes.join("ignored.txt"), "Not Markdown").unwrap();
+
+    let knowledge_base = KnowledgeBaseHandler::load(&path_in(&dir)).await.unwrap();
+    let report = knowledge_base.import_markdown(&notes).await.unwrap();
+    assert_eq!(report.added, vec!["async-rust"]);
+    assert_eq!(report.skipped, vec!["empty.md: nothing to import"]);
+
+    let entry = knowledge_base.get("async-rust").await.unwrap();
+    assert_eq!(entry.source, MARKDOWN_SOURCE);
+    assert_eq!(entry.keywords, vec!["async", "rust"]);
+    assert_eq!(entry.tags, vec!["guide"]);
+
+    std::fs::write(notes.join("Async Rust.md"), "Futures do nothing until polled.").unwrap();
+    let report = knowledge_base.import_markdown(&notes).await.unwrap();
+    assert_eq!(report.updated, vec!["async-rust"]);
+    assert_eq!(knowledge_base.entries().await.len(), 1);
+    assert_eq!(knowledge_base.search("polled futures", 1).await[0].id, "async-rust");
+}
+
+#[tokio::test]
+async fn test_entries_are_edited_tagged_and_deleted_by_id_prefix() {
+    let dir = tempfile::tempdir().unwrap();
+    let knowledge_base = KnowledgeBaseHandler::load(&path_in(&dir)).await.unwrap();
+    let mut first = KnowledgeEntry::new(MANUAL_SOURCE, vec!["rust".to_string()], "Rust is fast");
+    first.id = "abc123".to_string();
+    let mut second = KnowledgeEntry::new(MANUAL_SOURCE, Vec::new(), "Go is simple");
+    second.id = "abd456".to_string();
+    knowledge_base.add(first).await.unwrap();
+    knowledge_base.add(second).await.unwrap();
+
+    assert!(knowledge_base.get("ab").await.is_none());
+    let update = EntryUpdate { content: Some("Rust is fast and safe".to_string()), ..Default::default() };
+    let edited = knowledge_base.update("abc", update).await.unwrap().unwrap();
+    assert_eq!(edited.content, "Rust is fast and safe");
+    assert_eq!(edited.keywords, vec!["rust"]);
+
+    let tagged = knowledge_base.tag("abc", &["Systems".to_string(), "draft".to_string()], &[]).await.unwrap().unwrap();
+    assert_eq!(tagged.tags, vec!["systems", "draft"]);
+    let tagged = knowledge_base.tag("abc", &[], &["DRAFT".to_string()]).await.unwrap().unwrap();
+    assert_eq!(tagged.tags, vec!["systems"]);
+
+    assert_eq!(knowledge_base.delete("abd").await.unwrap().unwrap().id, "abd456");
+    assert!(knowledge_base.delete("abd").await.unwrap().is_none());
+
+    let reloaded = KnowledgeBaseHandler::load(&path_in(&dir)).await.unwrap();
+    assert_eq!(reloaded.entries().await, knowledge_base.entries().await);
+}
+
+#[tokio::test]
+async fn test_failed_writes_leave_the_entries_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = path_in(&dir);
+    let knowledge_base = KnowledgeBaseHandler::load(&path).await.unwrap();
+    knowledge_base.add_entry("tokio", "An async runtime.").await.unwrap();
+    let before = knowledge_base.entries().await;
+
+    // Saving writes a temporary file next to the real one first
+    tokio::fs::create_dir(format!("{}.tmp", path)).await.unwrap();
+    assert!(knowledge_base.add_entry("serde", "A serialization framework.").await.is_err());
+    assert!(knowledge_base.tag(&before[0].id, &["runtime".to_string()], &[]).await.is_err());
+
+    assert_eq!(knowledge_base.entries().await, before);
+    assert!(knowledge_base.search("serialization", 5).await.is_empty());
+}
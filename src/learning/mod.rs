@@ -1,23 +1,111 @@
-use crate::database::Database;
-use crate::knowledge_base::knowledge_base::KnowledgeBaseHandler;
-use log::info;
+use crate::completion::{ChatMessage, CompletionError, CompletionProvider};
+use crate::database::{self, Database, DatabaseError, LOCAL_USER};
+use crate::knowledge_base::index::Bm25Index;
+use crate::knowledge_base::knowledge_base::{KnowledgeBaseHandler, LEARNED_SOURCE};
+use crate::structured::JsonCompletion;
+use crate::text::tokenize;
+use crate::tokens;
+use log::{info, warn};
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Insight {
+mod queue;
+
+pub use queue::{Learner, LearningConfig, LearningQueue};
+
+#[cfg(test)]
+mod tests;
+
+/// Words that mark a statement as uncertain.
+const HEDGES: &[&str] = &[
+    "apparently", "could", "guess", "likely", "maybe", "might", "perhaps", "possibly", "probably", "seems",
+    "suppose", "unclear", "unsure",
+];
+
+/// Verbs the offline extractor reads as the predicate of a statement.
+const PREDICATES: &[&str] = &[
+    "allows", "are", "contains", "creates", "enforce", "enforces", "had", "has", "have", "includes", "is", "like",
+    "likes", "live", "lives", "love", "loves", "makes", "means", "need", "needs", "own", "owns", "prefer",
+    "prefers", "provides", "rejects", "requires", "returns", "runs", "stores", "supports", "use", "uses", "want",
+    "wants", "was", "were", "work", "works",
+];
+
+/// Subjects that mean nothing without the sentence before them.
+const PRONOUNS: &[&str] = &[
+    "he", "here", "it", "she", "that", "there", "these", "they", "this", "those", "we", "what", "which", "who",
+];
+
+/// Words left off the front of subjects and topics.
+const DETERMINERS: &[&str] = &["a", "all", "an", "any", "each", "every", "some", "the"];
+
+/// Longest subject the offline extractor accepts, in words.
+const MAX_SUBJECT_WORDS: usize = 4;
+
+/// Longest object the offline extractor keeps, in words.
+const MAX_OBJECT_WORDS: usize = 24;
+
+/// Each side of an exchange is cut to this many tokens for extraction.
+const EXCHANGE_TOKENS: usize = 1500;
+
+/// Known topics offered to the model to reuse.
+const KNOWN_TOPICS_IN_PROMPT: usize = 40;
+
+/// Learned facts recalled for a query.
+pub const RELEVANT_FACTS: usize = 5;
+
+/// Topic of the facts about the user themselves.
+pub const USER_TOPIC: &str = "user";
+
+/// Something learned from an exchange, as a subject, a predicate and an
+/// object, e.g. ("user's cat", "is", "called Miso").
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Fact {
+    /// Canonical name of what the fact is filed under.
     pub topic: String,
-    pub context: String,
+    pub subject: String,
+    pub predicate: String,
+    pub object: String,
     pub confidence: f32,
+    /// `model`, or `user_input` or `ai_response` for facts the offline
+    /// extractor found.
     pub source: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+impl Fact {
+    pub fn statement(&self) -> String {
+        format!("{} {} {}", self.subject, self.predicate, self.object)
+    }
+
+    /// Facts with the same key say the same thing in different words.
+    fn key(&self) -> Vec<String> {
+        tokenize(&self.statement())
+    }
+}
+
+/// What the model is asked to return for an interaction.
+#[derive(Deserialize)]
+struct ExtractedLearning {
+    #[serde(default)]
+    topics: Vec<String>,
+    #[serde(default)]
+    facts: Vec<ExtractedFact>,
+}
+
+#[derive(Deserialize)]
+struct ExtractedFact {
+    subject: String,
+    predicate: String,
+    object: String,
+    #[serde(default)]
+    topic: String,
+    confidence: f32,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LearningContext {
-    pub insights: Vec<Insight>,
+    pub facts: Vec<Fact>,
     pub related_topics: Vec<String>,
     pub metadata: HashMap<String, String>,
 }
@@ -25,84 +113,274 @@ pub struct LearningContext {
 impl LearningContext {
     pub fn new() -> Self {
         Self {
-            insights: Vec::new(),
+            facts: Vec::new(),
             related_topics: Vec::new(),
             metadata: HashMap::new(),
         }
     }
 
+    /// Finds facts without a model: sentences shaped like "<subject> <verb>
+    /// <object>" whose verb is a common predicate. Questions, and statements
+    /// about a pronoun, are skipped. Deterministic, for offline use and tests.
     pub fn extract_from_interaction(user_input: &str, ai_response: &str) -> Self {
         let mut context = Self::new();
         let now = chrono::Utc::now();
 
-        // Extract topics and insights from user input
-        let topics = Self::extract_topics(user_input);
-        for topic in &topics {
-            context.related_topics.push(topic.clone());
-            context.insights.push(Insight {
-                topic: topic.clone(),
-                context: user_input.to_string(),
-                confidence: 0.7,
-                source: "user_input".to_string(),
-                timestamp: now,
-            });
+        let sides = [(user_input, ai_response, "user_input"), (ai_response, user_input, "ai_response")];
+        for (text, other, source) in sides {
+            // A subject the other side also mentions is more likely on point
+            let other_terms = tokenize(other);
+            for sentence in text.split_inclusive(['.', '!', '?', '\n']).map(str::trim) {
+                if sentence.ends_with('?') {
+                    continue;
+                }
+                let Some((subject, predicate, object)) = parse_statement(sentence, source == "user_input") else {
+                    continue;
+                };
+                let Some(topic) = canonical_topic(&subject) else {
+                    continue;
+                };
+                let corroborated = tokenize(&subject).iter().any(|term| other_terms.contains(term));
+                context.push(Fact {
+                    topic,
+                    subject,
+                    predicate,
+                    object,
+                    confidence: Self::estimate_confidence(sentence, corroborated),
+                    source: source.to_string(),
+                    timestamp: now,
+                });
+            }
         }
 
-        // Extract insights from AI response
-        let response_insights = Self::extract_insights(ai_response);
-        for (topic, content) in response_insights {
-            context.insights.push(Insight {
+        context
+    }
+
+    /// Asks the model for the facts in an interaction, offering it the
+    /// `known_topics` so that it files new facts under the same names.
+    pub async fn extract_with(
+        provider: &dyn CompletionProvider,
+        user_input: &str,
+        ai_response: &str,
+        known_topics: &[String],
+    ) -> Result<Self, CompletionError> {
+        let known = if known_topics.is_empty() {
+            String::new()
+        } else {
+            let topics: Vec<&str> = known_topics.iter().take(KNOWN_TOPICS_IN_PROMPT).map(String::as_str).collect();
+            format!("Topics already known; reuse one of these names when it fits: {}\n\n", topics.join(", "))
+        };
+        let prompt = format!(
+            r#"Extract the facts worth remembering from this exchange and return them as a JSON object with these fields:
+"topics": (array of strings) Canonical names of the subjects discussed: short lower-case noun phrases like "rust ownership", never questions or sentence fragments
+"facts": (array) Facts that stay true after this conversation, each an object with
+    "subject": (string) What the fact is about; "user" for the user themselves
+    "predicate": (string) How the subject and object relate, e.g. "is", "prefers", "works at"
+    "object": (string) The rest of the fact
+    "topic": (string) One of the topics
+    "confidence": (number) How certain the fact is, between 0 and 1
+Leave out greetings, questions and small talk; return empty arrays when nothing is worth remembering.
+
+{}User: {}
+
+Assistant: {}"#,
+            known,
+            tokens::truncate_to_tokens(user_input, EXCHANGE_TOKENS),
+            tokens::truncate_to_tokens(ai_response, EXCHANGE_TOKENS)
+        );
+
+        let extracted: ExtractedLearning = provider.complete_json(&[ChatMessage::user(prompt)]).await?;
+        let now = chrono::Utc::now();
+
+        let mut context = Self::new();
+        context.related_topics = extracted.topics.iter().filter_map(|topic| canonical_topic(topic)).collect();
+        for fact in extracted.facts {
+            let (subject, predicate, object) = (fact.subject.trim(), fact.predicate.trim(), fact.object.trim());
+            if subject.is_empty() || predicate.is_empty() || object.is_empty() {
+                continue;
+            }
+            let Some(topic) = canonical_topic(&fact.topic).or_else(|| canonical_topic(subject)) else {
+                continue;
+            };
+            context.push(Fact {
                 topic,
-                context: content,
-                confidence: 0.8,
-                source: "ai_response".to_string(),
+                subject: subject.to_string(),
+                predicate: predicate.to_string(),
+                object: object.to_string(),
+                confidence: fact.confidence.clamp(0.0, 1.0),
+                source: "model".to_string(),
                 timestamp: now,
             });
         }
-
-        context
+        Ok(context)
     }
 
-    fn extract_topics(text: &str) -> Vec<String> {
-        let mut topics = Vec::new();
-        let words: Vec<&str> = text.split_whitespace().collect();
-        
-        // Simple topic extraction (can be enhanced with NLP)
-        for window in words.windows(2) {
-            if window[0].len() > 3 && window[1].len() > 3 {
-                topics.push(format!("{} {}", window[0], window[1]));
-            }
+    /// Adds `fact`, and its topic unless it is already listed.
+    fn push(&mut self, fact: Fact) {
+        if !self.related_topics.contains(&fact.topic) {
+            self.related_topics.push(fact.topic.clone());
         }
-
-        topics
+        self.facts.push(fact);
     }
 
-    fn extract_insights(text: &str) -> Vec<(String, String)> {
-        let mut insights = Vec::new();
-        let sentences: Vec<&str> = text.split(['.', '!', '?']).collect();
+    /// How sure a heuristically extracted statement is, between 0.1 and
+    /// 0.95: hedged statements and questions are less certain, ones the
+    /// rest of the exchange backs up and ones with concrete figures more.
+    fn estimate_confidence(text: &str, corroborated: bool) -> f32 {
+        let lower = text.to_lowercase();
+        let hedges = lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| HEDGES.contains(word))
+            .count()
+            .min(3);
 
-        for sentence in sentences {
-            let sentence = sentence.trim();
-            if sentence.len() > 20 {
-                if let Some(topic) = Self::identify_main_topic(sentence) {
-                    insights.push((topic, sentence.to_string()));
-                }
-            }
+        let mut confidence = 0.5 - 0.15 * hedges as f32;
+        if corroborated {
+            confidence += 0.25;
         }
+        if text.chars().any(|c| c.is_ascii_digit()) {
+            confidence += 0.05;
+        }
+        if text.trim_end().ends_with('?') {
+            confidence -= 0.1;
+        }
+        confidence.clamp(0.1, 0.95)
+    }
+}
+
+/// Splits `sentence` at its first known predicate into subject, predicate
+/// and object. "I" and "my" are the user when the user says them, as are
+/// "you" and "your" when the assistant does.
+fn parse_statement(sentence: &str, from_user: bool) -> Option<(String, String, String)> {
+    let words: Vec<&str> = sentence
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'' && c != ','))
+        .filter(|word| !word.is_empty())
+        .collect();
+    let verb = words
+        .iter()
+        .position(|word| PREDICATES.contains(&word.to_lowercase().as_str()))
+        .filter(|&i| i > 0)?;
+
+    // "However, Rust is fast" is about Rust
+    let start = words[..verb].iter().rposition(|word| word.ends_with(',')).map_or(0, |i| i + 1);
+    let mut subject: Vec<String> = words[start..verb]
+        .iter()
+        .map(|word| word.trim_end_matches(',').to_string())
+        .skip_while(|word| DETERMINERS.contains(&word.to_lowercase().as_str()))
+        .collect();
+    let (me, mine) = if from_user { ("i", "my") } else { ("you", "your") };
+    let (other_me, other_mine) = if from_user { ("you", "your") } else { ("i", "my") };
+    match subject.first().map(|word| word.to_lowercase()) {
+        None => return None,
+        Some(first) if PRONOUNS.contains(&first.as_str()) || first == other_me || first == other_mine => return None,
+        // "I really like" is not worth guessing about
+        Some(first) if first == me && subject.len() > 1 => return None,
+        Some(first) if first == me => subject[0] = USER_TOPIC.to_string(),
+        Some(first) if first == mine => subject[0] = format!("{}'s", USER_TOPIC),
+        Some(_) => {}
+    }
+    if subject.len() > MAX_SUBJECT_WORDS {
+        return None;
+    }
 
-        insights
+    let mut predicate = words[verb].to_lowercase();
+    if subject == [USER_TOPIC] {
+        // "I have" and "you have" become "user has"
+        predicate = match predicate.as_str() {
+            "have" => "has".to_string(),
+            "are" => "is".to_string(),
+            "were" => "was".to_string(),
+            verb if !verb.ends_with('s') && verb != "had" => format!("{}s", verb),
+            _ => predicate,
+        };
+    }
+    let mut rest = &words[verb + 1..];
+    if let Some(negation) = rest.first().filter(|word| ["not", "never"].contains(&word.to_lowercase().as_str())) {
+        predicate = format!("{} {}", predicate, negation.to_lowercase());
+        rest = &rest[1..];
+    }
+    let object = rest
+        .iter()
+        .take(MAX_OBJECT_WORDS)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(',')
+        .to_string();
+    if object.is_empty() {
+        return None;
     }
+    Some((subject.join(" "), predicate, object))
+}
 
-    fn identify_main_topic(sentence: &str) -> Option<String> {
-        let words: Vec<&str> = sentence.split_whitespace().collect();
-        words.first().map(|w| w.to_string())
+/// The canonical name of a topic: lower-case words without leading
+/// determiners or punctuation. Facts about the user or their belongings
+/// are all filed under `USER_TOPIC`.
+pub fn canonical_topic(name: &str) -> Option<String> {
+    let words: Vec<String> = name
+        .split_whitespace()
+        .map(|word| word.to_lowercase().chars().filter(|c| c.is_alphanumeric() || *c == '\'').collect::<String>())
+        .filter(|word| !word.is_empty())
+        .skip_while(|word| DETERMINERS.contains(&word.as_str()))
+        .collect();
+    match words.first().map(String::as_str) {
+        None => None,
+        Some(first) if first == USER_TOPIC || first == "user's" => Some(USER_TOPIC.to_string()),
+        Some(_) => Some(words.join(" ")),
     }
 }
 
+/// Learned facts as a list for a prompt.
+pub fn format_facts(facts: &[String]) -> String {
+    facts.iter().map(|fact| format!("• {}", fact)).collect::<Vec<_>>().join("\n")
+}
+
+/// `topic`, or the known topic that means the same, e.g. "borrow checkers"
+/// for "borrow checker".
+fn known_topic(topic: &str, known: &[String]) -> String {
+    let terms = sorted_terms(topic);
+    known
+        .iter()
+        .find(|name| sorted_terms(name) == terms)
+        .cloned()
+        .unwrap_or_else(|| topic.to_string())
+}
+
+fn sorted_terms(text: &str) -> Vec<String> {
+    let mut terms = tokenize(text);
+    terms.sort();
+    terms
+}
+
+/// Adds the `new` facts to the `stored` ones, skipping those already
+/// stored but keeping the higher confidence. Returns how many were added.
+fn merge_facts(stored: &mut Vec<Fact>, new: Vec<Fact>) -> usize {
+    let mut added = 0;
+    for fact in new {
+        let key = fact.key();
+        match stored.iter_mut().find(|existing| existing.key() == key) {
+            Some(existing) => existing.confidence = existing.confidence.max(fact.confidence),
+            None => {
+                stored.push(fact);
+                added += 1;
+            }
+        }
+    }
+    added
+}
+
+/// Learns from the exchanges between one user and one character. What it
+/// learns is kept for that pair alone; it only goes into (and is looked up
+/// from) the knowledge base every user shares when that is switched on with
+/// [`LearningManager::with_shared_knowledge`].
 pub struct LearningManager {
     db: Arc<Database>,
     knowledge_base: Arc<KnowledgeBaseHandler>,
-    context_cache: Arc<Mutex<HashMap<String, LearningContext>>>,
+    provider: Option<Arc<dyn CompletionProvider>>,
+    user: String,
+    character: String,
+    shared_knowledge: bool,
 }
 
 impl LearningManager {
@@ -110,111 +388,160 @@ impl LearningManager {
         Self {
             db: Arc::new(db),
             knowledge_base: Arc::new(knowledge_base),
-            context_cache: Arc::new(Mutex::new(HashMap::new())),
+            provider: None,
+            user: LOCAL_USER.to_string(),
+            character: String::new(),
+            shared_knowledge: false,
         }
     }
 
+    /// Learn for `user`'s conversations with `character` (by default the
+    /// local user, with no particular character).
+    pub fn with_scope(mut self, user: impl Into<String>, character: impl Into<String>) -> Self {
+        self.user = user.into();
+        self.character = character.into();
+        self
+    }
+
+    /// Also add what is learned to the shared knowledge base, and use it when
+    /// looking up context.
+    pub fn with_shared_knowledge(mut self, shared: bool) -> Self {
+        self.shared_knowledge = shared;
+        self
+    }
+
+    /// Key of learned knowledge of `kind` about `name`, private to this
+    /// manager's user and character.
+    fn scoped_key(&self, kind: &str, name: &str) -> String {
+        database::learned_knowledge_key(kind, &self.user, &self.character, name)
+    }
+
+    /// Extract facts with `provider` instead of the offline heuristics.
+    pub fn with_provider(mut self, provider: Arc<dyn CompletionProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// The facts learned so far, by topic.
+    pub async fn facts(&self) -> Result<Vec<(String, Vec<Fact>)>, DatabaseError> {
+        let prefix = self.scoped_key("fact", "");
+        let records = self.db.get_knowledge_with_prefix(prefix.clone()).await?;
+        Ok(records
+            .into_iter()
+            .filter_map(|record| {
+                let topic = record.key[prefix.len()..].to_string();
+                match serde_json::from_str(&record.value) {
+                    Ok(facts) => Some((topic, facts)),
+                    Err(e) => {
+                        warn!("Ignoring unreadable facts about {}: {}", topic, e);
+                        None
+                    }
+                }
+            })
+            .collect())
+    }
+
+    /// Forgets the facts learned about `topic`, or about the known topic
+    /// that means the same. Returns the name of the topic forgotten, if any
+    /// facts were filed under it. What went into the shared knowledge base
+    /// stays there.
+    pub async fn forget_topic(&self, topic: &str) -> Result<Option<String>, DatabaseError> {
+        let Some(topic) = canonical_topic(topic) else {
+            return Ok(None);
+        };
+        let known: Vec<String> = self.facts().await?.into_iter().map(|(topic, _)| topic).collect();
+        let topic = known_topic(&topic, &known);
+        let forgotten = self.db.delete_knowledge(self.scoped_key("fact", &topic)).await?;
+        Ok(forgotten.then_some(topic))
+    }
+
     pub async fn learn_from_interaction(
         &self,
         user_input: &str,
         ai_response: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // Extract learning context
-        let context = LearningContext::extract_from_interaction(user_input, ai_response);
-        
-        // Store insights in database
-        for insight in &context.insights {
-            self.db.save_knowledge(
-                format!("insight:{}:{}", insight.topic, insight.timestamp.timestamp()),
-                serde_json::to_string(&insight)?,
-            ).await?;
-        }
+        let stored = self.facts().await?;
+        let known: Vec<String> = stored.iter().map(|(topic, _)| topic.clone()).collect();
 
-        // Update knowledge base
-        for topic in &context.related_topics {
-            if let Some(existing) = self.knowledge_base.get_entry(topic).await? {
-                let mut entry = existing;
-                entry.push_str("\n");
-                entry.push_str(ai_response);
-                self.knowledge_base.update_entry(topic, &entry).await?;
-            } else {
-                self.knowledge_base.add_entry(topic, ai_response).await?;
-            }
-        }
+        // Extract facts, falling back to the heuristics if the model fails
+        let context = match &self.provider {
+            Some(provider) => LearningContext::extract_with(provider.as_ref(), user_input, ai_response, &known)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Model-based learning extraction failed: {}", e);
+                    LearningContext::extract_from_interaction(user_input, ai_response)
+                }),
+            None => LearningContext::extract_from_interaction(user_input, ai_response),
+        };
 
-        // Update context cache
-        let mut cache = self.context_cache.lock().await;
-        for topic in &context.related_topics {
-            cache.insert(topic.clone(), context.clone());
+        let mut by_topic: HashMap<String, Vec<Fact>> = HashMap::new();
+        for mut fact in context.facts {
+            fact.topic = known_topic(&fact.topic, &known);
+            by_topic.entry(fact.topic.clone()).or_default().push(fact);
         }
+        let mut stored: HashMap<String, Vec<Fact>> = stored.into_iter().collect();
 
-        info!("Learned from interaction: {} insights, {} topics", 
-            context.insights.len(), 
-            context.related_topics.len()
-        );
+        let (mut added, mut repeated) = (0, 0);
+        for (topic, facts) in by_topic {
+            let count = facts.len();
+            let facts_about = stored.entry(topic.clone()).or_default();
+            let new = merge_facts(facts_about, facts);
+            added += new;
+            repeated += count - new;
+            self.db.save_knowledge(self.scoped_key("fact", &topic), serde_json::to_string(&facts_about)?).await?;
 
+            // Add to what everyone knows about the topic, only if opted in
+            if self.shared_knowledge && new > 0 {
+                let mut lines: Vec<String> = self.knowledge_base
+                    .get_entry(&topic)
+                    .await
+                    .map(|entry| entry.lines().map(str::to_string).collect())
+                    .unwrap_or_default();
+                for fact in facts_about.iter() {
+                    let statement = fact.statement();
+                    if !lines.contains(&statement) {
+                        lines.push(statement);
+                    }
+                }
+                self.knowledge_base.add_entry(&topic, &lines.join("\n")).await?;
+            }
+        }
+
+        info!("Learned from interaction: {} new facts, {} already known", added, repeated);
         Ok(())
     }
 
+    /// The learned facts that best match `query`, and with shared knowledge
+    /// switched on, what everyone learned about the topics it names.
     pub async fn get_relevant_context(
         &self,
         query: &str,
     ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let mut context = Vec::new();
-
-        // Check context cache first
-        let cache = self.context_cache.lock().await;
-        for topic in LearningContext::extract_topics(query) {
-            if let Some(cached_context) = cache.get(&topic) {
-                for insight in &cached_context.insights {
-                    context.push(insight.context.clone());
-                }
-            }
-        }
+        let facts: Vec<Fact> = self.facts().await?.into_iter().flat_map(|(_, facts)| facts).collect();
+        let terms = tokenize(query);
 
-        // Get insights from database
-        let topics = LearningContext::extract_topics(query);
-        for topic in &topics {
-            if let Some(value) = self.db.get_knowledge(format!("topic:{}", topic)).await? {
-                if let Ok(insight) = serde_json::from_str::<Insight>(&value) {
-                    context.push(insight.context);
-                }
-            }
-        }
+        let documents: Vec<Vec<String>> = facts
+            .iter()
+            .map(|fact| tokenize(&format!("{} {}", fact.topic, fact.statement())))
+            .collect();
+        let mut context: Vec<String> = Bm25Index::build(&documents)
+            .search(&terms, RELEVANT_FACTS)
+            .into_iter()
+            .map(|(i, _)| facts[i].statement())
+            .collect();
 
-        // Get knowledge base entries
-        for topic in &topics {
-            if let Some(entry) = self.knowledge_base.get_entry(topic).await? {
-                context.push(entry);
+        if self.shared_knowledge {
+            let named = |topic: &String| {
+                let topic_terms = tokenize(topic);
+                !topic_terms.is_empty() && topic_terms.iter().all(|term| terms.contains(term))
+            };
+            for entry in self.knowledge_base.entries().await {
+                if entry.source == LEARNED_SOURCE && entry.keywords.iter().any(named) && !context.contains(&entry.content) {
+                    context.push(entry.content);
+                }
             }
         }
 
         Ok(context)
     }
-
-    pub async fn get_learning_summary(&self) -> Result<String, Box<dyn std::error::Error>> {
-        let cache = self.context_cache.lock().await;
-        let mut summary = String::new();
-
-        summary.push_str("Learning Summary:\n\n");
-
-        // Summarize topics
-        let mut all_topics = Vec::new();
-        for context in cache.values() {
-            all_topics.extend(context.related_topics.clone());
-        }
-        all_topics.sort();
-        all_topics.dedup();
-
-        summary.push_str(&format!("Topics Learned: {}\n", all_topics.join(", ")));
-
-        // Summarize insights
-        let mut total_insights = 0;
-        for context in cache.values() {
-            total_insights += context.insights.len();
-        }
-        summary.push_str(&format!("\nTotal Insights: {}\n", total_insights));
-
-        Ok(summary)
-    }
 }
@@ -0,0 +1,165 @@
+use crate::completion::CompletionProvider;
+use crate::database::Database;
+use crate::knowledge_base::knowledge_base::KnowledgeBaseHandler;
+use super::LearningManager;
+use log::warn;
+use std::env;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+/// Whether characters learn from their conversations, and whether what they
+/// learn is shared with every user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LearningConfig {
+    pub enabled: bool,
+    /// Also add learned facts to the shared knowledge base; see
+    /// [`LearningManager::with_shared_knowledge`].
+    pub shared_knowledge: bool,
+}
+
+impl Default for LearningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shared_knowledge: false,
+        }
+    }
+}
+
+impl LearningConfig {
+    /// Defaults overridden by `LEARNING` (`off` to disable) and
+    /// `LEARNING_SHARED`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let flag = |name: &str, default: bool| match env::var(name).map(|v| v.trim().to_lowercase()) {
+            Ok(v) if ["0", "false", "no", "off"].contains(&v.as_str()) => false,
+            Ok(v) if ["1", "true", "yes", "on"].contains(&v.as_str()) => true,
+            _ => default,
+        };
+        Self {
+            enabled: flag("LEARNING", defaults.enabled),
+            shared_knowledge: flag("LEARNING_SHARED", defaults.shared_knowledge),
+        }
+    }
+}
+
+enum Job {
+    Learn {
+        manager: LearningManager,
+        user_input: String,
+        ai_response: String,
+    },
+    Flush(oneshot::Sender<()>),
+}
+
+/// Learns from exchanges in a background task, one at a time and in the order
+/// they happened, so that replies never wait on fact extraction.
+#[derive(Clone)]
+pub struct LearningQueue {
+    db: Database,
+    knowledge_base: KnowledgeBaseHandler,
+    config: LearningConfig,
+    jobs: Option<mpsc::UnboundedSender<Job>>,
+}
+
+impl LearningQueue {
+    /// Starts the background task, unless `config` disables learning. Must
+    /// be called from within a Tokio runtime.
+    pub fn spawn(db: Database, knowledge_base: KnowledgeBaseHandler, config: LearningConfig) -> Self {
+        let jobs = config.enabled.then(|| {
+            let (sender, mut receiver) = mpsc::unbounded_channel();
+            tokio::spawn(async move {
+                while let Some(job) = receiver.recv().await {
+                    match job {
+                        Job::Learn { manager, user_input, ai_response } => {
+                            if let Err(e) = manager.learn_from_interaction(&user_input, &ai_response).await {
+                                warn!("Failed to learn from interaction: {}", e);
+                            }
+                        }
+                        Job::Flush(done) => {
+                            done.send(()).ok();
+                        }
+                    }
+                }
+            });
+            sender
+        });
+        Self { db, knowledge_base, config, jobs }
+    }
+
+    /// Learns for `user`'s conversations with `character`, extracting facts
+    /// with `provider`, or with the offline heuristics without one.
+    pub fn learner(&self, user: &str, character: &str, provider: Option<Arc<dyn CompletionProvider>>) -> Learner {
+        Learner {
+            queue: self.clone(),
+            user: user.to_string(),
+            character: character.to_string(),
+            provider,
+        }
+    }
+
+    /// Waits until every exchange queued so far has been learned from.
+    pub async fn flush(&self) {
+        let Some(jobs) = &self.jobs else {
+            return;
+        };
+        let (done, finished) = oneshot::channel();
+        if jobs.send(Job::Flush(done)).is_ok() {
+            finished.await.ok();
+        }
+    }
+}
+
+/// What one user teaches one character, queued on a [`LearningQueue`].
+#[derive(Clone)]
+pub struct Learner {
+    queue: LearningQueue,
+    user: String,
+    character: String,
+    provider: Option<Arc<dyn CompletionProvider>>,
+}
+
+impl Learner {
+    /// The manager of what this user and character have learned.
+    pub fn manager(&self) -> LearningManager {
+        let manager = LearningManager::new(self.queue.db.clone(), self.queue.knowledge_base.clone())
+            .with_scope(self.user.as_str(), self.character.as_str())
+            .with_shared_knowledge(self.queue.config.shared_knowledge);
+        match &self.provider {
+            Some(provider) => manager.with_provider(provider.clone()),
+            None => manager,
+        }
+    }
+
+    /// Waits until every exchange queued so far has been learned from.
+    pub async fn flush(&self) {
+        self.queue.flush().await;
+    }
+
+    /// Queues an exchange to learn from.
+    pub fn observe(&self, user_input: &str, ai_response: &str) {
+        let Some(jobs) = &self.queue.jobs else {
+            return;
+        };
+        let job = Job::Learn {
+            manager: self.manager(),
+            user_input: user_input.to_string(),
+            ai_response: ai_response.to_string(),
+        };
+        if jobs.send(job).is_err() {
+            warn!("Learning task has stopped; not learning from this interaction");
+        }
+    }
+
+    /// The learned facts relevant to `query`, for a prompt. Empty when
+    /// learning is disabled.
+    pub async fn relevant_context(&self, query: &str) -> Vec<String> {
+        if !self.queue.config.enabled {
+            return Vec::new();
+        }
+        self.manager().get_relevant_context(query).await.unwrap_or_else(|e| {
+            warn!("Failed to look up learned facts: {}", e);
+            Vec::new()
+        })
+    }
+}
@@ -0,0 +1,145 @@
+use super::*;
+use crate::providers::mock::mock::MockProvider;
+
+/// A learning manager for `user` and Nova with an empty knowledge base kept
+/// in `dir`.
+async fn manager(db: &Database, dir: &tempfile::TempDir, user: &str) -> LearningManager {
+    let path = dir.path().join("knowledge_base.json");
+    let knowledge_base = KnowledgeBaseHandler::load(path.to_str().unwrap()).await.unwrap();
+    LearningManager::new(db.clone(), knowledge_base).with_scope(user, "Nova")
+}
+
+#[tokio::test]
+async fn test_extract_with_model_reads_facts_and_canonical_topics() {
+    let provider = MockProvider::new([r#"{
+        "topics": ["The Rust Ownership"],
+        "facts": [
+            {"subject": "Each value", "predicate": "has", "object": "a single owner", "topic": "Rust ownership", "confidence": 1.4},
+            {"subject": "", "predicate": "is", "object": "nothing", "topic": "noise", "confidence": 0.5}
+        ]
+    }"#]);
+
+    let known = vec!["borrow checker".to_string()];
+    let context = LearningContext::extract_with(&provider, "How does ownership work?", "Each value has one owner.", &known)
+        .await
+        .unwrap();
+
+    assert_eq!(context.related_topics, vec!["rust ownership"]);
+    assert_eq!(context.facts.len(), 1);
+    assert_eq!(context.facts[0].statement(), "Each value has a single owner");
+    assert_eq!(context.facts[0].confidence, 1.0);
+    let prompt = provider.last_prompt().unwrap();
+    assert!(prompt.contains("User: How does ownership work?"));
+    assert!(prompt.contains("reuse one of these names when it fits: borrow checker"));
+}
+
+#[test]
+fn test_offline_extraction_reads_statements_not_word_pairs() {
+    let context = LearningContext::extract_from_interaction(
+        "What about Rust, is it fast? My cat is called Miso.",
+        "Rust is a systems language. It might be fast. You have a cute cat.",
+    );
+
+    let statements: Vec<String> = context.facts.iter().map(Fact::statement).collect();
+    assert_eq!(statements, vec!["user's cat is called Miso", "Rust is a systems language", "user has a cute cat"]);
+    assert_eq!(context.related_topics, vec!["user", "rust"]);
+}
+
+#[test]
+fn test_heuristic_confidence_reflects_the_exchange() {
+    let context = LearningContext::extract_from_interaction(
+        "Explain borrow checking please",
+        "Borrow checking rejects dangling references at compile time. Borrow checking is possibly relaxed in some future edition.",
+    );
+
+    let confidence = |text: &str| context.facts.iter().find(|f| f.statement().starts_with(text)).unwrap().confidence;
+    assert!(confidence("Borrow checking rejects") > confidence("Borrow checking is"));
+    assert!(context.facts.iter().all(|f| (0.1..=0.95).contains(&f.confidence)));
+}
+
+#[tokio::test]
+async fn test_facts_are_deduplicated_against_what_is_known() {
+    let db = Database::new(":memory:").await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let learning = manager(&db, &dir, "sam").await;
+
+    learning.learn_from_interaction("Tell me about borrowing", "The borrow checker enforces one mutable reference.")
+        .await
+        .unwrap();
+    learning.learn_from_interaction("And again?", "Borrow checkers enforce one mutable reference!")
+        .await
+        .unwrap();
+
+    // The model names the topic differently, but it is filed with the rest
+    let provider = Arc::new(MockProvider::new([r#"{"topics": ["Borrow Checkers"], "facts": [
+        {"subject": "The borrow checker", "predicate": "runs", "object": "at compile time", "topic": "Borrow Checkers", "confidence": 0.9}
+    ]}"#]));
+    let learning = learning.with_provider(provider.clone());
+    learning.learn_from_interaction("When does it run?", "At compile time.").await.unwrap();
+    assert!(provider.last_prompt().unwrap().contains("names when it fits: borrow checker"));
+
+    let facts = learning.facts().await.unwrap();
+    assert_eq!(facts.len(), 1);
+    assert_eq!(facts[0].0, "borrow checker");
+    assert_eq!(facts[0].1.len(), 2);
+}
+
+#[tokio::test]
+async fn test_learned_knowledge_is_kept_per_user_and_character() {
+    let db = Database::new(":memory:").await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let sam = manager(&db, &dir, "sam").await;
+    sam.learn_from_interaction("Explain borrow checking", "The borrow checker enforces one mutable reference.")
+        .await
+        .unwrap();
+    let context = sam.get_relevant_context("more about borrow checking").await.unwrap();
+    assert!(context.iter().any(|c| c.contains("one mutable reference")));
+
+    for other in [manager(&db, &dir, "alex").await, manager(&db, &dir, "sam").await.with_scope("sam", "Echo")] {
+        assert!(other.get_relevant_context("more about borrow checking").await.unwrap().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_shared_knowledge_collects_facts_under_the_topic() {
+    let db = Database::new(":memory:").await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let sam = manager(&db, &dir, "sam").await.with_shared_knowledge(true);
+    sam.learn_from_interaction("Tell me about Tokio", "Tokio runs async tasks on a thread pool.").await.unwrap();
+    let alex = manager(&db, &dir, "alex").await.with_shared_knowledge(true);
+    alex.learn_from_interaction("And Tokio?", "Tokio provides timers and channels.").await.unwrap();
+
+    let context = alex.get_relevant_context("what does tokio do").await.unwrap();
+    assert!(context.contains(&"Tokio runs async tasks on a thread pool\nTokio provides timers and channels".to_string()));
+}
+
+#[tokio::test]
+async fn test_queue_learns_in_the_background_until_a_topic_is_forgotten() {
+    let db = Database::new(":memory:").await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("knowledge_base.json");
+    let knowledge_base = KnowledgeBaseHandler::load(path.to_str().unwrap()).await.unwrap();
+
+    let queue = LearningQueue::spawn(db.clone(), knowledge_base.clone(), LearningConfig::default());
+    let sam = queue.learner("sam", "Nova", None);
+    sam.observe("Tell me about Tokio", "Tokio runs async tasks on a thread pool.");
+    sam.observe("My cat is called Miso.", "What a lovely name.");
+    queue.flush().await;
+
+    assert_eq!(sam.relevant_context("what does tokio do").await, vec!["Tokio runs async tasks on a thread pool"]);
+    assert!(queue.learner("alex", "Nova", None).relevant_context("what does tokio do").await.is_empty());
+
+    let manager = sam.manager();
+    assert_eq!(manager.forget_topic("The Tokio").await.unwrap(), Some("tokio".to_string()));
+    assert_eq!(manager.forget_topic("tokio").await.unwrap(), None);
+    let topics: Vec<String> = manager.facts().await.unwrap().into_iter().map(|(topic, _)| topic).collect();
+    assert_eq!(topics, vec![USER_TOPIC]);
+
+    let disabled = LearningConfig { enabled: false, ..LearningConfig::default() };
+    let quiet = LearningQueue::spawn(db, knowledge_base, disabled).learner("sam", "Nova", None);
+    quiet.observe("Tell me about Rust", "Rust is a systems language.");
+    quiet.flush().await;
+    assert!(quiet.relevant_context("my cat").await.is_empty());
+}
@@ -1,7 +1,16 @@
 pub mod memory;
 pub mod providers;
 pub mod completion;
+pub mod tokens;
+pub mod cache;
+pub mod embeddings;
+pub mod usage;
+pub mod structured;
+pub mod tools;
 pub mod knowledge_base;
+pub mod archive;
+pub mod files;
+pub mod text;
 pub mod database;
 pub mod learning;
 pub mod personality;
@@ -1,4 +1,3 @@
-use std::env;
 use std::io::Write;
 use std::path::Path;
 use std::fs::File;
@@ -12,10 +11,9 @@ use rustyline::history::DefaultHistory;
 use axum::serve;
 use tokio::net::TcpListener;
 
-use crate::providers::deepseek::deepseek::DeepSeekProvider;
-use crate::knowledge_base::knowledge_base::KnowledgeBaseHandler;
+use crate::providers::registry::ProviderRegistry;
+use crate::knowledge_base::knowledge_base::{KnowledgeBaseHandler, DEFAULT_KNOWLEDGE_BASE_PATH};
 use crate::database::Database;
-use crate::learning::LearningManager;
 use crate::personality::{Personality, PersonalityProfile};
 
 // Twitter integration
@@ -32,9 +30,18 @@ use crate::commands::CommandHandler;
 mod memory;
 mod providers;
 mod knowledge_base;
+mod archive;
+mod files;
+mod text;
 mod database;
 mod learning;
 mod completion;
+mod tokens;
+mod cache;
+mod embeddings;
+mod usage;
+mod structured;
+mod tools;
 mod personality;
 mod commands;
 mod api;
@@ -54,6 +61,10 @@ struct Args {
     #[arg(long)]
     character: Option<String>,
 
+    /// Provider to use by default (a name from data/providers.json, or deepseek/openai/ollama)
+    #[arg(long)]
+    provider: Option<String>,
+
     #[arg(long)]
     twitter_cookie: Option<String>,
 
@@ -84,11 +95,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Parse command line arguments
     let args = Args::parse();
 
-    // Get API key from command line or environment
-    let api_key = match &args.api_key {
-        Some(key) => key.clone(),
-        None => env::var("DEEPSEEK_API_KEY").expect("API key must be provided via --api-key or DEEPSEEK_API_KEY env var"),
-    };
+    // Load provider configuration; --api-key and --provider override the environment
+    let registry = load_registry(&args)?;
 
     // Initialize personality
     let mut current_personality = if let Some(character_file) = &args.character {
@@ -108,23 +116,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         Personality::Dynamic(profile) => profile.clone(),
     };
 
-    // Initialize Deepseek provider
-    let deepseek_provider = DeepSeekProvider::new(
-        api_key.clone(),
-        personality_profile.generate_system_prompt(),
-    ).await?;
-
-    // Initialize database
-    let database = Database::new("data/agent.db").await?;
-
     let result = if args.api {
-        run_api_server(args).await
+        run_api_server(args, registry).await
     } else {
         run_cli_mode(
             &args,
             personality_profile,
-            deepseek_provider,
-            database,
+            registry,
         ).await
     };
     
@@ -134,14 +132,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 async fn run_cli_mode(
     args: &Args,
     personality_profile: PersonalityProfile,
-    deepseek_provider: DeepSeekProvider,
-    database: Database,
+    registry: ProviderRegistry,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Initialize knowledge base handler
-    let knowledge_base_handler = KnowledgeBaseHandler::new("data/knowledge_base.json");
-
-    // Initialize learning manager
-    let learning_manager = LearningManager::new(database.clone(), knowledge_base_handler.clone());
+    let knowledge_base_handler = KnowledgeBaseHandler::load(DEFAULT_KNOWLEDGE_BASE_PATH).await?;
 
     // Initialize command handler
     let mut command_handler = CommandHandler::new(
@@ -156,7 +150,8 @@ async fn run_cli_mode(
         } else {
             None
         },
-        deepseek_provider,
+        registry,
+        knowledge_base_handler.clone(),
     ).await?;
 
     // Show initial help menu
@@ -190,9 +185,21 @@ async fn run_cli_mode(
             }
         }
     }
+    command_handler.finish_learning().await;
     Ok(())
 }
 
+fn load_registry(args: &Args) -> Result<ProviderRegistry, Box<dyn std::error::Error + Send + Sync>> {
+    let mut registry = ProviderRegistry::load_default()?;
+    if let Some(key) = &args.api_key {
+        registry = registry.with_api_key(key.clone());
+    }
+    if let Some(name) = &args.provider {
+        registry = registry.with_default(name.clone());
+    }
+    Ok(registry)
+}
+
 fn load_personality_from_filename(filename: &str) -> Option<Personality> {
     let path = Path::new("characters").join(filename);
     if path.exists() {
@@ -221,19 +228,13 @@ fn create_default_personality() -> Personality {
     })
 }
 
-async fn run_api_server(args: Args) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+async fn run_api_server(args: Args, registry: ProviderRegistry) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let addr: SocketAddr = format!("0.0.0.0:{}", args.port)
         .parse()
         .expect("Failed to parse address");
     
     println!("Starting API server on {}", addr);
     
-    // Get API key from command line or environment
-    let api_key = match &args.api_key {
-        Some(key) => key.clone(),
-        None => env::var("DEEPSEEK_API_KEY").expect("API key must be provided via --api-key or DEEPSEEK_API_KEY env var"),
-    };
-
     // Initialize personality
     let personality = if let Some(character_file) = &args.character {
         if let Some(Personality::Dynamic(profile)) = load_personality_from_filename(character_file) {
@@ -245,17 +246,15 @@ async fn run_api_server(args: Args) -> Result<(), Box<dyn std::error::Error + Se
         create_default_personality().into_dynamic_profile()
     };
     
-    // Initialize providers
-    let deepseek = DeepSeekProvider::new(
-        api_key.clone(),
-        personality.generate_system_prompt(),
-    ).await?;
+    // Fail early if the configured provider cannot be built
+    registry.for_character(&personality)?;
     
     // Initialize database
     let db = Database::new("data/agent.db").await?;
+    let knowledge_base = KnowledgeBaseHandler::load(DEFAULT_KNOWLEDGE_BASE_PATH).await?;
     
     println!("Initializing API routes...");
-    let app = crate::api::create_api(deepseek, personality, db).await;
+    let app = crate::api::create_api(registry, personality, db, knowledge_base).await;
     
     println!("API routes configured, attempting to bind to address...");
     
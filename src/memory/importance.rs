@@ -0,0 +1,67 @@
+// src/memory/importance.rs
+use chrono::{DateTime, NaiveDateTime, Utc};
+use crate::database::MemoryRecord;
+
+/// Days without being recalled after which a memory counts half as much.
+pub const HALF_LIFE_DAYS: f32 = 30.0;
+
+/// Share of the way towards full importance a memory moves each time it is
+/// recalled.
+pub const REINFORCEMENT: f32 = 0.1;
+
+/// Words that mark a statement worth remembering: preferences, plans,
+/// commitments and facts about people.
+const SIGNAL_WORDS: &[&str] = &[
+    "always", "birthday", "called", "deadline", "favorite", "favourite", "goal", "hate", "like", "live",
+    "love", "name", "need", "never", "plan", "prefer", "remember", "want", "work",
+];
+
+/// How worth remembering `text` is, between 0 and 1, judged when it is
+/// written: statements about the speaker, preferences and plans, and
+/// concrete details like numbers and dates score higher; questions and small
+/// talk lower.
+pub fn score(text: &str) -> f32 {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        return 0.0;
+    }
+
+    let mut importance = 0.2;
+    if words.iter().any(|w| matches!(*w, "i" | "i'm" | "i've" | "my" | "me" | "we" | "our")) {
+        importance += 0.25;
+    }
+    let signals = words
+        .iter()
+        .filter(|w| SIGNAL_WORDS.iter().any(|signal| w.starts_with(signal)))
+        .count();
+    importance += (signals as f32 * 0.15).min(0.3);
+    if text.chars().any(|c| c.is_ascii_digit()) {
+        importance += 0.1;
+    }
+    if text.trim_end().ends_with('?') {
+        importance -= 0.15;
+    }
+    importance += (words.len() as f32 / 100.0).min(0.1);
+    importance.clamp(0.0, 1.0)
+}
+
+/// `importance` after `days` without being recalled.
+pub fn decay(importance: f32, days: f32) -> f32 {
+    importance * 0.5f32.powf(days.max(0.0) / HALF_LIFE_DAYS)
+}
+
+/// The importance of `memory` at `now`, decayed since it was last
+/// recalled. Pinned memories do not fade.
+pub fn strength(memory: &MemoryRecord, now: DateTime<Utc>) -> f32 {
+    if memory.pinned {
+        return memory.importance;
+    }
+    let days = NaiveDateTime::parse_from_str(&memory.accessed_at, "%Y-%m-%d %H:%M:%S")
+        .map(|accessed| (now - accessed.and_utc()).num_seconds() as f32 / 86_400.0)
+        .unwrap_or(0.0);
+    decay(memory.importance, days)
+}
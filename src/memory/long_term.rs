@@ -1,43 +1,211 @@
 // src/memory/long_term.rs
-use std::collections::HashMap;
-use serde::{Serialize, Deserialize};
-use std::fs;
-use chrono;
+use std::collections::HashSet;
+use chrono::Utc;
+use crate::database::{Database, DatabaseError, MemoryRecord};
+use crate::embeddings::store::{memory_namespace, VectorStore};
+use super::importance::{self, REINFORCEMENT};
 
-#[derive(Serialize, Deserialize)]
+/// Memories recalled into a chat prompt, pinned ones included.
+pub const RECALL_LIMIT: usize = 5;
+
+/// Observations scoring below this are not worth keeping.
+pub const OBSERVE_THRESHOLD: f32 = 0.5;
+
+/// What a character remembers about one user, plus the memories other users
+/// chose to share with everyone talking to it. They live in the `memories`
+/// table and are loaded once when the character is. With a vector store
+/// attached they are also embedded, so recall can find memories by meaning.
 pub struct LongTermMemory {
-    data: HashMap<String, String>, // Key-value pairs for persistent data
+    db: Database,
+    user: String,
+    character: String,
+    memories: Vec<MemoryRecord>,
+    vectors: Option<VectorStore>,
 }
 
 impl LongTermMemory {
-    pub fn new() -> Self {
-        Self {
-            data: HashMap::new(),
+    pub async fn load(db: Database, user: &str, character: &str) -> Result<Self, DatabaseError> {
+        let memories = db.get_memories(user.to_string(), character.to_string()).await?;
+        Ok(Self {
+            db,
+            user: user.to_string(),
+            character: character.to_string(),
+            memories,
+            vectors: None,
+        })
+    }
+
+    pub fn with_vectors(mut self, vectors: VectorStore) -> Self {
+        self.vectors = Some(vectors);
+        self
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn character(&self) -> &str {
+        &self.character
+    }
+
+    /// Pinned first, then most important and newest.
+    pub fn memories(&self) -> &[MemoryRecord] {
+        &self.memories
+    }
+
+    /// Stores `content`, learned from `source`, with an importance between 0
+    /// and 1. Returns the id of the memory, which is the existing one when
+    /// the character already remembers exactly this.
+    pub async fn add_memory(&mut self, source: &str, content: &str, importance: f32) -> Result<i64, DatabaseError> {
+        self.add_derived_memory(source, content, importance, Vec::new()).await
+    }
+
+    /// Stores `content` if it scores at least `OBSERVE_THRESHOLD`, returning
+    /// the id of the memory it was stored as.
+    pub async fn observe(&mut self, source: &str, content: &str) -> Result<Option<i64>, DatabaseError> {
+        let importance = importance::score(content);
+        if importance < OBSERVE_THRESHOLD {
+            return Ok(None);
         }
+        self.add_memory(source, content, importance).await.map(Some)
     }
 
-    pub fn store(&mut self, key: String, value: String) {
-        self.data.insert(key, value);
+    /// Like `add_memory`, recording the ids of the memories `content` was
+    /// derived from.
+    pub async fn add_derived_memory(
+        &mut self,
+        source: &str,
+        content: &str,
+        importance: f32,
+        derived_from: Vec<i64>,
+    ) -> Result<i64, DatabaseError> {
+        let content = content.trim();
+        if let Some(existing) = self.memories.iter().find(|m| m.content == content) {
+            return Ok(existing.id);
+        }
+
+        let id = self.db
+            .save_memory(
+                self.user.clone(),
+                self.character.clone(),
+                source.to_string(),
+                content.to_string(),
+                importance.clamp(0.0, 1.0),
+                derived_from,
+            )
+            .await?;
+        if let Some(vectors) = &self.vectors {
+            let items = vec![(id.to_string(), content.to_string())];
+            if let Err(e) = vectors.index(&memory_namespace(&self.character), items).await {
+                log::warn!("Failed to embed memory: {}", e);
+            }
+        }
+        self.reload().await?;
+        Ok(id)
+    }
+
+    /// Up to `limit` memories worth bringing into a reply to `input`: pinned
+    /// memories, then the most similar ones when a vector store is attached,
+    /// then those sharing the most words with it, the stronger first.
+    /// Recalled memories are marked as accessed and reinforced.
+    pub async fn recall(&mut self, input: &str, limit: usize) -> Vec<MemoryRecord> {
+        // The namespace holds every user's memories of the character, so only
+        // search the ones this user can see
+        let visible: HashSet<String> = self.memories.iter().map(|m| m.id.to_string()).collect();
+        let similar = match &self.vectors {
+            Some(vectors) => match vectors.search_keys(&memory_namespace(&self.character), input, visible.len(), &visible).await {
+                Ok(matches) => matches,
+                Err(e) => {
+                    log::warn!("Semantic recall failed, matching words only: {}", e);
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+
+        let now = Utc::now();
+        let words = keywords(input);
+        let mut scored: Vec<_> = self.memories
+            .iter()
+            .filter_map(|memory| {
+                let similarity = similar.iter().find(|m| m.key == memory.id.to_string()).map(|m| m.score);
+                let overlap = keywords(&memory.content).intersection(&words).count();
+                if memory.pinned {
+                    Some((f32::MAX, memory))
+                } else if let Some(similarity) = similarity {
+                    // Similar memories rank above word matches
+                    Some((1000.0 + similarity + importance::strength(memory, now) / 10.0, memory))
+                } else if overlap > 0 {
+                    Some((overlap as f32 + importance::strength(memory, now), memory))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        let recalled: Vec<MemoryRecord> = scored.into_iter().take(limit).map(|(_, m)| m.clone()).collect();
+        if !recalled.is_empty() {
+            let ids = recalled.iter().map(|m| m.id).collect();
+            if let Err(e) = self.db.touch_memories(ids, REINFORCEMENT).await {
+                log::warn!("Failed to mark memories as recalled: {}", e);
+            } else if let Err(e) = self.reload().await {
+                log::warn!("Failed to reload memories: {}", e);
+            }
+        }
+        recalled
     }
 
-    pub fn add_memory(&mut self, user_input: &str, ai_response: &str) {
-        let key = format!("memory_{}", chrono::Utc::now().timestamp());
-        let value = format!("User: {}\nAssistant: {}", user_input, ai_response);
-        self.store(key, value);
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryRecord>, DatabaseError> {
+        self.db.search_memories(self.user.clone(), self.character.clone(), query.to_string(), limit as i64).await
     }
 
-    pub fn retrieve(&self, key: &str) -> Option<&String> {
-        self.data.get(key)
+    /// Pins or unpins memory `id`, returning whether this user has it.
+    pub async fn set_pinned(&mut self, id: i64, pinned: bool) -> Result<bool, DatabaseError> {
+        let found = self.db.set_memory_pinned(self.user.clone(), self.character.clone(), id, pinned).await?;
+        self.reload().await?;
+        Ok(found)
     }
 
-    pub fn save_to_file(&self, path: &str) -> std::io::Result<()> {
-        let serialized = serde_json::to_string(&self.data)?;
-        fs::write(path, serialized)
+    /// Shares memory `id` with every user of the character, or makes it
+    /// private again, returning whether this user has it.
+    pub async fn set_shared(&mut self, id: i64, shared: bool) -> Result<bool, DatabaseError> {
+        let found = self.db.set_memory_shared(self.user.clone(), self.character.clone(), id, shared).await?;
+        self.reload().await?;
+        Ok(found)
     }
 
-    pub fn load_from_file(path: &str) -> std::io::Result<Self> {
-        let data = fs::read_to_string(path)?;
-        let data: HashMap<String, String> = serde_json::from_str(&data)?;
-        Ok(Self { data })
+    /// Deletes memory `id`, returning whether this user had it.
+    pub async fn forget(&mut self, id: i64) -> Result<bool, DatabaseError> {
+        let found = self.db.delete_memory(self.user.clone(), self.character.clone(), id).await?;
+        if let (true, Some(vectors)) = (found, &self.vectors) {
+            if let Err(e) = vectors.remove(&memory_namespace(&self.character), &id.to_string()).await {
+                log::warn!("Failed to remove memory embedding: {}", e);
+            }
+        }
+        self.reload().await?;
+        Ok(found)
+    }
+
+    async fn reload(&mut self) -> Result<(), DatabaseError> {
+        self.memories = self.db.get_memories(self.user.clone(), self.character.clone()).await?;
+        Ok(())
     }
 }
+
+/// Recalled memories as a bulleted list for a prompt.
+pub fn format_memories(memories: &[MemoryRecord]) -> String {
+    memories
+        .iter()
+        .map(|m| format!("• {}", m.content))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Lower-cased words longer than three letters.
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 3)
+        .map(str::to_lowercase)
+        .collect()
+}
@@ -1,6 +1,12 @@
 // src/memory/mod.rs
 pub mod short_term;
 pub mod long_term;
+pub mod summary;
+pub mod importance;
+pub mod reflection;
+
+#[cfg(test)]
+mod tests;
 
 pub use short_term::ShortTermMemory;
 pub use long_term::LongTermMemory;
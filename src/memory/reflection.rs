@@ -0,0 +1,163 @@
+// src/memory/reflection.rs
+use std::env;
+use serde::Deserialize;
+use thiserror::Error;
+use crate::completion::{ChatMessage, CompletionError, CompletionProvider};
+use crate::database::{Database, DatabaseError, MemoryRecord};
+use crate::structured::JsonCompletion;
+use super::LongTermMemory;
+
+/// Source of the memories a reflection pass derives.
+pub const REFLECTION_SOURCE: &str = "reflection";
+
+#[derive(Error, Debug)]
+pub enum ReflectionError {
+    #[error("Reflection failed: {0}")]
+    Completion(#[from] CompletionError),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+}
+
+/// When a character reflects on what it has recently remembered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectionConfig {
+    /// A pass runs once the memories since the last one add up to this much
+    /// importance.
+    pub threshold: f32,
+    /// Most recent memories shown to the model in one pass.
+    pub max_memories: usize,
+}
+
+impl Default for ReflectionConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 2.5,
+            max_memories: 30,
+        }
+    }
+}
+
+impl ReflectionConfig {
+    /// Defaults overridden by `REFLECTION_THRESHOLD`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let threshold = env::var("REFLECTION_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(defaults.threshold);
+        defaults.with_threshold(threshold)
+    }
+
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+/// What the model is asked to return for a reflection pass.
+#[derive(Deserialize)]
+struct Reflections {
+    insights: Vec<ReflectedInsight>,
+}
+
+#[derive(Deserialize)]
+struct ReflectedInsight {
+    content: String,
+    importance: f32,
+    #[serde(default)]
+    sources: Vec<i64>,
+}
+
+/// Periodically asks the model what the recent memories of a character add
+/// up to, and stores the answers as new memories linked to the ones they
+/// were drawn from.
+#[derive(Clone)]
+pub struct Reflector {
+    config: ReflectionConfig,
+}
+
+impl Reflector {
+    pub fn new(config: ReflectionConfig) -> Self {
+        Self { config }
+    }
+
+    /// The user's own memories added since the last pass, oldest first,
+    /// leaving out earlier reflections.
+    pub async fn pending(&self, db: &Database, memory: &LongTermMemory) -> Result<Vec<MemoryRecord>, DatabaseError> {
+        let cursor = db
+            .get_reflection_cursor(memory.user().to_string(), memory.character().to_string())
+            .await?;
+        let mut pending: Vec<MemoryRecord> = memory
+            .memories()
+            .iter()
+            .filter(|m| m.id > cursor && m.user_id == memory.user() && m.source != REFLECTION_SOURCE)
+            .cloned()
+            .collect();
+        pending.sort_by_key(|m| m.id);
+        pending.drain(..pending.len().saturating_sub(self.config.max_memories));
+        Ok(pending)
+    }
+
+    /// Whether enough has been remembered since the last pass to reflect.
+    pub fn is_due(&self, pending: &[MemoryRecord]) -> bool {
+        !pending.is_empty() && pending.iter().map(|m| m.importance).sum::<f32>() >= self.config.threshold
+    }
+
+    /// Runs a pass over the pending memories when one is due, or whenever
+    /// there are any if `force` is set. Returns the ids of the memories it
+    /// derived.
+    pub async fn reflect(
+        &self,
+        db: &Database,
+        provider: &dyn CompletionProvider,
+        memory: &mut LongTermMemory,
+        force: bool,
+    ) -> Result<Vec<i64>, ReflectionError> {
+        let pending = self.pending(db, memory).await?;
+        if pending.is_empty() || !(force || self.is_due(&pending)) {
+            return Ok(Vec::new());
+        }
+
+        let listed = pending
+            .iter()
+            .map(|m| format!("[{}] {}", m.id, m.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            r#"These are recent memories of {}, numbered by id:
+
+{}
+
+What higher-level conclusions can be drawn from them, such as facts about the people involved, their preferences, goals or recurring themes? Return at most 3 as a JSON object with this field:
+"insights": (array) Each an object with
+    "content": (string) The conclusion, stated in one sentence
+    "importance": (number) How useful it is to remember, between 0 and 1
+    "sources": (array of numbers) Ids of the memories it is based on
+
+Return an empty array if nothing new can be concluded."#,
+            memory.character(),
+            listed
+        );
+
+        let reflections: Reflections = provider.complete_json(&[ChatMessage::user(prompt)]).await?;
+        let mut derived = Vec::new();
+        for insight in reflections.insights {
+            let sources: Vec<i64> = insight
+                .sources
+                .into_iter()
+                .filter(|id| pending.iter().any(|m| m.id == *id))
+                .collect();
+            if insight.content.trim().is_empty() || sources.is_empty() {
+                continue;
+            }
+            let id = memory
+                .add_derived_memory(REFLECTION_SOURCE, &insight.content, insight.importance, sources)
+                .await?;
+            derived.push(id);
+        }
+
+        let last = pending.iter().map(|m| m.id).max().unwrap_or_default();
+        db.save_reflection_cursor(memory.user().to_string(), memory.character().to_string(), last).await?;
+        Ok(derived)
+    }
+}
@@ -3,6 +3,9 @@ use std::collections::{VecDeque, HashMap};
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 
+/// Highest relevance score a conversation can get.
+const MAX_RELEVANCE: f32 = 5.0;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Conversation {
     pub timestamp: DateTime<Utc>,
@@ -16,7 +19,8 @@ pub struct ShortTermMemory {
     conversations: VecDeque<Conversation>,
     topic_index: HashMap<String, Vec<usize>>,
     max_size: usize,
-    interactions: Vec<(String, String)>,
+    /// Running summary of the conversations folded out of the buffer.
+    summary: String,
 }
 
 impl ShortTermMemory {
@@ -25,24 +29,67 @@ impl ShortTermMemory {
             conversations: VecDeque::new(),
             topic_index: HashMap::new(),
             max_size: 50, // Keep last 50 conversations
-            interactions: Vec::new(),
+            summary: String::new(),
         }
     }
 
+    /// Records one exchange, indexing its topics and scoring it against the
+    /// last few conversations, then prunes down to `max_size`.
     pub fn add_interaction(&mut self, user_input: &str, ai_response: &str) {
-        self.interactions.push((user_input.to_string(), ai_response.to_string()));
+        let topics = self.extract_topics(user_input, ai_response);
+        let relevance_score = self.calculate_relevance(&topics);
+
+        let index = self.conversations.len();
+        for topic in &topics {
+            self.topic_index.entry(topic.clone()).or_default().push(index);
+        }
+        self.conversations.push_back(Conversation {
+            timestamp: Utc::now(),
+            user_input: user_input.to_string(),
+            ai_response: ai_response.to_string(),
+            topics,
+            relevance_score,
+        });
+
+        self.prune_conversations();
+    }
+
+    /// The last `count` conversations, oldest first.
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &Conversation> {
+        self.conversations.iter().skip(self.conversations.len().saturating_sub(count))
+    }
+
+    pub fn clear(&mut self) {
+        self.conversations.clear();
+        self.topic_index.clear();
+        self.summary.clear();
+    }
+
+    pub fn summary(&self) -> &str {
+        &self.summary
     }
 
-    pub fn get_interactions(&self) -> &Vec<(String, String)> {
-        &self.interactions
+    pub fn set_summary(&mut self, summary: String) {
+        self.summary = summary;
+    }
+
+    /// Replaces the `count` oldest conversations with `summary`, which
+    /// should cover them and the previous summary.
+    pub fn fold_oldest(&mut self, count: usize, summary: String) {
+        self.conversations.drain(..count.min(self.conversations.len()));
+        self.rebuild_topic_index();
+        self.summary = summary;
     }
 
     fn extract_topics(&self, user_input: &str, ai_response: &str) -> Vec<String> {
         let mut topics = Vec::new();
-        let combined_text = format!("{} {}", user_input, ai_response);
-        
+        let combined_text = format!("{} {}", user_input, ai_response).to_lowercase();
+
         // Simple topic extraction (can be enhanced with NLP)
-        let words: Vec<&str> = combined_text.split_whitespace().collect();
+        let words: Vec<&str> = combined_text
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+            .collect();
         for window in words.windows(2) {
             if window[0].len() > 3 && window[1].len() > 3 {
                 topics.push(format!("{} {}", window[0], window[1]));
@@ -59,7 +106,7 @@ impl ShortTermMemory {
             return 1.0;
         }
 
-        let mut relevance = 0.0;
+        let mut relevance: f32 = 0.0;
         let recent_conversations: Vec<_> = self.conversations.iter().rev().take(5).collect();
 
         for topic in topics {
@@ -70,11 +117,7 @@ impl ShortTermMemory {
             }
         }
 
-        if relevance > 5.0 {
-            5.0
-        } else {
-            relevance + 1.0
-        }
+        (relevance + 1.0).min(MAX_RELEVANCE)
     }
 
     fn prune_conversations(&mut self) {
@@ -82,23 +125,23 @@ impl ShortTermMemory {
             return;
         }
 
-        // Sort conversations by relevance and recency
-        let mut conversations: Vec<_> = self.conversations.drain(..).collect();
-        conversations.sort_by(|a, b| {
-            let recency_weight = 0.7;
-            let relevance_weight = 0.3;
-
-            let a_score = (a.timestamp.timestamp() as f32 * recency_weight) + 
-                         (a.relevance_score * relevance_weight);
-            let b_score = (b.timestamp.timestamp() as f32 * recency_weight) + 
-                         (b.relevance_score * relevance_weight);
-
-            b_score.partial_cmp(&a_score).unwrap()
+        // Sort conversations by relevance and recency, both scaled to 0..1 so
+        // neither drowns out the other
+        let len = self.conversations.len() as f32;
+        let score = |i: usize, conv: &Conversation| {
+            let recency = (i + 1) as f32 / len;
+            let relevance = (conv.relevance_score / MAX_RELEVANCE).clamp(0.0, 1.0);
+            recency * 0.7 + relevance * 0.3
+        };
+        let mut conversations: Vec<_> = self.conversations.drain(..).enumerate().collect();
+        conversations.sort_by(|(i, a), (j, b)| {
+            score(*j, b).partial_cmp(&score(*i, a)).unwrap().then(j.cmp(i))
         });
 
-        // Keep the most relevant conversations
+        // Keep the most relevant conversations, in the order they happened
         conversations.truncate(self.max_size);
-        self.conversations = conversations.into_iter().collect();
+        conversations.sort_by_key(|(i, _)| *i);
+        self.conversations = conversations.into_iter().map(|(_, conv)| conv).collect();
 
         // Rebuild topic index
         self.rebuild_topic_index();
@@ -116,6 +159,9 @@ impl ShortTermMemory {
         }
     }
 
+    /// Up to ten past exchanges, picked by topic overlap with
+    /// `current_input` and recency, formatted as a transcript in the order
+    /// they happened. Empty when nothing has been recorded.
     pub fn get_context(&self, current_input: &str) -> String {
         let current_topics = self.extract_topics(current_input, "");
         let mut relevant_conversations: Vec<_> = self.conversations
@@ -126,20 +172,20 @@ impl ShortTermMemory {
                     .iter()
                     .filter(|t| current_topics.contains(t))
                     .count();
-                let recency_score = (self.conversations.len() - i) as f32;
+                let recency_score = (i + 1) as f32 / self.conversations.len() as f32;
                 let relevance = (topic_overlap as f32 * 0.6) + (recency_score * 0.4);
-                (conv, relevance)
+                (i, conv, relevance)
             })
             .collect();
 
-        // Sort by relevance
-        relevant_conversations.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        // Take the top 10 most relevant conversations
+        relevant_conversations.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+        relevant_conversations.truncate(10);
+        relevant_conversations.sort_by_key(|(i, _, _)| *i);
 
-        // Take top 10 most relevant conversations
         let context: Vec<String> = relevant_conversations
             .iter()
-            .take(10)
-            .map(|(conv, _)| {
+            .map(|(_, conv, _)| {
                 format!("User: {}\nAssistant: {}", conv.user_input, conv.ai_response)
             })
             .collect();
@@ -154,10 +200,14 @@ impl ShortTermMemory {
     pub fn get_memory_stats(&self) -> String {
         let total_conversations = self.conversations.len();
         let total_topics: usize = self.topic_index.len();
-        let avg_relevance: f32 = self.conversations
-            .iter()
-            .map(|c| c.relevance_score)
-            .sum::<f32>() / total_conversations as f32;
+        let avg_relevance: f32 = if total_conversations == 0 {
+            0.0
+        } else {
+            self.conversations
+                .iter()
+                .map(|c| c.relevance_score)
+                .sum::<f32>() / total_conversations as f32
+        };
 
         format!(
             "Memory Stats:\n\
@@ -0,0 +1,170 @@
+// src/memory/summary.rs
+use std::env;
+use crate::completion::{ChatMessage, CompletionError, CompletionProvider};
+use crate::database::{Database, DatabaseError};
+use crate::tokens;
+
+/// Uncovered turns older than this many are not replayed or summarized.
+pub const MAX_HISTORY_TURNS: usize = 50;
+
+/// When a conversation is condensed and how much of it stays verbatim.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryConfig {
+    /// Summary plus replayed turns may use this many tokens before the
+    /// oldest turns are folded into the summary.
+    pub token_budget: usize,
+    /// Turns always replayed word for word.
+    pub keep_recent: usize,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        Self {
+            token_budget: 2_000,
+            keep_recent: 4,
+        }
+    }
+}
+
+impl SummaryConfig {
+    /// Defaults overridden by `HISTORY_TOKEN_BUDGET` and `HISTORY_KEEP_TURNS`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| env::var(name).ok().and_then(|v| v.parse::<usize>().ok());
+
+        defaults.clone()
+            .with_token_budget(var("HISTORY_TOKEN_BUDGET").unwrap_or(defaults.token_budget))
+            .with_keep_recent(var("HISTORY_KEEP_TURNS").unwrap_or(defaults.keep_recent))
+    }
+
+    pub fn with_token_budget(mut self, token_budget: usize) -> Self {
+        self.token_budget = token_budget;
+        self
+    }
+
+    pub fn with_keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+}
+
+/// Earlier conversation for a prompt: a running summary of the old turns
+/// plus the recent ones it does not cover, oldest first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    pub summary: String,
+    pub turns: Vec<(String, String)>,
+}
+
+impl History {
+    /// The summary as a paragraph for the system prompt, if there is one.
+    pub fn summary_section(&self) -> Option<String> {
+        summary_section(&self.summary)
+    }
+
+    /// The recent turns as user/assistant messages.
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        self.turns
+            .iter()
+            .flat_map(|(user, assistant)| [ChatMessage::user(user.as_str()), ChatMessage::assistant(assistant.as_str())])
+            .collect()
+    }
+}
+
+/// `summary` as a paragraph for the system prompt, unless it is empty.
+pub fn summary_section(summary: &str) -> Option<String> {
+    (!summary.trim().is_empty()).then(|| format!("Summary of the conversation so far:\n{}", summary.trim()))
+}
+
+/// Condenses the oldest turns of a conversation into a running summary once
+/// it outgrows its token budget.
+#[derive(Clone)]
+pub struct Summarizer {
+    config: SummaryConfig,
+}
+
+impl Summarizer {
+    pub fn new(config: SummaryConfig) -> Self {
+        Self { config }
+    }
+
+    /// How many of the oldest `turns` should be folded into `summary`: none
+    /// while both fit the budget, otherwise all but the most recent ones.
+    pub fn turns_to_fold(&self, summary: &str, turns: &[(String, String)]) -> usize {
+        let used = tokens::count_tokens(summary)
+            + turns
+                .iter()
+                .map(|(user, assistant)| tokens::count_tokens(user) + tokens::count_tokens(assistant))
+                .sum::<usize>();
+        if used <= self.config.token_budget {
+            0
+        } else {
+            turns.len().saturating_sub(self.config.keep_recent)
+        }
+    }
+
+    /// Asks the model to merge `turns` into `summary`, returning the new summary.
+    pub async fn fold(
+        &self,
+        provider: &dyn CompletionProvider,
+        summary: &str,
+        turns: &[(String, String)],
+    ) -> Result<String, CompletionError> {
+        let instructions = "You keep a running summary of a conversation between a user and an assistant. \
+            Merge the earlier summary and the new turns into one concise summary written in the third person. \
+            Keep names, facts, decisions, preferences and open questions; drop small talk. \
+            Reply with the summary only.";
+        let earlier = if summary.trim().is_empty() { "(none)" } else { summary.trim() };
+        let transcript = turns
+            .iter()
+            .map(|(user, assistant)| format!("User: {}\nAssistant: {}", user, assistant))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let request = |transcript: &str| format!("Earlier summary:\n{}\n\nNew turns:\n{}", earlier, transcript);
+        let transcript = tokens::fit_text_for(provider, &format!("{}\n\n{}", instructions, request("")), &transcript);
+        let messages = [ChatMessage::system(instructions), ChatMessage::user(request(&transcript))];
+
+        let summary = provider.complete_messages(&messages).await?;
+        Ok(summary.trim().to_string())
+    }
+
+    /// The history of a session whose turns are stored in `conversations`.
+    /// When it is over budget the oldest turns are folded into the session's
+    /// summary with `provider` and the summary is saved. A failed summary is
+    /// logged and the turns are replayed as they are.
+    pub async fn load_history(
+        &self,
+        db: &Database,
+        provider: &dyn CompletionProvider,
+        session: &str,
+    ) -> Result<History, DatabaseError> {
+        let stored = db.get_summary(session.to_string()).await?;
+        let (summary, covered_until) = stored
+            .map(|s| (s.summary, s.covered_until))
+            .unwrap_or_default();
+
+        let mut records = db.get_session_conversations(session.to_string(), covered_until).await?;
+        records.drain(..records.len().saturating_sub(MAX_HISTORY_TURNS));
+        let turns: Vec<(String, String)> = records
+            .iter()
+            .map(|r| (r.user_input.clone(), r.ai_response.clone()))
+            .collect();
+
+        let fold = self.turns_to_fold(&summary, &turns);
+        if fold == 0 {
+            return Ok(History { summary, turns });
+        }
+
+        match self.fold(provider, &summary, &turns[..fold]).await {
+            Ok(summary) => {
+                db.save_summary(session.to_string(), summary.clone(), records[fold - 1].id).await?;
+                Ok(History { summary, turns: turns[fold..].to_vec() })
+            }
+            Err(e) => {
+                log::warn!("Failed to summarize conversation {}: {}", session, e);
+                Ok(History { summary, turns })
+            }
+        }
+    }
+}
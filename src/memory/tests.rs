@@ -1,52 +1,341 @@
-#[cfg(test)]
-mod tests {
-    use super::super::{ShortTermMemory, LongTermMemory};
-    use std::fs;
-
-    #[test]
-    fn test_short_term_memory() {
-        let mut stm = ShortTermMemory::new();
-        
-        // Test adding conversations
-        stm.add_interaction(
-            "What is machine learning?",
-            "Machine learning is a branch of AI that enables systems to learn from data."
-        );
-        stm.add_interaction(
-            "Tell me about neural networks",
-            "Neural networks are computing systems inspired by biological neural networks."
-        );
-
-        // Test context retrieval
-        let context = stm.get_context("");
-        assert!(context.contains("machine learning"));
-        assert!(context.contains("neural networks"));
-
-        // Test conversation count
-        assert_eq!(stm.conversation_count(), 2);
+use super::{LongTermMemory, ShortTermMemory};
+use super::long_term::{format_memories, RECALL_LIMIT};
+use super::importance;
+use super::reflection::{ReflectionConfig, Reflector, REFLECTION_SOURCE};
+use super::summary::{Summarizer, SummaryConfig};
+use crate::database::{Database, LOCAL_USER};
+use crate::embeddings::local::HashEmbedder;
+use crate::embeddings::store::VectorStore;
+use crate::providers::mock::mock::MockProvider;
+use std::sync::Arc;
+
+#[test]
+fn test_short_term_memory() {
+    let mut stm = ShortTermMemory::new();
+
+    // Test adding conversations
+    stm.add_interaction(
+        "What is machine learning?",
+        "Machine learning is a branch of AI that enables systems to learn from data."
+    );
+    stm.add_interaction(
+        "Tell me about neural networks",
+        "Neural networks are computing systems inspired by biological neural networks."
+    );
+
+    // Test context retrieval
+    let context = stm.get_context("");
+    assert!(context.contains("machine learning"));
+    assert!(context.contains("neural networks"));
+    assert!(context.find("machine learning") < context.find("Neural networks"));
+
+    // Test conversation count
+    assert_eq!(stm.conversation_count(), 2);
+}
+
+#[test]
+fn test_context_prefers_matching_topics() {
+    let mut stm = ShortTermMemory::new();
+    stm.add_interaction("Which rust crates handle async?", "Tokio is the usual choice for async rust.");
+    for i in 0..12 {
+        stm.add_interaction(&format!("Weather report {}", i), "Sunny skies all week.");
+    }
+
+    let context = stm.get_context("More about async rust please");
+    assert!(context.starts_with("User: Which rust crates handle async?"));
+    assert!(!context.contains("Weather report 0"));
+    assert!(context.contains("Weather report 11"));
+}
+
+#[test]
+fn test_buffer_is_pruned_to_max_size_in_order() {
+    let mut stm = ShortTermMemory::new();
+    for i in 0..60 {
+        stm.add_interaction(&format!("Question {}", i), &format!("Answer {}", i));
+    }
+
+    assert_eq!(stm.conversation_count(), 50);
+    let recent: Vec<_> = stm.recent(2).map(|c| c.user_input.as_str()).collect();
+    assert_eq!(recent, vec!["Question 58", "Question 59"]);
+
+    stm.clear();
+    assert_eq!(stm.conversation_count(), 0);
+    assert_eq!(stm.get_context("anything"), "");
+    assert!(stm.get_memory_stats().contains("Average Relevance: 0.00"));
+}
+
+#[tokio::test]
+async fn test_long_term_memory() {
+    let db = Database::new(":memory:").await.unwrap();
+    let mut ltm = LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap();
+
+    // Two memories stored in the same second keep separate ids
+    let search = ltm.add_memory("user", "Binary search halves the range on every step", 0.6).await.unwrap();
+    let launch = ltm.add_memory("document:plan.md", "The launch is planned for March", 0.9).await.unwrap();
+    assert_ne!(search, launch);
+    assert_eq!(ltm.add_memory("user", "The launch is planned for March", 0.5).await.unwrap(), launch);
+
+    // Memories are loaded again at startup, per character
+    let mut reloaded = LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap();
+    assert_eq!(reloaded.memories().len(), 2);
+    assert!(LongTermMemory::load(db.clone(), LOCAL_USER, "Echo").await.unwrap().memories().is_empty());
+
+    let recalled = reloaded.recall("When is the launch?", RECALL_LIMIT).await;
+    assert_eq!(recalled.iter().map(|m| m.id).collect::<Vec<_>>(), vec![launch]);
+    assert_eq!(format_memories(&recalled), "• The launch is planned for March");
+    let found = reloaded.search("binary", 10).await.unwrap();
+    assert_eq!(found.iter().map(|m| m.id).collect::<Vec<_>>(), vec![search]);
+}
+
+#[tokio::test]
+async fn test_pinned_memories_are_always_recalled() {
+    let db = Database::new(":memory:").await.unwrap();
+    let mut ltm = LongTermMemory::load(db, LOCAL_USER, "Nova").await.unwrap();
+    let name = ltm.add_memory("user", "The user's name is Sam", 0.8).await.unwrap();
+
+    assert!(ltm.recall("What's the weather like?", RECALL_LIMIT).await.is_empty());
+    assert!(ltm.set_pinned(name, true).await.unwrap());
+    assert_eq!(ltm.recall("What's the weather like?", RECALL_LIMIT).await[0].id, name);
+
+    assert!(ltm.forget(name).await.unwrap());
+    assert!(!ltm.forget(name).await.unwrap());
+    assert!(ltm.memories().is_empty());
+}
+
+#[test]
+fn test_turns_are_folded_only_over_budget() {
+    let summarizer = Summarizer::new(SummaryConfig::default().with_token_budget(50).with_keep_recent(2));
+    let short = vec![("Hi".to_string(), "Hello!".to_string()); 3];
+    assert_eq!(summarizer.turns_to_fold("", &short), 0);
+
+    let long = vec![("Tell me a long story about dragons and castles".to_string(), "Once upon a time ".repeat(5)); 5];
+    assert_eq!(summarizer.turns_to_fold("", &long), 3);
+    assert_eq!(summarizer.turns_to_fold("", &long[..1]), 0);
+}
+
+#[tokio::test]
+async fn test_old_turns_are_folded_into_a_saved_summary() {
+    let db = Database::new(":memory:").await.unwrap();
+    let session = db.open_session(None, "sam".to_string(), "Nova".to_string()).await.unwrap().unwrap();
+    for i in 1..=5 {
+        db.save_conversation(&session, format!("Question {} about the launch plan and its budget", i), format!("Answer {} with plenty of detail about timing and costs", i)).await.unwrap();
+    }
+    let other = db.open_session(None, "alex".to_string(), "Nova".to_string()).await.unwrap().unwrap();
+    db.save_conversation(&other, "Unrelated".to_string(), "Another user's session".to_string()).await.unwrap();
+
+    let summarizer = Summarizer::new(SummaryConfig::default().with_token_budget(40).with_keep_recent(2));
+    let provider = MockProvider::new(["The user asked about the launch plan."]);
+    let history = summarizer.load_history(&db, &provider, &session.id).await.unwrap();
+
+    assert_eq!(history.summary, "The user asked about the launch plan.");
+    assert_eq!(history.turns.len(), 2);
+    assert!(history.turns[0].0.starts_with("Question 4"));
+    assert_eq!(history.messages().len(), 4);
+    assert!(history.summary_section().unwrap().contains("launch plan"));
+    assert!(provider.last_prompt().unwrap().contains("Question 3"));
+
+    let saved = db.get_summary(session.id.clone()).await.unwrap().unwrap();
+    assert_eq!(saved.summary, history.summary);
+
+    // The folded turns are not summarized again
+    let again = summarizer.load_history(&db, &provider, &session.id).await.unwrap();
+    assert_eq!(again, history);
+    assert_eq!(provider.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_failed_summary_keeps_all_turns() {
+    let db = Database::new(":memory:").await.unwrap();
+    let session = db.open_session(None, "sam".to_string(), "Nova".to_string()).await.unwrap().unwrap();
+    for i in 1..=3 {
+        db.save_conversation(&session, format!("Question {} about something long enough", i), format!("Answer {} that is also long enough", i)).await.unwrap();
+    }
+
+    let summarizer = Summarizer::new(SummaryConfig::default().with_token_budget(10).with_keep_recent(1));
+    let provider = MockProvider::new(Vec::<String>::new());
+    let history = summarizer.load_history(&db, &provider, &session.id).await.unwrap();
+
+    assert!(history.summary.is_empty());
+    assert_eq!(history.turns.len(), 3);
+    assert!(db.get_summary(session.id.clone()).await.unwrap().is_none());
+}
+
+#[test]
+fn test_fold_oldest_replaces_turns_with_summary() {
+    let mut stm = ShortTermMemory::new();
+    stm.add_interaction("First question", "First answer");
+    stm.add_interaction("Second question", "Second answer");
+    stm.add_interaction("Third question", "Third answer");
+
+    stm.fold_oldest(2, "Two questions were answered.".to_string());
+    assert_eq!(stm.conversation_count(), 1);
+    assert_eq!(stm.summary(), "Two questions were answered.");
+    assert!(!stm.get_context("").contains("First"));
+
+    stm.clear();
+    assert!(stm.summary().is_empty());
+}
+
+#[test]
+fn test_importance_is_scored_at_write_time() {
+    let personal = importance::score("My sister's birthday is on June 12th and I want to plan a party");
+    let question = importance::score("What time is it?");
+    assert!(personal > 0.7, "{}", personal);
+    assert!(question < 0.3, "{}", question);
+    assert_eq!(importance::score("   "), 0.0);
+}
+
+#[test]
+fn test_importance_decays_by_half_life() {
+    assert_eq!(importance::decay(0.8, 0.0), 0.8);
+    assert!((importance::decay(0.8, importance::HALF_LIFE_DAYS) - 0.4).abs() < 1e-6);
+    assert!(importance::decay(0.8, 365.0) < 0.01);
+}
+
+#[tokio::test]
+async fn test_recall_reinforces_memories_and_observe_filters_small_talk() {
+    let db = Database::new(":memory:").await.unwrap();
+    let mut ltm = LongTermMemory::load(db, LOCAL_USER, "Nova").await.unwrap();
+
+    assert_eq!(ltm.observe("chat", "ok thanks").await.unwrap(), None);
+    let id = ltm.observe("chat", "I love hiking in the Alps every summer").await.unwrap().unwrap();
+    let before = ltm.memories()[0].importance;
+
+    ltm.recall("Where should I go hiking?", RECALL_LIMIT).await;
+    let after = ltm.memories().iter().find(|m| m.id == id).unwrap().importance;
+    assert!(after > before && after <= 1.0);
+}
+
+#[tokio::test]
+async fn test_reflection_derives_linked_memories() {
+    let db = Database::new(":memory:").await.unwrap();
+    let mut ltm = LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap();
+    let coffee = ltm.add_memory("chat", "I drink three espressos a day", 0.7).await.unwrap();
+    let tea = ltm.add_memory("chat", "I never drink tea", 0.6).await.unwrap();
+
+    let reflector = Reflector::new(ReflectionConfig::default().with_threshold(2.0));
+    let provider = MockProvider::new([format!(
+        r#"{{"insights": [
+            {{"content": "The user strongly prefers coffee over tea", "importance": 0.8, "sources": [{}, {}, 999]}},
+            {{"content": "Unsupported guess", "importance": 0.5, "sources": []}}
+        ]}}"#,
+        coffee, tea
+    )]);
+
+    // Not enough has piled up for a pass on its own
+    assert!(reflector.reflect(&db, &provider, &mut ltm, false).await.unwrap().is_empty());
+    assert!(provider.requests().is_empty());
+
+    let derived = reflector.reflect(&db, &provider, &mut ltm, true).await.unwrap();
+    assert_eq!(derived.len(), 1);
+    let reflection = ltm.memories().iter().find(|m| m.id == derived[0]).unwrap();
+    assert_eq!(reflection.source, REFLECTION_SOURCE);
+    assert_eq!(reflection.derived_from, vec![coffee, tea]);
+    assert!(provider.last_prompt().unwrap().contains(&format!("[{}] I never drink tea", tea)));
+
+    // Memories already reflected on, and reflections themselves, are not pending
+    assert!(reflector.pending(&db, &ltm).await.unwrap().is_empty());
+    assert!(reflector.reflect(&db, &provider, &mut ltm, true).await.unwrap().is_empty());
+}
+
+#[test]
+fn test_pruning_weighs_relevance_against_recency() {
+    let mut stm = ShortTermMemory::new();
+    stm.add_interaction("Tell me about rust ownership", "Rust ownership means one owner.");
+    for i in 0..49 {
+        stm.add_interaction(&format!("Filler question {}", i), &format!("Filler answer {}", i));
     }
+    stm.add_interaction("More on rust ownership", "Rust ownership moves values.");
+
+    // Recent conversations outrank an old one, even a related one
+    assert_eq!(stm.conversation_count(), 50);
+    assert!(!stm.get_context("").contains("Tell me about rust ownership"));
+    assert!(stm.recent(1).any(|c| c.user_input == "More on rust ownership"));
+}
+
+#[tokio::test]
+async fn test_sessions_belong_to_one_user_and_character() {
+    let db = Database::new(":memory:").await.unwrap();
+    let session = db.open_session(Some("s1".to_string()), "sam".to_string(), "Nova".to_string()).await.unwrap().unwrap();
+    assert_eq!(session.id, "s1");
+
+    // The same user continues it; anyone else is turned away
+    let again = db.open_session(Some("s1".to_string()), "sam".to_string(), "Nova".to_string()).await.unwrap();
+    assert_eq!(again.map(|s| s.id), Some("s1".to_string()));
+    assert!(db.open_session(Some("s1".to_string()), "alex".to_string(), "Nova".to_string()).await.unwrap().is_none());
+    assert!(db.open_session(Some("s1".to_string()), "sam".to_string(), "Echo".to_string()).await.unwrap().is_none());
+
+    let fresh = db.open_session(None, "sam".to_string(), "Echo".to_string()).await.unwrap().unwrap();
+    assert_ne!(fresh.id, session.id);
+    assert_eq!(db.get_sessions("sam".to_string()).await.unwrap().len(), 2);
+    assert!(db.get_sessions("alex".to_string()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_memories_are_private_unless_shared() {
+    let db = Database::new(":memory:").await.unwrap();
+    let mut sam = LongTermMemory::load(db.clone(), "sam", "Nova").await.unwrap();
+    let dog = sam.add_memory("chat", "Sam has a dog called Rex", 0.8).await.unwrap();
+    let tip = sam.add_memory("user", "The office closes at six", 0.6).await.unwrap();
 
-    #[test]
-    fn test_long_term_memory() {
-        let mut ltm = LongTermMemory::new();
-        
-        // Test storing memories
-        ltm.add_memory(
-            "How do I implement a binary search?",
-            "Here's how to implement binary search..."
-        );
-
-        // Test saving to file
-        let test_file = "test_memory.json";
-        ltm.save_to_file(test_file).expect("Failed to save memory");
-
-        // Test loading from file
-        let loaded_ltm = LongTermMemory::load_from_file(test_file).expect("Failed to load memory");
-        
-        // Verify loaded memory
-        assert!(loaded_ltm.retrieve("How do I implement a binary search?").is_some());
-
-        // Cleanup
-        fs::remove_file(test_file).ok();
+    let mut alex = LongTermMemory::load(db.clone(), "alex", "Nova").await.unwrap();
+    assert!(alex.memories().is_empty());
+    assert!(alex.recall("Tell me about the dog Rex", RECALL_LIMIT).await.is_empty());
+    assert!(!alex.forget(dog).await.unwrap());
+
+    assert!(sam.set_shared(tip, true).await.unwrap());
+    let mut alex = LongTermMemory::load(db.clone(), "alex", "Nova").await.unwrap();
+    assert_eq!(alex.memories().iter().map(|m| m.id).collect::<Vec<_>>(), vec![tip]);
+    assert_eq!(alex.recall("When does the office close?", RECALL_LIMIT).await[0].id, tip);
+
+    // Only the owner can change a shared memory
+    assert!(!alex.set_pinned(tip, true).await.unwrap());
+    assert!(!alex.forget(tip).await.unwrap());
+    assert_eq!(LongTermMemory::load(db, "sam", "Nova").await.unwrap().memories().len(), 2);
+}
+
+#[tokio::test]
+async fn test_recall_is_not_crowded_out_by_other_users_memories() {
+    let db = Database::new(":memory:").await.unwrap();
+    let vectors = VectorStore::new(db.clone(), Arc::new(HashEmbedder::default()));
+    let mut sam = LongTermMemory::load(db.clone(), "sam", "Nova").await.unwrap().with_vectors(vectors.clone());
+    // Shares no word with the query, so only the vector search can find it
+    let hobby = sam.add_memory("chat", "Sam loves gardening", 0.7).await.unwrap();
+
+    let mut alex = LongTermMemory::load(db.clone(), "alex", "Nova").await.unwrap().with_vectors(vectors);
+    for plot in 1..=10 {
+        alex.add_memory("chat", &format!("Alex keeps garden plot {}", plot), 0.7).await.unwrap();
     }
-} 
\ No newline at end of file
+
+    let recalled = sam.recall("garden tips", RECALL_LIMIT).await;
+    assert_eq!(recalled.iter().map(|m| m.id).collect::<Vec<_>>(), vec![hobby]);
+}
+
+#[tokio::test]
+async fn test_existing_rows_migrate_to_the_local_user() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("agent.db");
+    let conn = tokio_rusqlite::Connection::open(&path).await.unwrap();
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE conversations (
+                id INTEGER PRIMARY KEY, timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+                user_input TEXT NOT NULL, ai_response TEXT NOT NULL, personality TEXT NOT NULL
+            );
+            CREATE TABLE memories (
+                id INTEGER PRIMARY KEY, character TEXT NOT NULL, source TEXT NOT NULL, content TEXT NOT NULL,
+                importance REAL NOT NULL, pinned INTEGER NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP, accessed_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO memories (character, source, content, importance) VALUES ('Nova', 'user', 'Likes tea', 0.8);"
+        )
+    })
+    .await
+    .unwrap();
+    drop(conn);
+
+    let db = Database::new(&path).await.unwrap();
+    let local = LongTermMemory::load(db.clone(), LOCAL_USER, "Nova").await.unwrap();
+    assert_eq!(local.memories()[0].content, "Likes tea");
+    assert!(LongTermMemory::load(db, "sam", "Nova").await.unwrap().memories().is_empty());
+}
@@ -1,5 +1,6 @@
 use serde::{Serialize, Deserialize};
 use serde_json::Value;
+use crate::completion::GenerationParams;
 use std::fs;
 use std::path::Path;
 
@@ -30,6 +31,17 @@ impl PersonalityProfile {
             .and_then(|v| v.as_object())
     }
 
+    /// Default sampling settings from the profile's `"generation"` object.
+    pub fn generation_params(&self) -> GenerationParams {
+        match self.attributes.get("generation") {
+            Some(generation) => serde_json::from_value(generation.clone()).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid generation settings for {}: {}", self.name, e);
+                GenerationParams::default()
+            }),
+            None => GenerationParams::default(),
+        }
+    }
+
     pub fn generate_system_prompt(&self) -> String {
         let description = self.get_str("description")
             .unwrap_or("an AI assistant");
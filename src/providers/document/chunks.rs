@@ -0,0 +1,93 @@
+use crate::database::DocumentChunk;
+
+/// Words in a chunk.
+pub const CHUNK_WORDS: usize = 180;
+
+/// Words a chunk repeats from the end of the one before it, so that a
+/// passage cut in two is still whole in one of them.
+pub const OVERLAP_WORDS: usize = 40;
+
+/// Text extracted from one page of a PDF, one sheet of a spreadsheet, or a
+/// whole document that has neither.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Section {
+    pub page: Option<u32>,
+    pub sheet: Option<String>,
+    pub text: String,
+}
+
+impl Section {
+    pub fn whole(text: String) -> Self {
+        Self { text, ..Default::default() }
+    }
+}
+
+/// The text of `sections` joined together.
+pub fn joined(sections: &[Section]) -> String {
+    sections.iter().map(|section| section.text.as_str()).collect::<Vec<_>>().join("\n")
+}
+
+/// Splits `sections` into chunks of about `size` words, each repeating the
+/// last `overlap` words of the one before it. Chunks never span sections, so
+/// each keeps the page or sheet it came from, and the lines it covers.
+pub fn split(document_path: &str, sections: &[Section], size: usize, overlap: usize) -> Vec<DocumentChunk> {
+    let size = size.max(1);
+    let overlap = overlap.min(size - 1);
+    let mut chunks = Vec::new();
+
+    for section in sections {
+        // Long lines are cut into pieces no longer than the overlap, so
+        // chunks of them can still overlap
+        let piece_words = if overlap > 0 { overlap } else { size };
+        let pieces: Vec<(usize, Vec<&str>)> = section.text
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                words.chunks(piece_words).map(|piece| (i + 1, piece.to_vec())).collect::<Vec<_>>()
+            })
+            .collect();
+
+        let mut start = 0;
+        while start < pieces.len() {
+            let mut end = start;
+            let mut words = 0;
+            while end < pieces.len() && (words == 0 || words + pieces[end].1.len() <= size) {
+                words += pieces[end].1.len();
+                end += 1;
+            }
+
+            let mut content = String::new();
+            for (i, (line, words)) in pieces[start..end].iter().enumerate() {
+                if i > 0 {
+                    // Pieces of one line are joined back into it
+                    content.push(if pieces[start + i - 1].0 == *line { ' ' } else { '\n' });
+                }
+                content.push_str(&words.join(" "));
+            }
+            chunks.push(DocumentChunk {
+                id: 0,
+                document_path: document_path.to_string(),
+                chunk_index: chunks.len(),
+                page: section.page,
+                sheet: section.sheet.clone(),
+                start_line: pieces[start].0,
+                end_line: pieces[end - 1].0,
+                content,
+            });
+            if end == pieces.len() {
+                break;
+            }
+
+            // Step back over whole pieces while they fit in the overlap
+            let mut next = end;
+            let mut repeated = 0;
+            while next > start + 1 && repeated + pieces[next - 1].1.len() <= overlap {
+                repeated += pieces[next - 1].1.len();
+                next -= 1;
+            }
+            start = next;
+        }
+    }
+    chunks
+}
@@ -29,6 +29,9 @@ pub enum DocumentError {
     #[error("Insight extraction error: {0}")]
     InsightError(String),
     
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] crate::database::DatabaseError),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     
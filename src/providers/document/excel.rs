@@ -8,11 +8,13 @@ impl ExcelExtractor {
         Self
     }
 
-    pub fn extract_text(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
+    /// The name and text of each sheet, with a line per row.
+    pub fn extract_sheets(&self, file_path: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
         let mut workbook: calamine::Xlsx<_> = open_workbook(file_path)?;
-        let mut text = String::new();
+        let mut sheets = Vec::new();
 
         for sheet_name in workbook.sheet_names() {
+            let mut text = String::new();
             if let Some(Ok(range)) = workbook.worksheet_range(&sheet_name) {
                 for row in range.rows() {
                     for cell in row {
@@ -22,8 +24,9 @@ impl ExcelExtractor {
                     text.push('\n');
                 }
             }
+            sheets.push((sheet_name, text));
         }
 
-        Ok(text)
+        Ok(sheets)
     }
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::database::{Database, DocumentChunk, IngestedDocument};
+use crate::embeddings::VectorStore;
+use crate::embeddings::store::DOCUMENTS_NAMESPACE;
+use crate::knowledge_base::index::Bm25Index;
+use crate::text::tokenize;
+use crate::tokens;
+use super::chunks::{self, Section, CHUNK_WORDS, OVERLAP_WORDS};
+use super::DocumentError;
+
+/// Passages put into a `doc chat` prompt.
+pub const PASSAGE_LIMIT: usize = 5;
+
+/// Passages are cut to this many tokens in a prompt.
+const CITED_TOKENS: usize = 400;
+
+/// How many of the best chunks each ranking contributes to the fused one.
+const CANDIDATES: usize = 20;
+
+/// Reciprocal rank fusion constant; the larger it is, the less the top few
+/// ranks of either ranking dominate.
+const RRF_K: f32 = 60.0;
+
+/// A chunk found for a question.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Passage {
+    pub chunk: DocumentChunk,
+    pub score: f32,
+}
+
+/// Chunks of ingested documents, stored in the database and embedded in the
+/// vector store. Questions are matched against every document at once, by
+/// keywords (BM25) and by meaning, and the two rankings are fused.
+#[derive(Clone)]
+pub struct DocumentLibrary {
+    db: Database,
+    vectors: VectorStore,
+}
+
+impl DocumentLibrary {
+    pub fn new(db: Database, vectors: VectorStore) -> Self {
+        Self { db, vectors }
+    }
+
+    /// The path a document is stored under: its canonical path when it
+    /// exists, so that one file reached by two paths is one document.
+    pub fn document_path(file_path: &str) -> String {
+        std::fs::canonicalize(file_path)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| file_path.to_string())
+    }
+
+    /// Splits `sections` into overlapping chunks and stores them as the
+    /// content of `file_path`, replacing those ingested from it before.
+    /// Returns how many chunks were stored.
+    pub async fn ingest(&self, file_path: &str, sections: &[Section]) -> Result<usize, DocumentError> {
+        let path = Self::document_path(file_path);
+        let chunks = chunks::split(&path, sections, CHUNK_WORDS, OVERLAP_WORDS);
+        let (removed, stored) = self.db.replace_document_chunks(path.clone(), chunks).await?;
+        self.forget_embeddings(removed).await;
+
+        let items = stored.iter().map(|chunk| (chunk.id.to_string(), chunk.content.clone())).collect();
+        if let Err(e) = self.vectors.index(DOCUMENTS_NAMESPACE, items).await {
+            // Keyword search still finds the chunks
+            log::warn!("Failed to embed the chunks of {}: {}", path, e);
+        }
+        Ok(stored.len())
+    }
+
+    pub async fn documents(&self) -> Result<Vec<IngestedDocument>, DocumentError> {
+        Ok(self.db.list_ingested_documents().await?)
+    }
+
+    /// Drops the chunks of `file_path`, returning how many there were.
+    pub async fn remove(&self, file_path: &str) -> Result<usize, DocumentError> {
+        let removed = self.db.delete_document_chunks(Self::document_path(file_path)).await?;
+        let count = removed.len();
+        self.forget_embeddings(removed).await;
+        Ok(count)
+    }
+
+    /// The `limit` chunks of all documents that best answer `query`, best
+    /// first.
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<Passage>, DocumentError> {
+        let chunks = self.db.get_all_document_chunks().await?;
+        if chunks.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        let terms: Vec<Vec<String>> = chunks.iter().map(|chunk| tokenize(&chunk.content)).collect();
+        let ranked = Bm25Index::build(&terms).search(&tokenize(query), CANDIDATES);
+        for (rank, (position, _)) in ranked.into_iter().enumerate() {
+            *scores.entry(position).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+
+        match self.vectors.search(DOCUMENTS_NAMESPACE, query, CANDIDATES).await {
+            Ok(matches) => {
+                let positions: HashMap<String, usize> = chunks
+                    .iter()
+                    .enumerate()
+                    .map(|(position, chunk)| (chunk.id.to_string(), position))
+                    .collect();
+                let ranked = matches.iter().filter_map(|m| positions.get(&m.key));
+                for (rank, &position) in ranked.enumerate() {
+                    *scores.entry(position).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+                }
+            }
+            Err(e) => log::warn!("Failed to search document embeddings, ranking by keywords only: {}", e),
+        }
+
+        let mut fused: Vec<(usize, f32)> = scores.into_iter().collect();
+        fused.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        fused.truncate(limit);
+        Ok(fused
+            .into_iter()
+            .map(|(position, score)| Passage { chunk: chunks[position].clone(), score })
+            .collect())
+    }
+
+    async fn forget_embeddings(&self, ids: Vec<i64>) {
+        for id in ids {
+            if let Err(e) = self.vectors.remove(DOCUMENTS_NAMESPACE, &id.to_string()).await {
+                log::warn!("Failed to remove the embedding of chunk {}: {}", id, e);
+            }
+        }
+    }
+}
+
+/// Where `chunk` comes from, e.g. `report.pdf p. 3`, `budget.xlsx sheet Q1,
+/// rows 4-10` or `notes.md lines 12-30`.
+pub fn citation(chunk: &DocumentChunk) -> String {
+    let name = Path::new(&chunk.document_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| chunk.document_path.clone());
+    let span = |unit: &str| {
+        if chunk.start_line == chunk.end_line {
+            format!("{} {}", unit, chunk.start_line)
+        } else {
+            format!("{}s {}-{}", unit, chunk.start_line, chunk.end_line)
+        }
+    };
+
+    match (&chunk.page, &chunk.sheet) {
+        (Some(page), _) => format!("{} p. {}", name, page),
+        (None, Some(sheet)) => format!("{} sheet {}, {}", name, sheet, span("row")),
+        (None, None) => format!("{} {}", name, span("line")),
+    }
+}
+
+/// A note listing `passages` by number with where they come from, for the
+/// system prompt.
+pub fn format_passages(passages: &[Passage]) -> String {
+    let excerpts = passages
+        .iter()
+        .enumerate()
+        .map(|(i, passage)| format!(
+            "[{}] ({})\n{}",
+            i + 1,
+            citation(&passage.chunk),
+            tokens::truncate_to_tokens(&passage.chunk.content, CITED_TOKENS)
+        ))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    format!(
+        "Excerpts from the user's documents. Answer from them, cite each one you use by its number, \
+        like [1], and say so when they do not hold the answer:\n\n{}",
+        excerpts
+    )
+}
+
+/// The passages `reply` cites, with their numbers, in order.
+pub fn cited<'a>(reply: &str, passages: &'a [Passage]) -> Vec<(usize, &'a Passage)> {
+    passages
+        .iter()
+        .enumerate()
+        .map(|(i, passage)| (i + 1, passage))
+        .filter(|(number, _)| reply.contains(&format!("[{}]", number)))
+        .collect()
+}
@@ -5,6 +5,11 @@ pub mod ocr;
 pub mod insights;
 pub mod error;
 pub mod text;
+pub mod chunks;
+pub mod library;
+
+#[cfg(test)]
+mod tests;
 
 pub use pdf::PdfExtractor;
 pub use excel::ExcelExtractor;
@@ -13,8 +18,12 @@ pub use ocr::OcrExtractor;
 pub use insights::InsightExtractor;
 pub use error::DocumentError;
 pub use text::TextExtractor;
+pub use chunks::Section;
+pub use library::DocumentLibrary;
 
 use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::Arc;
+use crate::completion::CompletionProvider;
 
 pub struct DocumentProcessor {
     pdf_extractor: PdfExtractor,
@@ -28,7 +37,7 @@ pub struct DocumentProcessor {
 impl DocumentProcessor {
     const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024; // 10MB limit
 
-    pub async fn new(api_key: String, system_message: String) -> Result<Self, DocumentError> {
+    pub fn new(provider: Arc<dyn CompletionProvider>) -> Result<Self, DocumentError> {
         Ok(Self {
             pdf_extractor: PdfExtractor::new(),
             excel_extractor: ExcelExtractor::new(),
@@ -36,23 +45,36 @@ impl DocumentProcessor {
             ocr_extractor: OcrExtractor::new()
                 .map_err(|e| DocumentError::OcrError(e.to_string()))?,
             text_extractor: TextExtractor::new(),
-            insight_extractor: InsightExtractor::new(api_key, system_message)
-                .await
-                .map_err(|e| DocumentError::InsightError(e.to_string()))?,
+            insight_extractor: InsightExtractor::new(provider),
         })
     }
 
-    pub async fn process_document(&mut self, file_path: &str) -> Result<Vec<insights::Insight>, DocumentError> {
+    /// The text of `file_path`, by page for PDFs and by sheet for
+    /// spreadsheets.
+    pub fn extract_sections(&mut self, file_path: &str) -> Result<Vec<Section>, DocumentError> {
         let extension = std::path::Path::new(file_path)
             .extension()
             .and_then(|ext| ext.to_str())
             .ok_or(DocumentError::InvalidExtension)?;
 
         let text = match extension.to_lowercase().as_str() {
-            "pdf" => self.pdf_extractor.extract_text(file_path)
-                .map_err(|e| DocumentError::PdfError(e.to_string()))?,
-            "xlsx" | "xls" => self.excel_extractor.extract_text(file_path)
-                .map_err(|e| DocumentError::ExcelError(e.to_string()))?,
+            "pdf" => {
+                let pages = self.pdf_extractor.extract_pages(file_path)
+                    .map_err(|e| DocumentError::PdfError(e.to_string()))?;
+                return Ok(pages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, text)| Section { page: Some(i as u32 + 1), sheet: None, text })
+                    .collect());
+            }
+            "xlsx" | "xls" => {
+                let sheets = self.excel_extractor.extract_sheets(file_path)
+                    .map_err(|e| DocumentError::ExcelError(e.to_string()))?;
+                return Ok(sheets
+                    .into_iter()
+                    .map(|(name, text)| Section { page: None, sheet: Some(name), text })
+                    .collect());
+            }
             "docx" | "doc" => self.word_extractor.extract_text(file_path)
                 .map_err(|e| DocumentError::WordError(e.to_string()))?,
             "png" | "jpg" | "jpeg" => {
@@ -64,36 +86,24 @@ impl DocumentProcessor {
                 .map_err(|e| DocumentError::TextError(e.to_string()))?,
             _ => return Err(DocumentError::UnsupportedFileType(extension.to_string())),
         };
+        Ok(vec![Section::whole(text)])
+    }
 
-        let insights = self.insight_extractor.extract_insights(&text).await
+    pub async fn process_document(&mut self, file_path: &str) -> Result<Vec<insights::Insight>, DocumentError> {
+        let sections = self.extract_sections(file_path)?;
+        self.extract_insights(&sections).await
+    }
+
+    /// Insights into the text of `sections`.
+    pub async fn extract_insights(&self, sections: &[Section]) -> Result<Vec<insights::Insight>, DocumentError> {
+        let insights = self.insight_extractor.extract_insights(&chunks::joined(sections)).await
             .map_err(|e| DocumentError::InsightError(e.to_string()))?;
         Ok(insights)
     }
 
     pub async fn quick_analyze(&mut self, file_path: &str) -> Result<String, DocumentError> {
-        let extension = std::path::Path::new(file_path)
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .ok_or(DocumentError::InvalidExtension)?;
-
-        let text = match extension.to_lowercase().as_str() {
-            "pdf" => self.pdf_extractor.extract_text(file_path)
-                .map_err(|e| DocumentError::PdfError(e.to_string()))?,
-            "xlsx" | "xls" => self.excel_extractor.extract_text(file_path)
-                .map_err(|e| DocumentError::ExcelError(e.to_string()))?,
-            "docx" | "doc" => self.word_extractor.extract_text(file_path)
-                .map_err(|e| DocumentError::WordError(e.to_string()))?,
-            "png" | "jpg" | "jpeg" => {
-                let extractor = std::mem::replace(&mut self.ocr_extractor, OcrExtractor::default());
-                extractor.extract_text(file_path)
-            }
-                .map_err(|e| DocumentError::OcrError(e.to_string()))?,
-            "txt" | "md" | "rs" | "py" | "js" | "json" | "yaml" | "yml" => self.text_extractor.extract_text(file_path)
-                .map_err(|e| DocumentError::TextError(e.to_string()))?,
-            _ => return Err(DocumentError::UnsupportedFileType(extension.to_string())),
-        };
-
-        self.insight_extractor.quick_analyze(&text).await
+        let sections = self.extract_sections(file_path)?;
+        self.insight_extractor.quick_analyze(&chunks::joined(&sections)).await
             .map_err(|e| DocumentError::InsightError(e.to_string()))
     }
 
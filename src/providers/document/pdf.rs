@@ -1,4 +1,4 @@
-use pdf_extract::extract_text;
+use pdf_extract::extract_text_by_pages;
 use std::error::Error;
 
 pub struct PdfExtractor;
@@ -8,8 +8,9 @@ impl PdfExtractor {
         Self
     }
 
-    pub fn extract_text(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
-        let text = extract_text(file_path)?;
-        Ok(text)
+    /// The text of each page, in order.
+    pub fn extract_pages(&self, file_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let pages = extract_text_by_pages(file_path)?;
+        Ok(pages)
     }
 }
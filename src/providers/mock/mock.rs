@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use futures::stream;
+use crate::completion::{
+    with_system_message, ChatMessage, Completion, CompletionError, CompletionProvider, CompletionStream, GenerationParams,
+    Role, StreamEvent, ToolCall, ToolDefinition, Usage,
+};
+
+/// Provider that answers from a script instead of a model, for offline tests.
+///
+/// Replies (and errors added with [`MockProvider::with_error`]) are returned
+/// in order; once the script runs out every call fails, unless a fallback was
+/// set with [`MockProvider::always`]. Every conversation it receives is kept
+/// so tests can assert on the prompts.
+pub struct MockProvider {
+    model: String,
+    system_message: String,
+    temperature: Option<f32>,
+    responses: Mutex<VecDeque<Result<Completion, CompletionError>>>,
+    fallback: Option<String>,
+    requests: Mutex<Vec<Vec<ChatMessage>>>,
+    params: Mutex<Vec<GenerationParams>>,
+}
+
+impl MockProvider {
+    pub fn new<I, S>(responses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            model: "mock".to_string(),
+            system_message: String::new(),
+            temperature: None,
+            responses: Mutex::new(
+                responses
+                    .into_iter()
+                    .map(|content| Ok(Completion::new(content.into(), Usage::default())))
+                    .collect(),
+            ),
+            fallback: None,
+            requests: Mutex::new(Vec::new()),
+            params: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A provider that gives the same reply to every request.
+    pub fn always(response: impl Into<String>) -> Self {
+        Self {
+            fallback: Some(response.into()),
+            ..Self::new(Vec::<String>::new())
+        }
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    pub fn with_system_message(mut self, system_message: impl Into<String>) -> Self {
+        self.system_message = system_message.into();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Appends a reply that asks for `name` to be called with `arguments`.
+    /// The call id is `call_<n>`, numbered from 1 across the script.
+    pub fn with_tool_call(self, name: &str, arguments: serde_json::Value) -> Self {
+        {
+            let mut responses = self.responses.lock().unwrap();
+            let calls = responses.iter().filter(|r| r.as_ref().is_ok_and(|r| !r.tool_calls.is_empty())).count();
+            responses.push_back(Ok(Completion {
+                tool_calls: vec![ToolCall::new(format!("call_{}", calls + 1), name, &arguments)],
+                ..Completion::new(String::new(), Usage::default())
+            }));
+        }
+        self
+    }
+
+    /// Appends a plain text reply to the script.
+    pub fn with_reply(self, content: impl Into<String>) -> Self {
+        self.responses.lock().unwrap().push_back(Ok(Completion::new(content.into(), Usage::default())));
+        self
+    }
+
+    /// Appends a failure to the script.
+    pub fn with_error(self, error: CompletionError) -> Self {
+        self.responses.lock().unwrap().push_back(Err(error));
+        self
+    }
+
+    /// Every conversation received so far, including the system message.
+    pub fn requests(&self) -> Vec<Vec<ChatMessage>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The params passed with each request so far.
+    pub fn request_params(&self) -> Vec<GenerationParams> {
+        self.params.lock().unwrap().clone()
+    }
+
+    /// Content of the final user turn of the most recent request.
+    pub fn last_prompt(&self) -> Option<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .last()
+            .and_then(|messages| messages.iter().rev().find(|m| m.role == Role::User))
+            .map(|message| message.content.clone())
+    }
+}
+
+#[async_trait::async_trait]
+impl CompletionProvider for MockProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn system_message(&self) -> &str {
+        &self.system_message
+    }
+
+    fn params(&self) -> GenerationParams {
+        GenerationParams { temperature: self.temperature, ..GenerationParams::default() }
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        let messages = with_system_message(&self.system_message, messages);
+        self.requests.lock().unwrap().push(messages.clone());
+        self.params.lock().unwrap().push(params.clone());
+
+        let mut completion = self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .or_else(|| self.fallback.clone().map(|content| Ok(Completion::new(content, Usage::default()))))
+            .unwrap_or_else(|| Err(CompletionError::ApiError("MockProvider has no scripted response left".to_string())))?;
+        completion.usage = Usage::estimate(&messages, &completion.content);
+
+        Ok(completion)
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    /// Streams the scripted reply a word at a time, like a model would.
+    async fn chat_with_tools_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<CompletionStream, CompletionError> {
+        let completion = self.chat_with_tools(messages, tools, params).await?;
+        let mut events: Vec<_> = completion
+            .content
+            .split_inclusive(' ')
+            .map(|word| StreamEvent::Delta(word.to_string()))
+            .collect();
+        if !completion.tool_calls.is_empty() {
+            events.push(StreamEvent::ToolCalls(completion.tool_calls));
+        }
+        events.push(StreamEvent::Usage(completion.usage));
+        Ok(Box::pin(stream::iter(events.into_iter().map(Ok))))
+    }
+}
@@ -0,0 +1,5 @@
+pub mod mock;
+pub mod replay;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::cache::stable_hash;
+use crate::completion::{
+    with_system_message, ChatMessage, Completion, CompletionError, CompletionProvider, GenerationParams, Usage,
+};
+
+/// One recorded exchange, stored as a line of a JSONL cassette.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CassetteEntry {
+    pub key: String,
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub response: String,
+    /// Usage reported when the exchange was recorded.
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+/// Stable key for a conversation: a hash of its JSON form.
+pub fn request_key(messages: &[ChatMessage]) -> String {
+    stable_hash(&serde_json::to_string(messages).unwrap_or_default())
+}
+
+/// Replays completions from a JSONL cassette, keyed by a hash of the full
+/// conversation (system message included).
+///
+/// In recording mode, requests missing from the cassette are forwarded to the
+/// wrapped provider and the exchange is appended to the file; without one,
+/// a miss is an error so tests never reach the network by accident.
+pub struct ReplayProvider {
+    path: PathBuf,
+    system_message: String,
+    entries: Mutex<HashMap<String, CassetteEntry>>,
+    inner: Option<Box<dyn CompletionProvider>>,
+}
+
+impl ReplayProvider {
+    /// Serves only what is already recorded in `path`.
+    pub fn replaying<P: AsRef<Path>>(path: P, system_message: String) -> Result<Self, CompletionError> {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            return Err(CompletionError::Config(format!("Cassette {} does not exist", path.display())));
+        }
+
+        Ok(Self {
+            entries: Mutex::new(Self::load(&path)?),
+            path,
+            system_message,
+            inner: None,
+        })
+    }
+
+    /// Replays from `path` when possible and records `inner`'s answers otherwise.
+    pub fn recording<P: AsRef<Path>>(path: P, inner: Box<dyn CompletionProvider>) -> Result<Self, CompletionError> {
+        let path = path.as_ref().to_path_buf();
+        let entries = if path.exists() { Self::load(&path)? } else { HashMap::new() };
+
+        Ok(Self {
+            entries: Mutex::new(entries),
+            path,
+            system_message: inner.system_message().to_string(),
+            inner: Some(inner),
+        })
+    }
+
+    fn load(path: &Path) -> Result<HashMap<String, CassetteEntry>, CompletionError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| CompletionError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+
+        let mut entries = HashMap::new();
+        for (number, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let entry: CassetteEntry = serde_json::from_str(line).map_err(|e| {
+                CompletionError::Config(format!("Invalid cassette entry at {}:{}: {}", path.display(), number + 1, e))
+            })?;
+            entries.insert(entry.key.clone(), entry);
+        }
+        Ok(entries)
+    }
+
+    fn append(&self, entry: &CassetteEntry) -> Result<(), CompletionError> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| CompletionError::Config(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| CompletionError::Config(format!("Failed to open {}: {}", self.path.display(), e)))?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)
+            .map_err(|e| CompletionError::Config(format!("Failed to write {}: {}", self.path.display(), e)))
+    }
+}
+
+#[async_trait::async_trait]
+impl CompletionProvider for ReplayProvider {
+    fn model(&self) -> &str {
+        self.inner.as_ref().map(|inner| inner.model()).unwrap_or("replay")
+    }
+
+    fn system_message(&self) -> &str {
+        &self.system_message
+    }
+
+    fn params(&self) -> GenerationParams {
+        self.inner.as_ref().map(|inner| inner.params()).unwrap_or_default()
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        let messages = with_system_message(&self.system_message, messages);
+        let key = request_key(&messages);
+
+        if let Some(entry) = self.entries.lock().unwrap().get(&key) {
+            return Ok(Completion::new(
+                entry.response.clone(),
+                entry.usage.unwrap_or_else(|| Usage::estimate(&messages, &entry.response)),
+            ));
+        }
+
+        let inner = self.inner.as_ref().ok_or_else(|| CompletionError::Config(format!(
+            "No recorded response for request {} in {}",
+            key,
+            self.path.display()
+        )))?;
+
+        let completion = inner.chat(&messages, params).await?;
+        let entry = CassetteEntry {
+            key: key.clone(),
+            model: inner.model().to_string(),
+            messages,
+            response: completion.content.clone(),
+            usage: Some(completion.usage),
+        };
+        self.append(&entry)?;
+        self.entries.lock().unwrap().insert(key, entry);
+        Ok(completion)
+    }
+}
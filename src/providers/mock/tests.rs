@@ -0,0 +1,90 @@
+use super::mock::MockProvider;
+use super::replay::{request_key, ReplayProvider};
+use crate::completion::{
+    ChatMessage, CompletionError, CompletionProvider, GenerationParams, ParamsProvider, Role, StreamEvent,
+};
+use futures::StreamExt;
+use std::sync::Arc;
+
+fn cassette_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("rust-ai-agent-{}-{}.jsonl", name, std::process::id()));
+    std::fs::remove_file(&path).ok();
+    path
+}
+
+#[tokio::test]
+async fn test_mock_provider_returns_script_in_order() {
+    let provider = MockProvider::new(["first", "second"]).with_system_message("Be brief.");
+
+    assert_eq!(provider.complete("one").await.unwrap(), "first");
+    assert_eq!(provider.complete("two").await.unwrap(), "second");
+    assert!(matches!(provider.complete("three").await, Err(CompletionError::ApiError(_))));
+
+    let requests = provider.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0][0], ChatMessage::system("Be brief."));
+    assert_eq!(requests[1][1].role, Role::User);
+    assert_eq!(provider.last_prompt().as_deref(), Some("three"));
+}
+
+#[tokio::test]
+async fn test_mock_provider_always_and_stream() {
+    let provider = MockProvider::always("same answer");
+    assert_eq!(provider.complete("a").await.unwrap(), "same answer");
+
+    let mut stream = provider.complete_stream(&[ChatMessage::user("b")]).await.unwrap();
+    assert_eq!(stream.next().await.unwrap().unwrap(), StreamEvent::Delta("same answer".to_string()));
+    assert!(matches!(stream.next().await, Some(Ok(StreamEvent::Usage(_)))));
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_params_provider_layers_params_under_the_call() {
+    let mock = Arc::new(MockProvider::always("ok").with_temperature(1.0));
+    let character = ParamsProvider::wrap(mock.clone(), GenerationParams { top_p: Some(0.8), ..GenerationParams::default().with_temperature(0.9) });
+    let command = ParamsProvider::wrap(character, GenerationParams::default().with_temperature(0.2));
+    assert_eq!(command.params().temperature, Some(0.2));
+    assert_eq!(command.params().top_p, Some(0.8));
+
+    command.complete("a").await.unwrap();
+    command.chat(&[ChatMessage::user("b")], &GenerationParams::default().with_max_tokens(50)).await.unwrap();
+
+    let sent = mock.request_params();
+    assert_eq!(sent[0], GenerationParams { top_p: Some(0.8), ..GenerationParams::default().with_temperature(0.2) });
+    assert_eq!(sent[1].max_tokens, Some(50));
+    assert_eq!(sent[1].temperature, Some(0.2));
+}
+
+#[tokio::test]
+async fn test_record_then_replay() {
+    let path = cassette_path("record-replay");
+    let history = [ChatMessage::user("Capital of Italy?"), ChatMessage::assistant("Rome"), ChatMessage::user("France?")];
+
+    let recorder = ReplayProvider::recording(
+        &path,
+        Box::new(MockProvider::new(["Paris", "Berlin"]).with_system_message("Be terse.")),
+    ).unwrap();
+    assert_eq!(recorder.complete_messages(&history).await.unwrap(), "Paris");
+    assert_eq!(recorder.complete("Capital of Germany?").await.unwrap(), "Berlin");
+    // Already recorded, so the exhausted inner provider is not consulted
+    assert_eq!(recorder.complete_messages(&history).await.unwrap(), "Paris");
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+    let replayer = ReplayProvider::replaying(&path, "Be terse.".to_string()).unwrap();
+    assert_eq!(replayer.complete("Capital of Germany?").await.unwrap(), "Berlin");
+    assert_eq!(replayer.complete_messages(&history).await.unwrap(), "Paris");
+
+    // A different system prompt is a different request
+    let other = ReplayProvider::replaying(&path, "Be verbose.".to_string()).unwrap();
+    assert!(matches!(other.complete("Capital of Germany?").await, Err(CompletionError::Config(_))));
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn test_request_key_is_stable() {
+    let messages = [ChatMessage::system("s"), ChatMessage::user("hello")];
+    assert_eq!(request_key(&messages), request_key(&messages.clone()));
+    assert_ne!(request_key(&messages), request_key(&[ChatMessage::user("hello")]));
+    assert_eq!(request_key(&[]), "09612b07b5ecb5a5");
+}
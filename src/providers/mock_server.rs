@@ -0,0 +1,141 @@
+//! Minimal scripted HTTP server for exercising providers without a network.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+/// Default pause between body chunks, long enough for each to arrive alone.
+pub const CHUNK_DELAY: Duration = Duration::from_millis(5);
+
+/// One canned reply. The body is written chunk by chunk so streaming parsers
+/// see realistic packet boundaries.
+#[derive(Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub chunks: Vec<String>,
+    /// Wait before sending anything, to trip client timeouts.
+    pub delay: Duration,
+    /// Pause between chunks of the body.
+    pub chunk_delay: Duration,
+}
+
+impl MockResponse {
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            chunks: vec![body.to_string()],
+            delay: Duration::ZERO,
+            chunk_delay: CHUNK_DELAY,
+        }
+    }
+
+    pub fn sse(chunks: &[&str]) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+            chunks: chunks.iter().map(|c| c.to_string()).collect(),
+            delay: Duration::ZERO,
+            chunk_delay: CHUNK_DELAY,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_chunk_delay(mut self, chunk_delay: Duration) -> Self {
+        self.chunk_delay = chunk_delay;
+        self
+    }
+}
+
+/// Answers successive connections with the scripted responses in order,
+/// recording each raw request. The last response repeats once the script runs out.
+pub struct MockServer {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<MockResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            let mut index = 0;
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let response = responses[index.min(responses.len() - 1)].clone();
+                index += 1;
+
+                let request = read_request(&mut socket).await;
+                recorded.lock().await.push(request);
+                // Serve each connection separately so a delayed reply doesn't block the next one
+                tokio::spawn(async move {
+                    tokio::time::sleep(response.delay).await;
+                    write_response(&mut socket, &response).await;
+                });
+            }
+        });
+
+        Self { base_url, requests }
+    }
+
+    pub async fn requests(&self) -> Vec<String> {
+        self.requests.lock().await.clone()
+    }
+}
+
+async fn read_request(socket: &mut TcpStream) -> String {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap_or(0);
+        data.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&data).to_string();
+        if n == 0 {
+            return text;
+        }
+        if let Some(header_end) = text.find("\r\n\r\n") {
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(0);
+            if data.len() >= header_end + 4 + content_length {
+                return text;
+            }
+        }
+    }
+}
+
+async fn write_response(socket: &mut TcpStream, response: &MockResponse) {
+    let mut head = format!("HTTP/1.1 {} Mock\r\nConnection: close\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    if socket.write_all(head.as_bytes()).await.is_err() {
+        return;
+    }
+    for chunk in &response.chunks {
+        if socket.write_all(chunk.as_bytes()).await.is_err() {
+            return;
+        }
+        socket.flush().await.ok();
+        tokio::time::sleep(response.chunk_delay).await;
+    }
+    socket.shutdown().await.ok();
+}
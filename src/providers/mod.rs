@@ -1,4 +1,14 @@
 pub mod deepseek;
+pub mod openai;
+pub mod ollama;
+pub mod mock;
+pub mod registry;
+pub mod retry;
+pub mod router;
+pub mod streaming;
 pub mod web_crawler;
 pub mod twitter;
 pub mod document;
+
+#[cfg(test)]
+pub(crate) mod mock_server;
@@ -0,0 +1,4 @@
+pub mod ollama;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,200 @@
+use reqwest::Client;
+use serde_json::json;
+use crate::completion::{
+    with_system_message, ChatMessage, Completion, CompletionError, CompletionProvider, CompletionStream,
+    GenerationParams, StreamEvent, Usage,
+};
+use crate::providers::retry::{self, RetryPolicy};
+use crate::providers::streaming::{self, DeltaParser};
+
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+
+/// Provider for a local Ollama server's native `/api/chat` endpoint.
+pub struct OllamaProvider {
+    client: Client,
+    base_url: String,
+    model: String,
+    system_message: String,
+    params: GenerationParams,
+    retry: RetryPolicy,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: String, model: String, system_message: String) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model,
+            system_message,
+            params: GenerationParams::default(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.params.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.params.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn request_body(&self, messages: &[ChatMessage], params: &GenerationParams, stream: bool) -> serde_json::Value {
+        let params = params.clone().or(&self.params);
+        let mut options = serde_json::Map::new();
+        let settings = [
+            ("temperature", params.temperature.map(|v| json!(v))),
+            ("top_p", params.top_p.map(|v| json!(v))),
+            ("num_predict", params.max_tokens.map(|v| json!(v))),
+            ("frequency_penalty", params.frequency_penalty.map(|v| json!(v))),
+            ("presence_penalty", params.presence_penalty.map(|v| json!(v))),
+            ("seed", params.seed.map(|v| json!(v))),
+        ];
+        for (name, value) in settings {
+            if let Some(value) = value {
+                options.insert(name.to_string(), value);
+            }
+        }
+        if !params.stop.is_empty() {
+            options.insert("stop".to_string(), json!(params.stop));
+        }
+
+        json!({
+            "model": params.model_or(&self.model),
+            "messages": with_system_message(&self.system_message, messages),
+            "stream": stream,
+            "options": options
+        })
+    }
+
+    async fn send(&self, body: &serde_json::Value) -> Result<reqwest::Response, CompletionError> {
+        let response = self.client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(body)
+            .send()
+            .await?;
+        retry::check_status(response).await
+    }
+
+    async fn post_chat(&self, messages: &[ChatMessage], body: serde_json::Value) -> Result<Completion, CompletionError> {
+        let response_text = self.retry
+            .run(|| async { Ok(self.send(&body).await?.text().await?) })
+            .await?;
+        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+
+        let content = response_json
+            .get("message")
+            .and_then(|message| message.get("content"))
+            .and_then(|content| content.as_str())
+            .map(String::from)
+            .ok_or_else(|| CompletionError::MalformedResponse(
+                format!("Failed to extract content from response: {}", response_text)
+            ))?;
+        let usage = parse_usage(&response_json)
+            .unwrap_or_else(|| Usage::estimate(&with_system_message(&self.system_message, messages), &content));
+
+        Ok(Completion::new(content, usage))
+    }
+}
+
+#[async_trait::async_trait]
+impl CompletionProvider for OllamaProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn system_message(&self) -> &str {
+        &self.system_message
+    }
+
+    fn params(&self) -> GenerationParams {
+        self.params.clone()
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.post_chat(messages, self.request_body(messages, params, false)).await
+    }
+
+    async fn chat_json(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        let mut body = self.request_body(messages, params, false);
+        body["format"] = json!("json");
+        self.post_chat(messages, body).await
+    }
+
+    async fn chat_stream(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<CompletionStream, CompletionError> {
+        let body = self.request_body(messages, params, true);
+        let response = self.retry.run(|| self.send(&body)).await?;
+        Ok(Box::pin(streaming::delta_stream(response.bytes_stream(), NdjsonParser::default(), self.retry.timeout)))
+    }
+}
+
+/// Ollama reports token counts as `prompt_eval_count` and `eval_count` on the
+/// final (`"done": true`) response.
+fn parse_usage(response: &serde_json::Value) -> Option<Usage> {
+    Some(Usage::new(
+        response.get("prompt_eval_count")?.as_u64()? as usize,
+        response.get("eval_count")?.as_u64()? as usize,
+    ))
+}
+
+/// Parser for Ollama's streaming format: one JSON object per line, the last
+/// one carrying `"done": true` and the token counts.
+#[derive(Default)]
+pub struct NdjsonParser {
+    /// Raw bytes of the line being received, decoded once it is complete so
+    /// that a character split between two reads stays whole.
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl NdjsonParser {
+    fn parse_line(&mut self, line: &str) -> Result<Vec<StreamEvent>, serde_json::Error> {
+        if self.done || line.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let chunk: serde_json::Value = serde_json::from_str(line)?;
+        if chunk.get("done").and_then(|d| d.as_bool()).unwrap_or(false) {
+            self.done = true;
+        }
+
+        let delta = chunk
+            .get("message")
+            .and_then(|message| message.get("content"))
+            .and_then(|content| content.as_str())
+            .filter(|content| !content.is_empty())
+            .map(|content| StreamEvent::Delta(content.to_string()));
+        let usage = parse_usage(&chunk).map(StreamEvent::Usage);
+
+        Ok(delta.into_iter().chain(usage).collect())
+    }
+}
+
+impl DeltaParser for NdjsonParser {
+    fn feed(&mut self, bytes: &[u8]) -> Result<Vec<StreamEvent>, serde_json::Error> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            events.extend(self.parse_line(String::from_utf8_lossy(&line).trim())?);
+        }
+        Ok(events)
+    }
+
+    fn finish(&mut self) -> Result<Vec<StreamEvent>, serde_json::Error> {
+        let line = std::mem::take(&mut self.buffer);
+        self.parse_line(String::from_utf8_lossy(&line).trim())
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+}
@@ -0,0 +1,103 @@
+use super::ollama::{NdjsonParser, OllamaProvider};
+use crate::completion::{ChatMessage, CompletionProvider, GenerationParams, StreamEvent, Usage};
+use crate::providers::mock_server::{MockResponse, MockServer, CHUNK_DELAY};
+use crate::providers::streaming::DeltaParser;
+use futures::StreamExt;
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_ollama_chat() {
+    let server = MockServer::start(vec![MockResponse::json(200, json!({
+        "model": "llama3",
+        "message": {"role": "assistant", "content": "Hi there"},
+        "done": true
+    }))]).await;
+    let provider = OllamaProvider::new(server.base_url.clone(), "llama3".to_string(), "Be kind.".to_string())
+        .with_temperature(0.2);
+
+    let response = provider.complete("Hello").await.unwrap();
+    assert_eq!(response, "Hi there");
+
+    let request = &server.requests().await[0];
+    assert!(request.starts_with("POST /api/chat"));
+    assert!(request.contains("\"stream\":false"));
+    assert!(request.contains("\"temperature\":0.2"));
+    assert!(request.contains("Be kind."));
+}
+
+#[tokio::test]
+async fn test_ollama_maps_generation_params_to_options() {
+    let server = MockServer::start(vec![MockResponse::json(200, json!({
+        "message": {"role": "assistant", "content": "Hi there"},
+        "done": true
+    }))]).await;
+    let provider = OllamaProvider::new(server.base_url.clone(), "llama3".to_string(), String::new())
+        .with_temperature(0.2);
+    let params = GenerationParams {
+        model: Some("llama3.2:1b".to_string()),
+        top_p: Some(0.9),
+        stop: vec!["User:".to_string()],
+        ..GenerationParams::default().with_max_tokens(64)
+    };
+
+    provider.chat(&[ChatMessage::user("Hello")], &params).await.unwrap();
+
+    let request = &server.requests().await[0];
+    let (_, body) = request.split_once("\r\n\r\n").unwrap();
+    let body: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(body["model"], "llama3.2:1b");
+    assert_eq!(body["options"], json!({"temperature": 0.2f32, "top_p": 0.9f32, "num_predict": 64, "stop": ["User:"]}));
+}
+
+#[tokio::test]
+async fn test_ollama_stream() {
+    let server = MockServer::start(vec![MockResponse {
+        status: 200,
+        headers: vec![("Content-Type".to_string(), "application/x-ndjson".to_string())],
+        chunks: vec![
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"done\":false}\n".to_string(),
+            "{\"message\":{\"role\":\"assistant\",\"content\":\" the".to_string(),
+            "re\"},\"done\":false}\n".to_string(),
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"prompt_eval_count\":9,\"eval_count\":2}\n".to_string(),
+        ],
+        delay: Duration::ZERO,
+        chunk_delay: CHUNK_DELAY,
+    }]).await;
+    let provider = OllamaProvider::new(server.base_url.clone(), "llama3".to_string(), String::new());
+
+    let mut stream = provider.complete_stream(&[ChatMessage::user("Hello")]).await.unwrap();
+    let mut response = String::new();
+    let mut usage = None;
+    while let Some(event) = stream.next().await {
+        match event.unwrap() {
+            StreamEvent::Delta(delta) => response.push_str(&delta),
+            StreamEvent::Usage(reported) => usage = Some(reported),
+            StreamEvent::ToolCalls(calls) => panic!("unexpected tool calls: {:?}", calls),
+        }
+    }
+    assert_eq!(response, "Hi there");
+    assert_eq!(usage, Some(Usage::new(9, 2)));
+}
+
+#[test]
+fn test_ndjson_parser_keeps_characters_split_between_reads() {
+    let line = "{\"message\":{\"role\":\"assistant\",\"content\":\"hi 🦀\"},\"done\":false}\n".as_bytes();
+    // Cut inside the four bytes of the crab
+    let split = line.iter().position(|&b| b == 0xF0).unwrap() + 2;
+
+    let mut parser = NdjsonParser::default();
+    let mut events = parser.feed(&line[..split]).unwrap();
+    events.extend(parser.feed(&line[split..]).unwrap());
+
+    assert_eq!(events, vec![StreamEvent::Delta("hi 🦀".to_string())]);
+}
+
+#[tokio::test]
+async fn test_ollama_error_status() {
+    let server = MockServer::start(vec![MockResponse::json(404, json!({"error": "model not found"}))]).await;
+    let provider = OllamaProvider::new(server.base_url.clone(), "missing".to_string(), String::new());
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert!(err.to_string().contains("model not found"));
+}
@@ -0,0 +1,5 @@
+pub mod openai;
+pub mod sse;
+
+#[cfg(test)]
+mod tests;
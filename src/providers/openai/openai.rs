@@ -0,0 +1,185 @@
+use reqwest::Client;
+use serde_json::json;
+use crate::completion::{
+    with_system_message, ChatMessage, Completion, CompletionError, CompletionProvider, CompletionStream,
+    GenerationParams, ToolCall, ToolDefinition, Usage,
+};
+use crate::providers::openai::sse::{parse_usage, SseParser};
+use crate::providers::retry::{self, RetryPolicy};
+use crate::providers::streaming;
+
+/// Provider for any server implementing the OpenAI `/v1/chat/completions` API
+/// (OpenAI itself, DeepSeek, vLLM, llama.cpp server, LM Studio, ...).
+pub struct OpenAiProvider {
+    client: Client,
+    api_url: String,
+    api_key: Option<String>,
+    model: String,
+    system_message: String,
+    /// Defaults for every request; `model` is kept separately.
+    params: GenerationParams,
+    retry: RetryPolicy,
+}
+
+impl OpenAiProvider {
+    pub fn new(api_url: String, api_key: Option<String>, model: String, system_message: String) -> Self {
+        Self {
+            client: Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            api_key,
+            model,
+            system_message,
+            params: GenerationParams::default().with_max_tokens(2048).with_temperature(1.0),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_model(mut self, model: String) -> Self {
+        self.model = model;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.params.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.params.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Base URLs may be given with or without the trailing `/v1`.
+    fn endpoint(&self) -> String {
+        if self.api_url.ends_with("/v1") {
+            format!("{}/chat/completions", self.api_url)
+        } else {
+            format!("{}/v1/chat/completions", self.api_url)
+        }
+    }
+
+    fn request_body(&self, messages: &[ChatMessage], params: &GenerationParams, stream: bool) -> serde_json::Value {
+        let params = params.clone().or(&self.params);
+        let mut body = json!({
+            "model": params.model_or(&self.model),
+            "messages": with_system_message(&self.system_message, messages),
+            "stream": stream
+        });
+        // Apart from `model`, the params are named as in the request body
+        if let serde_json::Value::Object(settings) = json!(GenerationParams { model: None, ..params }) {
+            for (name, value) in settings {
+                body[name.as_str()] = value;
+            }
+        }
+        if stream {
+            // Ask for a final chunk carrying token usage
+            body["stream_options"] = json!({"include_usage": true});
+        }
+        body
+    }
+
+    async fn send(&self, body: &serde_json::Value) -> Result<reqwest::Response, CompletionError> {
+        let mut request = self.client.post(self.endpoint()).json(body);
+        if let Some(api_key) = &self.api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        retry::check_status(request.send().await?).await
+    }
+
+    async fn post_chat(&self, messages: &[ChatMessage], body: serde_json::Value) -> Result<Completion, CompletionError> {
+        let response_text = self.retry
+            .run(|| async { Ok(self.send(&body).await?.text().await?) })
+            .await?;
+        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+
+        let message = response_json
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .and_then(|choice| choice.get("message"))
+            .ok_or_else(|| CompletionError::MalformedResponse(
+                format!("Failed to extract message from response: {}", response_text)
+            ))?;
+        let tool_calls: Vec<ToolCall> = match message.get("tool_calls") {
+            Some(calls) if !calls.is_null() => serde_json::from_value(calls.clone())?,
+            _ => Vec::new(),
+        };
+        // Content is null when the model only asks for tool calls
+        let content = match message.get("content").and_then(|content| content.as_str()) {
+            Some(content) => content.to_string(),
+            None if !tool_calls.is_empty() => String::new(),
+            None => return Err(CompletionError::MalformedResponse(
+                format!("Failed to extract content from response: {}", response_text)
+            )),
+        };
+        let usage = parse_usage(&response_json)
+            .unwrap_or_else(|| Usage::estimate(&with_system_message(&self.system_message, messages), &content));
+
+        Ok(Completion { content, usage, tool_calls })
+    }
+}
+
+#[async_trait::async_trait]
+impl CompletionProvider for OpenAiProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn system_message(&self) -> &str {
+        &self.system_message
+    }
+
+    fn params(&self) -> GenerationParams {
+        self.params.clone()
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.post_chat(messages, self.request_body(messages, params, false)).await
+    }
+
+    async fn chat_json(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        let mut body = self.request_body(messages, params, false);
+        body["response_format"] = json!({"type": "json_object"});
+        self.post_chat(messages, body).await
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<Completion, CompletionError> {
+        let mut body = self.request_body(messages, params, false);
+        if !tools.is_empty() {
+            body["tools"] = tools.iter().map(ToolDefinition::to_openai).collect();
+        }
+        self.post_chat(messages, body).await
+    }
+
+    async fn chat_stream(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<CompletionStream, CompletionError> {
+        self.chat_with_tools_stream(messages, &[], params).await
+    }
+
+    async fn chat_with_tools_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<CompletionStream, CompletionError> {
+        let mut body = self.request_body(messages, params, true);
+        if !tools.is_empty() {
+            body["tools"] = tools.iter().map(ToolDefinition::to_openai).collect();
+        }
+        let response = self.retry.run(|| self.send(&body)).await?;
+
+        Ok(Box::pin(streaming::delta_stream(response.bytes_stream(), SseParser::new(), self.retry.timeout)))
+    }
+}
@@ -0,0 +1,124 @@
+use crate::completion::{FunctionCall, StreamEvent, ToolCall, Usage};
+use crate::providers::streaming::DeltaParser;
+
+/// Incremental parser for OpenAI-style `stream: true` responses.
+///
+/// Bytes are fed in as they arrive; complete `data:` lines are decoded and the
+/// `choices[0].delta.content` text of each chunk is returned, along with the
+/// `usage` object sent in the final chunk. Tool calls arrive in pieces; they
+/// are put together and returned once the stream ends.
+#[derive(Default)]
+pub struct SseParser {
+    /// Raw bytes of the line being received, decoded once it is complete so
+    /// that a character split between two reads stays whole.
+    buffer: Vec<u8>,
+    /// Tool calls received so far, by their index in the reply.
+    tool_calls: Vec<ToolCall>,
+    done: bool,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<Vec<StreamEvent>, serde_json::Error> {
+        if self.done {
+            return Ok(Vec::new());
+        }
+
+        // Blank lines separate events; anything but `data:` (comments, `event:`) is ignored
+        let payload = match line.strip_prefix("data:") {
+            Some(payload) => payload.trim(),
+            None => return Ok(Vec::new()),
+        };
+
+        if payload == "[DONE]" {
+            self.done = true;
+            return Ok(self.take_tool_calls().into_iter().collect());
+        }
+
+        let chunk: serde_json::Value = serde_json::from_str(payload)?;
+        let message = chunk
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .and_then(|choice| choice.get("delta"));
+        let delta = message
+            .and_then(|delta| delta.get("content"))
+            .and_then(|content| content.as_str())
+            .filter(|content| !content.is_empty())
+            .map(|content| StreamEvent::Delta(content.to_string()));
+        if let Some(calls) = message.and_then(|delta| delta.get("tool_calls")).and_then(|calls| calls.as_array()) {
+            for call in calls {
+                self.add_tool_call_piece(call);
+            }
+        }
+        let usage = parse_usage(&chunk).map(StreamEvent::Usage);
+
+        Ok(delta.into_iter().chain(usage).collect())
+    }
+
+    /// Adds a piece of a streamed tool call: the first piece of each call
+    /// carries its id and name, the rest more of its arguments.
+    fn add_tool_call_piece(&mut self, piece: &serde_json::Value) {
+        let index = piece.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as usize;
+        while self.tool_calls.len() <= index {
+            self.tool_calls.push(ToolCall {
+                id: String::new(),
+                kind: "function".to_string(),
+                function: FunctionCall { name: String::new(), arguments: String::new() },
+            });
+        }
+        let call = &mut self.tool_calls[index];
+        if let Some(id) = piece.get("id").and_then(|id| id.as_str()) {
+            call.id = id.to_string();
+        }
+        let function = piece.get("function");
+        if let Some(name) = function.and_then(|f| f.get("name")).and_then(|name| name.as_str()) {
+            call.function.name.push_str(name);
+        }
+        if let Some(arguments) = function.and_then(|f| f.get("arguments")).and_then(|a| a.as_str()) {
+            call.function.arguments.push_str(arguments);
+        }
+    }
+
+    fn take_tool_calls(&mut self) -> Option<StreamEvent> {
+        let calls = std::mem::take(&mut self.tool_calls);
+        (!calls.is_empty()).then_some(StreamEvent::ToolCalls(calls))
+    }
+}
+
+/// Reads the `usage` object of a (streamed or complete) chat completion.
+pub fn parse_usage(response: &serde_json::Value) -> Option<Usage> {
+    let usage = response.get("usage")?;
+    Some(Usage::new(
+        usage.get("prompt_tokens")?.as_u64()? as usize,
+        usage.get("completion_tokens")?.as_u64()? as usize,
+    ))
+}
+
+impl DeltaParser for SseParser {
+    fn feed(&mut self, bytes: &[u8]) -> Result<Vec<StreamEvent>, serde_json::Error> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            events.extend(self.parse_line(String::from_utf8_lossy(&line).trim())?);
+        }
+        Ok(events)
+    }
+
+    fn finish(&mut self) -> Result<Vec<StreamEvent>, serde_json::Error> {
+        let line = std::mem::take(&mut self.buffer);
+        let mut events = self.parse_line(String::from_utf8_lossy(&line).trim())?;
+        // The body ended without `data: [DONE]`
+        events.extend(self.take_tool_calls());
+        Ok(events)
+    }
+
+    /// True once the `data: [DONE]` sentinel has been seen.
+    fn is_done(&self) -> bool {
+        self.done
+    }
+}
@@ -0,0 +1,428 @@
+use super::openai::OpenAiProvider;
+use super::sse::SseParser;
+use crate::completion::{
+    ChatMessage, Completion, CompletionError, CompletionProvider, GenerationParams, StreamEvent, ToolCall,
+    ToolDefinition, Usage,
+};
+use crate::providers::deepseek::deepseek::DeepSeekProvider;
+use crate::providers::mock_server::{MockResponse, MockServer};
+use crate::providers::retry::{parse_retry_after, RetryPolicy};
+use crate::providers::streaming::DeltaParser;
+use crate::structured::JsonCompletion;
+use futures::StreamExt;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+const CANNED_CHUNKS: &[&str] = &[
+    "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\n",
+    "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+    // A chunk boundary in the middle of an event
+    "data: {\"choices\":[{\"delta\":{\"con",
+    "tent\":\"lo\"}}]}\n\n: keep-alive comment\n\n",
+    "data: {\"choices\":[{\"delta\":{\"content\":\" world\"},\"finish_reason\":null}]}\n\n",
+    "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+    "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":3,\"total_tokens\":15}}\n\n",
+    "data: [DONE]\n\n",
+];
+
+#[test]
+fn test_sse_parser_handles_split_lines_and_done() {
+    let mut parser = SseParser::new();
+    let mut events = Vec::new();
+    for chunk in CANNED_CHUNKS {
+        events.extend(parser.feed(chunk.as_bytes()).unwrap());
+    }
+
+    assert_eq!(events, vec![
+        StreamEvent::Delta("Hel".to_string()),
+        StreamEvent::Delta("lo".to_string()),
+        StreamEvent::Delta(" world".to_string()),
+        StreamEvent::Usage(Usage::new(12, 3)),
+    ]);
+    assert!(parser.is_done());
+}
+
+#[test]
+fn test_sse_parser_keeps_characters_split_between_reads() {
+    let line = "data: {\"choices\":[{\"delta\":{\"content\":\"hi 🦀\"}}]}\n\n".as_bytes();
+    // Cut inside the four bytes of the crab
+    let split = line.iter().position(|&b| b == 0xF0).unwrap() + 2;
+
+    let mut parser = SseParser::new();
+    let mut events = parser.feed(&line[..split]).unwrap();
+    events.extend(parser.feed(&line[split..]).unwrap());
+
+    assert_eq!(events, vec![StreamEvent::Delta("hi 🦀".to_string())]);
+}
+
+#[test]
+fn test_sse_parser_assembles_streamed_tool_calls() {
+    let mut parser = SseParser::new();
+    let mut events = Vec::new();
+    for chunk in [
+        "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"fetch_url\",\"arguments\":\"\"}}]}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"url\\\":\"}}]}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"https://example.com\\\"}\"}}]}}]}\n\n",
+        "data: [DONE]\n\n",
+    ] {
+        events.extend(parser.feed(chunk.as_bytes()).unwrap());
+    }
+
+    assert_eq!(events, vec![StreamEvent::ToolCalls(vec![ToolCall::new(
+        "call_1",
+        "fetch_url",
+        &json!({"url": "https://example.com"}),
+    )])]);
+}
+
+#[test]
+fn test_sse_parser_ignores_data_after_done() {
+    let mut parser = SseParser::new();
+    let deltas = parser
+        .feed(b"data: [DONE]\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"late\"}}]}\n\n")
+        .unwrap();
+
+    assert!(deltas.is_empty());
+}
+
+#[test]
+fn test_sse_parser_rejects_malformed_json() {
+    let mut parser = SseParser::new();
+    assert!(parser.feed(b"data: {not json}\n\n").is_err());
+}
+
+#[tokio::test]
+async fn test_complete_stream_against_mock_server() {
+    let server = MockServer::start(vec![MockResponse::sse(CANNED_CHUNKS)]).await;
+    let provider = DeepSeekProvider::new(
+        "test-key".to_string(),
+        "You are a test assistant.".to_string(),
+        server.base_url.clone(),
+    );
+
+    let mut stream = provider
+        .complete_stream(&[ChatMessage::user("Say hello")])
+        .await
+        .unwrap();
+
+    let mut events = Vec::new();
+    while let Some(event) = stream.next().await {
+        events.push(event.unwrap());
+    }
+    let deltas: Vec<&str> = events.iter().filter_map(StreamEvent::as_delta).collect();
+    assert_eq!(deltas.concat(), "Hello world");
+    assert_eq!(deltas.len(), 3);
+    assert_eq!(events.last(), Some(&StreamEvent::Usage(Usage::new(12, 3))));
+
+    let request = &server.requests().await[0];
+    assert!(request.starts_with("POST /v1/chat/completions"));
+    assert!(request.contains("\"stream\":true"));
+    assert!(request.contains("\"include_usage\":true"));
+    assert!(request.contains("You are a test assistant."));
+    assert!(request.to_lowercase().contains("authorization: bearer test-key"));
+}
+
+#[tokio::test]
+async fn test_openai_compatible_sends_history_and_model() {
+    let server = MockServer::start(vec![MockResponse::json(200, json!({
+        "choices": [{"message": {"role": "assistant", "content": "Paris"}}]
+    }))]).await;
+    // A base URL that already ends in /v1 must not get a second one
+    let provider = OpenAiProvider::new(
+        format!("{}/v1", server.base_url),
+        None,
+        "local-model".to_string(),
+        "You are terse.".to_string(),
+    );
+
+    let response = provider
+        .complete_messages(&[
+            ChatMessage::user("Capital of Italy?"),
+            ChatMessage::assistant("Rome"),
+            ChatMessage::user("And France?"),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(response, "Paris");
+
+    let request = &server.requests().await[0];
+    assert!(request.starts_with("POST /v1/chat/completions"));
+    assert!(request.contains("\"model\":\"local-model\""));
+    assert!(request.contains("\"content\":\"Rome\",\"role\":\"assistant\""));
+    assert!(!request.to_lowercase().contains("authorization"));
+}
+
+fn ok_response() -> MockResponse {
+    MockResponse::json(200, json!({
+        "choices": [{"message": {"role": "assistant", "content": "Paris"}}]
+    }))
+}
+
+fn provider_for(server: &MockServer, retry: RetryPolicy) -> OpenAiProvider {
+    OpenAiProvider::new(server.base_url.clone(), Some("test-key".to_string()), "m".to_string(), String::new())
+        .with_retry_policy(retry)
+}
+
+fn fast_retries(max_retries: u32) -> RetryPolicy {
+    RetryPolicy::default()
+        .with_max_retries(max_retries)
+        .with_backoff(Duration::from_millis(1), Duration::from_secs(5))
+}
+
+#[tokio::test]
+async fn test_retries_server_errors_until_success() {
+    let server = MockServer::start(vec![
+        MockResponse::json(503, json!({"error": {"message": "overloaded"}})),
+        MockResponse::json(502, json!({"error": {"message": "bad gateway"}})),
+        ok_response(),
+    ]).await;
+    let provider = provider_for(&server, fast_retries(3));
+
+    assert_eq!(provider.complete("Capital of France?").await.unwrap(), "Paris");
+    assert_eq!(server.requests().await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_retries() {
+    let server = MockServer::start(vec![MockResponse::json(500, json!({"error": {"message": "boom"}}))]).await;
+    let provider = provider_for(&server, fast_retries(2));
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert!(matches!(err, CompletionError::ServerError { status: 500, ref message } if message == "boom"));
+    assert_eq!(server.requests().await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_rate_limit_honours_retry_after() {
+    let server = MockServer::start(vec![
+        MockResponse::json(429, json!({"error": {"message": "slow down"}})).with_header("Retry-After", "1"),
+        ok_response(),
+    ]).await;
+    let provider = provider_for(&server, fast_retries(1));
+
+    let started = Instant::now();
+    assert_eq!(provider.complete("Hello").await.unwrap(), "Paris");
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(server.requests().await.len(), 2);
+}
+
+#[tokio::test]
+async fn test_rate_limit_error_carries_retry_after() {
+    let server = MockServer::start(vec![
+        MockResponse::json(429, json!({"error": {"message": "slow down"}})).with_header("Retry-After", "7"),
+    ]).await;
+    let provider = provider_for(&server, fast_retries(0));
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert_eq!(err.retry_after(), Some(Duration::from_secs(7)));
+    assert!(matches!(err, CompletionError::RateLimited { .. }));
+}
+
+#[tokio::test]
+async fn test_auth_failure_is_not_retried() {
+    let server = MockServer::start(vec![
+        MockResponse::json(401, json!({"error": {"message": "Authentication Fails (no such user)"}})),
+        ok_response(),
+    ]).await;
+    let provider = provider_for(&server, fast_retries(3));
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert!(matches!(err, CompletionError::AuthFailed(_)));
+    assert_eq!(server.requests().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_context_length_is_classified() {
+    let server = MockServer::start(vec![MockResponse::json(400, json!({"error": {
+        "message": "This model's maximum context length is 65536 tokens. However, you requested 70000 tokens.",
+        "type": "invalid_request_error"
+    }}))]).await;
+    let provider = provider_for(&server, fast_retries(3));
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert!(matches!(err, CompletionError::ContextTooLong(_)));
+    assert_eq!(server.requests().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_malformed_success_body() {
+    let server = MockServer::start(vec![MockResponse::json(200, json!({"unexpected": true}))]).await;
+    let provider = provider_for(&server, fast_retries(3));
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert!(matches!(err, CompletionError::MalformedResponse(_)));
+}
+
+#[tokio::test]
+async fn test_request_timeout_is_retried() {
+    let server = MockServer::start(vec![
+        ok_response().with_delay(Duration::from_secs(2)),
+        ok_response(),
+    ]).await;
+    let provider = provider_for(&server, fast_retries(1).with_timeout(Duration::from_millis(200)));
+
+    assert_eq!(provider.complete("Hello").await.unwrap(), "Paris");
+    assert_eq!(server.requests().await.len(), 2);
+
+    let server = MockServer::start(vec![ok_response().with_delay(Duration::from_secs(2))]).await;
+    let provider = provider_for(&server, fast_retries(0).with_timeout(Duration::from_millis(200)));
+    assert!(matches!(provider.complete("Hello").await.unwrap_err(), CompletionError::Timeout));
+}
+
+#[tokio::test]
+async fn test_streaming_request_is_retried_before_first_byte() {
+    let server = MockServer::start(vec![
+        MockResponse::json(503, json!({"error": "unavailable"})),
+        MockResponse::sse(CANNED_CHUNKS),
+    ]).await;
+    let provider = provider_for(&server, fast_retries(1));
+
+    let mut stream = provider.complete_stream(&[ChatMessage::user("Say hello")]).await.unwrap();
+    let mut response = String::new();
+    while let Some(event) = stream.next().await {
+        response.push_str(event.unwrap().as_delta().unwrap_or_default());
+    }
+    assert_eq!(response, "Hello world");
+}
+
+#[tokio::test]
+async fn test_stalled_stream_times_out() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+    ]).with_chunk_delay(Duration::from_secs(5))]).await;
+    let provider = provider_for(&server, fast_retries(0).with_timeout(Duration::from_millis(200)));
+
+    let started = Instant::now();
+    let mut stream = provider.complete_stream(&[ChatMessage::user("Say hello")]).await.unwrap();
+    assert_eq!(stream.next().await.unwrap().unwrap(), StreamEvent::Delta("Hel".to_string()));
+    assert!(matches!(stream.next().await.unwrap(), Err(CompletionError::Timeout)));
+    assert!(stream.next().await.is_none());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn test_complete_chat_reports_api_usage() {
+    let server = MockServer::start(vec![
+        MockResponse::json(200, json!({
+            "choices": [{"message": {"role": "assistant", "content": "Paris"}}],
+            "usage": {"prompt_tokens": 21, "completion_tokens": 1, "total_tokens": 22}
+        })),
+        ok_response(),
+    ]).await;
+    let provider = provider_for(&server, fast_retries(0));
+
+    let completion = provider.complete_chat(&[ChatMessage::user("Capital of France?")]).await.unwrap();
+    assert_eq!(completion.content, "Paris");
+    assert_eq!(completion.usage, Usage::new(21, 1));
+
+    // Without a usage field the counts are estimated locally
+    let completion = provider.complete_chat(&[ChatMessage::user("Capital of France?")]).await.unwrap();
+    assert_eq!(completion.usage.completion_tokens, 1);
+    assert!(completion.usage.prompt_tokens > 5);
+}
+
+fn request_json(request: &str) -> serde_json::Value {
+    let (_, body) = request.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(body).unwrap()
+}
+
+#[tokio::test]
+async fn test_generation_params_are_sent_per_call() {
+    let server = MockServer::start(vec![ok_response()]).await;
+    let provider = DeepSeekProvider::new("test-key".to_string(), String::new(), server.base_url.clone())
+        .with_temperature(0.7)
+        .with_retry_policy(fast_retries(0));
+    let messages = [ChatMessage::user("Capital of France?")];
+
+    provider.complete_chat(&messages).await.unwrap();
+    let params = GenerationParams {
+        model: Some("deepseek-reasoner".to_string()),
+        top_p: Some(0.5),
+        stop: vec!["\n\n".to_string()],
+        seed: Some(7),
+        ..GenerationParams::default().with_temperature(0.0)
+    };
+    provider.chat(&messages, &params).await.unwrap();
+
+    let requests = server.requests().await;
+    let defaults = request_json(&requests[0]);
+    assert_eq!(defaults["model"], "deepseek-chat");
+    assert_eq!(defaults["temperature"], json!(0.7f32));
+    assert_eq!(defaults["max_tokens"], json!(2048));
+    assert!(defaults.get("frequency_penalty").is_none());
+    assert!(defaults.get("presence_penalty").is_none());
+
+    let overridden = request_json(&requests[1]);
+    assert_eq!(overridden["model"], "deepseek-reasoner");
+    assert_eq!(overridden["temperature"], json!(0.0));
+    assert_eq!(overridden["top_p"], json!(0.5f32));
+    assert_eq!(overridden["stop"], json!(["\n\n"]));
+    assert_eq!(overridden["seed"], json!(7));
+    assert_eq!(overridden["max_tokens"], json!(2048));
+}
+
+#[tokio::test]
+async fn test_complete_json_requests_json_mode() {
+    let server = MockServer::start(vec![MockResponse::json(200, json!({
+        "choices": [{"message": {"role": "assistant", "content": "{\"city\": \"Paris\"}"}}]
+    }))]).await;
+    let provider = provider_for(&server, fast_retries(0));
+
+    let answer: serde_json::Value = provider.complete_json(&[ChatMessage::user("Capital of France as JSON?")]).await.unwrap();
+    assert_eq!(answer, json!({"city": "Paris"}));
+
+    let request = &server.requests().await[0];
+    assert!(request.contains("\"response_format\":{\"type\":\"json_object\"}"));
+}
+
+#[tokio::test]
+async fn test_complete_with_tools_sends_definitions_and_parses_calls() {
+    let server = MockServer::start(vec![MockResponse::json(200, json!({
+        "choices": [{"message": {
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": "call_abc",
+                "type": "function",
+                "function": {"name": "fetch_url", "arguments": "{\"url\":\"https://example.com\"}"}
+            }]
+        }}]
+    }))]).await;
+    let provider = provider_for(&server, fast_retries(0));
+    let tool = ToolDefinition {
+        name: "fetch_url".to_string(),
+        description: "Fetch a page".to_string(),
+        parameters: json!({"type": "object", "properties": {"url": {"type": "string"}}}),
+    };
+
+    let messages = [
+        ChatMessage::user("Earlier question"),
+        ChatMessage::from_completion(&Completion {
+            tool_calls: vec![ToolCall::new("call_1", "fetch_url", &json!({"url": "https://a.test"}))],
+            ..Completion::new(String::new(), Usage::default())
+        }),
+        ChatMessage::tool_result("call_1", "Page text"),
+    ];
+    let completion = provider.complete_with_tools(&messages, &[tool]).await.unwrap();
+
+    assert_eq!(completion.content, "");
+    assert_eq!(completion.tool_calls, vec![ToolCall::new("call_abc", "fetch_url", &json!({"url": "https://example.com"}))]);
+
+    let request = &server.requests().await[0];
+    assert!(request.contains("\"tools\":[{\"function\":{\"description\":\"Fetch a page\""));
+    assert!(request.contains("\"role\":\"tool\",\"tool_call_id\":\"call_1\""));
+    assert!(request.contains("\"tool_calls\":[{\"function\":{\"arguments\":"));
+}
+
+#[test]
+fn test_backoff_and_retry_after_parsing() {
+    let policy = RetryPolicy::default().with_backoff(Duration::from_millis(100), Duration::from_millis(350));
+    assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    assert_eq!(policy.backoff(1), Duration::from_millis(200));
+    assert_eq!(policy.backoff(2), Duration::from_millis(350));
+
+    assert_eq!(parse_retry_after(" 12 "), Some(Duration::from_secs(12)));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+    assert_eq!(parse_retry_after("soon"), None);
+}
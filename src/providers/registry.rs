@@ -0,0 +1,487 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::completion::{CompletionError, CompletionProvider, GenerationParams, ParamsProvider};
+use crate::personality::PersonalityProfile;
+use crate::providers::deepseek::deepseek::{self, DeepSeekProvider};
+use crate::providers::mock::replay::ReplayProvider;
+use crate::providers::ollama::ollama::{self, OllamaProvider};
+use crate::providers::openai::openai::OpenAiProvider;
+use crate::providers::retry::RetryPolicy;
+use crate::providers::router::{FallbackProvider, HealthTracker};
+
+pub const DEFAULT_CONFIG_PATH: &str = "data/providers.json";
+pub const DEFAULT_CASSETTE_PATH: &str = "data/cassettes/replay.jsonl";
+
+/// Routing key for generating tweet topics; falls back to the `tweet` route.
+pub const TASK_TWEET_TOPIC: &str = "tweet.topic";
+/// Routing key for extracting document insights; falls back to the `doc` route.
+pub const TASK_DOC_INSIGHTS: &str = "doc.insights";
+/// Routing key for condensing old chat turns; falls back to the `chat` route.
+pub const TASK_CHAT_SUMMARY: &str = "chat.summary";
+/// Routing key for reflecting on recent memories; falls back to the `chat` route.
+pub const TASK_MEMORY_REFLECTION: &str = "memory.reflection";
+/// Routing key for extracting facts to learn from exchanges; falls back to the `chat` route.
+pub const TASK_CHAT_LEARNING: &str = "chat.learning";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Deepseek,
+    Openai,
+    Ollama,
+    /// Recorded responses from a JSONL cassette; see `ReplayProvider`.
+    Replay,
+}
+
+/// One named backend entry, e.g. `"local": {"kind": "ollama", "model": "llama3"}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    pub kind: ProviderKind,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Name of the environment variable holding the API key.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// Overrides `COMPLETION_MAX_RETRIES` for this provider.
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    /// Overrides `COMPLETION_TIMEOUT_SECS` for this provider.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Replay only: path of the JSONL cassette.
+    #[serde(default)]
+    pub cassette: Option<String>,
+    /// Replay only: provider to call and record when the cassette has no answer.
+    #[serde(default)]
+    pub record_from: Option<String>,
+    /// Providers to try, in order, when this one fails or its circuit is open.
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
+}
+
+impl ProviderConfig {
+    pub fn new(kind: ProviderKind) -> Self {
+        Self {
+            kind,
+            base_url: None,
+            model: None,
+            api_key_env: None,
+            temperature: None,
+            max_tokens: None,
+            max_retries: None,
+            timeout_secs: None,
+            cassette: None,
+            record_from: None,
+            fallbacks: Vec::new(),
+        }
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        let mut policy = RetryPolicy::from_env();
+        if let Some(max_retries) = self.max_retries {
+            policy = policy.with_max_retries(max_retries);
+        }
+        if let Some(timeout_secs) = self.timeout_secs {
+            policy = policy.with_timeout(Duration::from_secs(timeout_secs));
+        }
+        policy
+    }
+}
+
+/// Contents of `data/providers.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistryConfig {
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderConfig>,
+    /// Per-command and per-task routes, e.g. `"doc": "local"` or
+    /// `"tweet.topic": "small"`. Also accepted as `"routes"`.
+    #[serde(default, alias = "routes")]
+    pub commands: HashMap<String, String>,
+    /// Per-command and per-task generation params, e.g.
+    /// `"tweet": {"temperature": 1.0}`, replacing the built-in ones.
+    #[serde(default)]
+    pub params: HashMap<String, GenerationParams>,
+}
+
+/// Builds completion providers by name.
+///
+/// Four entries are always available from the environment: `deepseek`
+/// (`DEEPSEEK_*`), `openai` (`OPENAI_*`), `ollama` (`OLLAMA_*`) and `replay`
+/// (`REPLAY_CASSETTE`, optionally recording from `REPLAY_RECORD_FROM`). A config
+/// file can add or override entries, pick the default (also settable with
+/// `AI_PROVIDER`) and route individual commands or tasks to other providers.
+/// A character selects its provider with a top-level `"provider"` field.
+///
+/// An entry with `fallbacks` (for the default provider, also settable with
+/// `AI_FALLBACKS`) is built as a `FallbackProvider` that moves down the list
+/// when a provider fails, sharing circuit-breaker state across the process.
+#[derive(Debug, Clone)]
+pub struct ProviderRegistry {
+    default: String,
+    providers: HashMap<String, ProviderConfig>,
+    commands: HashMap<String, String>,
+    params: HashMap<String, GenerationParams>,
+    api_key_override: Option<String>,
+    health: Arc<HealthTracker>,
+}
+
+impl ProviderRegistry {
+    /// Loads the config file named by `PROVIDERS_CONFIG`, or `data/providers.json`.
+    pub fn load_default() -> Result<Self, CompletionError> {
+        dotenv::dotenv().ok();
+        let path = env::var("PROVIDERS_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+        Self::load(path)
+    }
+
+    /// Loads a config file on top of the environment defaults. A missing file is not an error.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CompletionError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::from_config(RegistryConfig::default()));
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| CompletionError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+        let config: RegistryConfig = serde_json::from_str(&content)
+            .map_err(|e| CompletionError::Config(format!("Failed to parse {}: {}", path.display(), e)))?;
+        Ok(Self::from_config(config))
+    }
+
+    pub fn from_config(config: RegistryConfig) -> Self {
+        let mut providers = Self::builtin_providers();
+        providers.extend(config.providers);
+        let mut params = Self::builtin_params();
+        params.extend(config.params);
+
+        let default = config.default
+            .or_else(|| env::var("AI_PROVIDER").ok())
+            .unwrap_or_else(|| "deepseek".to_string());
+
+        if let Ok(fallbacks) = env::var("AI_FALLBACKS") {
+            if let Some(config) = providers.get_mut(&default).filter(|config| config.fallbacks.is_empty()) {
+                config.fallbacks = fallbacks
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty() && *name != default)
+                    .map(String::from)
+                    .collect();
+            }
+        }
+
+        Self {
+            default,
+            providers,
+            commands: config.commands,
+            params,
+            api_key_override: None,
+            health: HealthTracker::global(),
+        }
+    }
+
+    fn builtin_providers() -> HashMap<String, ProviderConfig> {
+        let mut providers = HashMap::new();
+        providers.insert("deepseek".to_string(), ProviderConfig {
+            base_url: env::var("DEEPSEEK_BASE_URL").ok(),
+            model: env::var("DEEPSEEK_MODEL").ok(),
+            api_key_env: Some("DEEPSEEK_API_KEY".to_string()),
+            temperature: env::var("DEEPSEEK_TEMPERATURE").ok().and_then(|v| v.parse().ok()),
+            max_tokens: env::var("DEEPSEEK_MAX_TOKENS").ok().and_then(|v| v.parse().ok()),
+            ..ProviderConfig::new(ProviderKind::Deepseek)
+        });
+        providers.insert("openai".to_string(), ProviderConfig {
+            base_url: env::var("OPENAI_BASE_URL").ok(),
+            model: env::var("OPENAI_MODEL").ok(),
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+            ..ProviderConfig::new(ProviderKind::Openai)
+        });
+        providers.insert("ollama".to_string(), ProviderConfig {
+            base_url: env::var("OLLAMA_BASE_URL").ok(),
+            model: env::var("OLLAMA_MODEL").ok(),
+            ..ProviderConfig::new(ProviderKind::Ollama)
+        });
+        providers.insert("replay".to_string(), ProviderConfig {
+            cassette: env::var("REPLAY_CASSETTE").ok(),
+            record_from: env::var("REPLAY_RECORD_FROM").ok(),
+            ..ProviderConfig::new(ProviderKind::Replay)
+        });
+        providers
+    }
+
+    /// Commands whose output suffers at the provider's default sampling:
+    /// extraction wants repeatable answers, tweets want variety.
+    fn builtin_params() -> HashMap<String, GenerationParams> {
+        let mut params = HashMap::new();
+        params.insert(TASK_DOC_INSIGHTS.to_string(), GenerationParams::default().with_temperature(0.2));
+        params.insert(TASK_CHAT_SUMMARY.to_string(), GenerationParams::default().with_temperature(0.2));
+        params.insert(TASK_MEMORY_REFLECTION.to_string(), GenerationParams::default().with_temperature(0.3));
+        params.insert(TASK_CHAT_LEARNING.to_string(), GenerationParams::default().with_temperature(0.2));
+        params.insert("tweet".to_string(), GenerationParams::default().with_temperature(1.2));
+        params
+    }
+
+    /// Makes `name` the provider used when nothing more specific is configured.
+    pub fn with_default(mut self, name: impl Into<String>) -> Self {
+        self.default = name.into();
+        self
+    }
+
+    /// Uses `api_key` for DeepSeek entries instead of reading `DEEPSEEK_API_KEY`.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key_override = Some(api_key.into());
+        self
+    }
+
+    /// Tracks provider health in `health` instead of the process-wide tracker.
+    pub fn with_health(mut self, health: Arc<HealthTracker>) -> Self {
+        self.health = health;
+        self
+    }
+
+    pub fn health(&self) -> &HealthTracker {
+        &self.health
+    }
+
+    pub fn default_name(&self) -> &str {
+        &self.default
+    }
+
+    /// The configured routes, sorted by command or task.
+    pub fn routes(&self) -> Vec<(&str, &str)> {
+        let mut routes: Vec<(&str, &str)> = self.commands
+            .iter()
+            .map(|(command, name)| (command.as_str(), name.as_str()))
+            .collect();
+        routes.sort();
+        routes
+    }
+
+    /// The fallback chain configured for `name`, if any.
+    pub fn fallbacks(&self, name: &str) -> &[String] {
+        self.providers.get(name).map_or(&[], |config| config.fallbacks.as_slice())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.providers.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// The provider a character asks for, falling back to the default.
+    pub fn name_for_character<'a>(&'a self, profile: &'a PersonalityProfile) -> &'a str {
+        profile.get_str("provider").unwrap_or(&self.default)
+    }
+
+    /// The provider configured for a command (`chat`, `doc`, `web`, `tweet`)
+    /// or task (`tweet.topic`, `doc.insights`), falling back to the parent
+    /// route of a task and then to the character's provider.
+    pub fn name_for_command<'a>(&'a self, command: &str, profile: &'a PersonalityProfile) -> &'a str {
+        let mut key = command;
+        loop {
+            if let Some(name) = self.commands.get(key) {
+                return name;
+            }
+            match key.rsplit_once('.') {
+                Some((parent, _)) => key = parent,
+                None => return self.name_for_character(profile),
+            }
+        }
+    }
+
+    /// Generation params for a command or task, layered over the
+    /// character's: a task's own params first, then its parent route's.
+    pub fn params_for_command(&self, command: &str, profile: &PersonalityProfile) -> GenerationParams {
+        let mut params = GenerationParams::default();
+        let mut key = Some(command);
+        while let Some(current) = key {
+            if let Some(route) = self.params.get(current) {
+                params = params.or(route);
+            }
+            key = current.rsplit_once('.').map(|(parent, _)| parent);
+        }
+        params.or(&profile.generation_params())
+    }
+
+    /// Builds provider `name`, wrapped with its fallbacks if it has any.
+    /// Fallbacks that cannot be built (e.g. a missing API key) are left out,
+    /// and so is the primary as long as some fallback can be built.
+    pub fn build(&self, name: &str, system_message: String) -> Result<Box<dyn CompletionProvider>, CompletionError> {
+        if let Some(cycle) = self.recording_cycle(name, &mut Vec::new()) {
+            return Err(CompletionError::Config(format!(
+                "Provider '{}' would record from itself: {}",
+                cycle[0],
+                cycle.join(" -> ")
+            )));
+        }
+
+        let fallbacks = self.fallbacks(name);
+        if fallbacks.is_empty() {
+            return self.build_single(name, system_message);
+        }
+
+        let mut candidates = Vec::new();
+        let mut first_error = None;
+        for candidate in std::iter::once(name).chain(fallbacks.iter().map(String::as_str)) {
+            match self.build_single(candidate, system_message.clone()) {
+                Ok(provider) => candidates.push((candidate.to_string(), provider)),
+                Err(e) => {
+                    log::warn!("Leaving provider '{}' out of the fallback chain: {}", candidate, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) if candidates.is_empty() => Err(e),
+            _ => Ok(Box::new(FallbackProvider::new(candidates, self.health.clone()).with_primary(name))),
+        }
+    }
+
+    /// The providers through which building `name` leads back to a replay
+    /// provider already being built, if any: a replay provider recording from
+    /// a chain that includes it would otherwise be built over and over.
+    /// `recording` holds the replay providers being built, each with the
+    /// provider it records from.
+    fn recording_cycle(&self, name: &str, recording: &mut Vec<(String, String)>) -> Option<Vec<String>> {
+        for member in std::iter::once(name).chain(self.fallbacks(name).iter().map(String::as_str)) {
+            if let Some(start) = recording.iter().position(|(replay, _)| replay == member) {
+                let mut cycle: Vec<String> = recording[start..]
+                    .iter()
+                    .flat_map(|(replay, source)| [replay.clone(), source.clone()])
+                    .collect();
+                cycle.push(member.to_string());
+                return Some(cycle);
+            }
+            let source = self.providers
+                .get(member)
+                .filter(|config| config.kind == ProviderKind::Replay)
+                .and_then(|config| config.record_from.as_deref());
+            if let Some(source) = source {
+                recording.push((member.to_string(), source.to_string()));
+                let cycle = self.recording_cycle(source, recording);
+                recording.pop();
+                if cycle.is_some() {
+                    return cycle;
+                }
+            }
+        }
+        None
+    }
+
+    fn build_single(&self, name: &str, system_message: String) -> Result<Box<dyn CompletionProvider>, CompletionError> {
+        let config = self.providers
+            .get(name)
+            .ok_or_else(|| CompletionError::Config(format!(
+                "Unknown provider '{}'. Available providers: {}",
+                name,
+                self.names().join(", ")
+            )))?;
+
+        let api_key = match &config.api_key_env {
+            Some(_) if config.kind == ProviderKind::Deepseek && self.api_key_override.is_some() => {
+                self.api_key_override.clone()
+            }
+            Some(var) => env::var(var).ok().filter(|key| !key.is_empty()),
+            None => None,
+        };
+
+        let provider: Box<dyn CompletionProvider> = match config.kind {
+            ProviderKind::Deepseek => {
+                let api_key = api_key.ok_or_else(|| CompletionError::Config(format!(
+                    "{} environment variable is not set. Please set it or pass --api-key.",
+                    config.api_key_env.as_deref().unwrap_or("DEEPSEEK_API_KEY")
+                )))?;
+                let base_url = config.base_url.clone()
+                    .unwrap_or_else(|| deepseek::DEFAULT_BASE_URL.to_string());
+
+                let mut provider = DeepSeekProvider::new(api_key, system_message, base_url)
+                    .with_retry_policy(config.retry_policy());
+                if let Some(model) = &config.model {
+                    provider = provider.with_model(model.clone());
+                }
+                if let Some(temperature) = config.temperature {
+                    provider = provider.with_temperature(temperature);
+                }
+                if let Some(max_tokens) = config.max_tokens {
+                    provider = provider.with_max_tokens(max_tokens);
+                }
+                Box::new(provider)
+            }
+            ProviderKind::Openai => {
+                let mut provider = OpenAiProvider::new(
+                    config.base_url.clone().unwrap_or_else(|| "https://api.openai.com".to_string()),
+                    api_key,
+                    config.model.clone().unwrap_or_else(|| "gpt-4o-mini".to_string()),
+                    system_message,
+                ).with_retry_policy(config.retry_policy());
+                if let Some(temperature) = config.temperature {
+                    provider = provider.with_temperature(temperature);
+                }
+                if let Some(max_tokens) = config.max_tokens {
+                    provider = provider.with_max_tokens(max_tokens);
+                }
+                Box::new(provider)
+            }
+            ProviderKind::Ollama => {
+                let mut provider = OllamaProvider::new(
+                    config.base_url.clone().unwrap_or_else(|| ollama::DEFAULT_BASE_URL.to_string()),
+                    config.model.clone().unwrap_or_else(|| "llama3".to_string()),
+                    system_message,
+                ).with_retry_policy(config.retry_policy());
+                if let Some(temperature) = config.temperature {
+                    provider = provider.with_temperature(temperature);
+                }
+                if let Some(max_tokens) = config.max_tokens {
+                    provider = provider.with_max_tokens(max_tokens);
+                }
+                Box::new(provider)
+            }
+            ProviderKind::Replay => {
+                let cassette = config.cassette.clone().unwrap_or_else(|| DEFAULT_CASSETTE_PATH.to_string());
+                match &config.record_from {
+                    Some(source) if self.providers.get(source).map(|c| c.kind) == Some(ProviderKind::Replay) => {
+                        return Err(CompletionError::Config(format!(
+                            "Replay provider '{}' cannot record from another replay provider '{}'",
+                            name, source
+                        )));
+                    }
+                    Some(source) => Box::new(ReplayProvider::recording(cassette, self.build(source, system_message)?)?),
+                    None => Box::new(ReplayProvider::replaying(cassette, system_message)?),
+                }
+            }
+        };
+
+        Ok(provider)
+    }
+
+    /// Builds the provider for a character with its generated system prompt
+    /// and generation params.
+    pub fn for_character(&self, profile: &PersonalityProfile) -> Result<Arc<dyn CompletionProvider>, CompletionError> {
+        let provider = self.build(self.name_for_character(profile), profile.generate_system_prompt())?;
+        Ok(ParamsProvider::wrap(provider.into(), profile.generation_params()))
+    }
+
+    /// Builds the provider routed to `command` for this character, with the
+    /// command's generation params.
+    pub fn for_command(
+        &self,
+        command: &str,
+        profile: &PersonalityProfile,
+        system_message: String,
+    ) -> Result<Arc<dyn CompletionProvider>, CompletionError> {
+        let provider = self.build(self.name_for_command(command, profile), system_message)?;
+        Ok(ParamsProvider::wrap(provider.into(), self.params_for_command(command, profile)))
+    }
+}
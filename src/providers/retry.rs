@@ -0,0 +1,127 @@
+use std::env;
+use std::future::Future;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use reqwest::header::RETRY_AFTER;
+use crate::completion::CompletionError;
+
+/// How completion requests are retried and how long each attempt may take.
+///
+/// Rate limits, 5xx responses and timeouts are retried with exponential
+/// backoff (`initial_backoff`, doubled per attempt, capped at `max_backoff`).
+/// A `Retry-After` from the provider replaces the computed delay, within the
+/// same cap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Defaults overridden by `COMPLETION_MAX_RETRIES`, `COMPLETION_BACKOFF_MS`,
+    /// `COMPLETION_MAX_BACKOFF_SECS` and `COMPLETION_TIMEOUT_SECS`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        defaults.clone()
+            .with_max_retries(var("COMPLETION_MAX_RETRIES").map(|v| v as u32).unwrap_or(defaults.max_retries))
+            .with_backoff(
+                var("COMPLETION_BACKOFF_MS").map(Duration::from_millis).unwrap_or(defaults.initial_backoff),
+                var("COMPLETION_MAX_BACKOFF_SECS").map(Duration::from_secs).unwrap_or(defaults.max_backoff),
+            )
+            .with_timeout(var("COMPLETION_TIMEOUT_SECS").map(Duration::from_secs).unwrap_or(defaults.timeout))
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Delay before retry number `attempt` (starting at 0).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    /// Runs `operation` until it succeeds, fails with a non-retryable error or
+    /// the retries are used up. Each attempt is bounded by `timeout`.
+    pub async fn run<T, F, Fut>(&self, mut operation: F) -> Result<T, CompletionError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, CompletionError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = match tokio::time::timeout(self.timeout, operation()).await {
+                Ok(result) => result,
+                Err(_) => Err(CompletionError::Timeout),
+            };
+
+            match result {
+                Err(e) if e.is_retryable() && attempt < self.max_retries => {
+                    let delay = e.retry_after()
+                        .map(|d| d.min(self.max_backoff))
+                        .unwrap_or_else(|| self.backoff(attempt));
+                    log::warn!("{} - retrying in {:?} (attempt {}/{})", e, delay, attempt + 1, self.max_retries);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+/// Passes successful responses through and turns any other status into a
+/// classified `CompletionError`.
+pub async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, CompletionError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = response.text().await.unwrap_or_default();
+    Err(CompletionError::from_status(status.as_u16(), &body, retry_after))
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&Utc) - Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
@@ -0,0 +1,196 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use crate::completion::CompletionError;
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// When a provider's circuit opens and how long it stays open.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitConfig {
+    /// Consecutive failures that open the circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects requests before a trial is let through.
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+}
+
+impl CircuitConfig {
+    /// Defaults overridden by `CIRCUIT_FAILURE_THRESHOLD` and `CIRCUIT_COOLDOWN_SECS`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        defaults.clone()
+            .with_failure_threshold(var("CIRCUIT_FAILURE_THRESHOLD").map(|v| v as u32).unwrap_or(defaults.failure_threshold))
+            .with_cooldown(var("CIRCUIT_COOLDOWN_SECS").map(Duration::from_secs).unwrap_or(defaults.cooldown))
+    }
+
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Requests go through.
+    Closed,
+    /// Too many recent failures; requests are skipped until the cooldown ends.
+    Open,
+    /// Cooldown over; the next request is a trial that closes or reopens it.
+    HalfOpen,
+}
+
+/// What is known about one provider, for status output.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderHealth {
+    pub name: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub successes: u64,
+    pub failures: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// When the half-open trial request was let through, if one is in flight.
+    trial_started: Option<Instant>,
+    successes: u64,
+    failures: u64,
+    last_error: Option<String>,
+}
+
+impl Circuit {
+    fn state(&self, cooldown: Duration) -> CircuitState {
+        match self.opened_at {
+            Some(opened_at) if opened_at.elapsed() < cooldown => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+            None => CircuitState::Closed,
+        }
+    }
+}
+
+/// Per-provider circuit breakers, keyed by registry name.
+///
+/// A provider that fails `failure_threshold` times in a row is skipped for
+/// `cooldown`; after that a single trial request decides whether it is
+/// healthy again. Only provider failures count (see
+/// `CompletionError::is_provider_failure`), not problems with the request.
+#[derive(Debug)]
+pub struct HealthTracker {
+    config: CircuitConfig,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl HealthTracker {
+    pub fn new(config: CircuitConfig) -> Self {
+        Self {
+            config,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The tracker shared by every registry in this process, so a provider
+    /// that is down is skipped by all commands, not just the one that noticed.
+    pub fn global() -> Arc<HealthTracker> {
+        static GLOBAL: OnceLock<Arc<HealthTracker>> = OnceLock::new();
+        GLOBAL.get_or_init(|| Arc::new(HealthTracker::new(CircuitConfig::from_env()))).clone()
+    }
+
+    /// Whether a request may be sent to `name` now. Lets one trial request
+    /// through a circuit whose cooldown has ended.
+    pub fn allow(&self, name: &str) -> bool {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = match circuits.get_mut(name) {
+            Some(circuit) => circuit,
+            None => return true,
+        };
+
+        match circuit.state(self.config.cooldown) {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => {
+                // A trial that never reported back (e.g. the caller gave up)
+                // must not keep the circuit open forever
+                let trial_pending = circuit
+                    .trial_started
+                    .is_some_and(|started| started.elapsed() < self.config.cooldown);
+                if trial_pending {
+                    return false;
+                }
+                circuit.trial_started = Some(Instant::now());
+                true
+            }
+        }
+    }
+
+    pub fn record_success(&self, name: &str) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(name.to_string()).or_default();
+        circuit.successes += 1;
+        circuit.consecutive_failures = 0;
+        circuit.opened_at = None;
+        circuit.trial_started = None;
+    }
+
+    pub fn record_failure(&self, name: &str, error: &CompletionError) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(name.to_string()).or_default();
+        circuit.failures += 1;
+        circuit.consecutive_failures += 1;
+        circuit.last_error = Some(error.to_string());
+
+        let failed_trial = circuit.trial_started.take().is_some();
+        if failed_trial || circuit.consecutive_failures >= self.config.failure_threshold {
+            log::warn!("Provider '{}' is unavailable, skipping it for {}s: {}", name, self.config.cooldown.as_secs(), error);
+            circuit.opened_at = Some(Instant::now());
+        }
+    }
+
+    pub fn state(&self, name: &str) -> CircuitState {
+        self.circuits
+            .lock()
+            .unwrap()
+            .get(name)
+            .map_or(CircuitState::Closed, |circuit| circuit.state(self.config.cooldown))
+    }
+
+    /// Every provider that has been used, sorted by name.
+    pub fn snapshot(&self) -> Vec<ProviderHealth> {
+        let circuits = self.circuits.lock().unwrap();
+        let mut health: Vec<ProviderHealth> = circuits
+            .iter()
+            .map(|(name, circuit)| ProviderHealth {
+                name: name.clone(),
+                state: circuit.state(self.config.cooldown),
+                consecutive_failures: circuit.consecutive_failures,
+                successes: circuit.successes,
+                failures: circuit.failures,
+                last_error: circuit.last_error.clone(),
+            })
+            .collect();
+        health.sort_by(|a, b| a.name.cmp(&b.name));
+        health
+    }
+}
@@ -0,0 +1,158 @@
+pub mod health;
+
+#[cfg(test)]
+mod tests;
+
+pub use health::{CircuitState, HealthTracker};
+
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use crate::completion::{
+    ChatMessage, Completion, CompletionError, CompletionProvider, CompletionStream, GenerationParams, ToolDefinition,
+};
+
+/// A provider that tries an ordered list of providers until one answers.
+///
+/// Provider failures (rate limits, 5xx, timeouts, auth problems) move on to
+/// the next entry and are recorded in the shared `HealthTracker`, so an
+/// entry whose circuit is open is skipped without being called. Errors
+/// about the request itself, like an oversized prompt, are returned as-is
+/// since every other provider would reject it too. Streams only fall back
+/// if they fail to start.
+///
+/// A `model` in the params of a call names one of the primary's models, so
+/// the other entries get the params without it and use their own.
+pub struct FallbackProvider {
+    candidates: Vec<(String, Box<dyn CompletionProvider>)>,
+    primary: String,
+    health: Arc<HealthTracker>,
+}
+
+impl FallbackProvider {
+    /// `candidates` are registry names paired with their providers, in the
+    /// order they should be tried. There must be at least one.
+    pub fn new(candidates: Vec<(String, Box<dyn CompletionProvider>)>, health: Arc<HealthTracker>) -> Self {
+        assert!(!candidates.is_empty(), "FallbackProvider needs at least one provider");
+        let primary = candidates[0].0.clone();
+        Self { candidates, primary, health }
+    }
+
+    /// Sets the entry the chain was configured for, when it is not the first
+    /// candidate because it could not be built.
+    pub fn with_primary(mut self, name: impl Into<String>) -> Self {
+        self.primary = name.into();
+        self
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.candidates.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// The provider requests will go to first: the first entry whose circuit
+    /// isn't open. Model, limits and prompt are reported from it.
+    fn current(&self) -> &dyn CompletionProvider {
+        self.candidates
+            .iter()
+            .find(|(name, _)| self.health.state(name) != CircuitState::Open)
+            .unwrap_or(&self.candidates[0])
+            .1
+            .as_ref()
+    }
+
+    async fn route<'a, T>(
+        &'a self,
+        params: &GenerationParams,
+        call: impl Fn(&'a dyn CompletionProvider, GenerationParams) -> BoxFuture<'a, Result<T, CompletionError>>,
+    ) -> Result<T, CompletionError> {
+        let mut last_error = None;
+
+        for (name, provider) in &self.candidates {
+            if !self.health.allow(name) {
+                log::debug!("Skipping provider '{}': circuit open", name);
+                continue;
+            }
+
+            let params = if *name == self.primary {
+                params.clone()
+            } else {
+                GenerationParams { model: None, ..params.clone() }
+            };
+            match call(provider.as_ref(), params).await {
+                Ok(result) => {
+                    self.health.record_success(name);
+                    return Ok(result);
+                }
+                Err(e) if e.is_provider_failure() => {
+                    log::warn!("Provider '{}' failed, trying the next one: {}", name, e);
+                    self.health.record_failure(name, &e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| CompletionError::Unavailable(format!(
+            "circuits are open for {}",
+            self.names().join(", ")
+        ))))
+    }
+}
+
+#[async_trait::async_trait]
+impl CompletionProvider for FallbackProvider {
+    fn model(&self) -> &str {
+        self.current().model()
+    }
+
+    fn system_message(&self) -> &str {
+        self.current().system_message()
+    }
+
+    fn params(&self) -> GenerationParams {
+        self.current().params()
+    }
+
+    fn context_window(&self) -> usize {
+        self.current().context_window()
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.route(params, |provider, params| Box::pin(async move { provider.chat(messages, &params).await })).await
+    }
+
+    async fn chat_json(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.route(params, |provider, params| Box::pin(async move { provider.chat_json(messages, &params).await })).await
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.current().supports_tools()
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<Completion, CompletionError> {
+        self.route(params, |provider, params| {
+            Box::pin(async move { provider.chat_with_tools(messages, tools, &params).await })
+        })
+        .await
+    }
+
+    async fn chat_stream(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<CompletionStream, CompletionError> {
+        self.route(params, |provider, params| Box::pin(async move { provider.chat_stream(messages, &params).await })).await
+    }
+
+    async fn chat_with_tools_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.route(params, |provider, params| {
+            Box::pin(async move { provider.chat_with_tools_stream(messages, tools, &params).await })
+        })
+        .await
+    }
+}
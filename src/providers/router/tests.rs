@@ -0,0 +1,221 @@
+use super::*;
+use super::health::CircuitConfig;
+use crate::personality::PersonalityProfile;
+use crate::providers::mock::mock::MockProvider;
+use crate::providers::mock_server::{MockResponse, MockServer};
+use crate::providers::registry::{ProviderRegistry, RegistryConfig, TASK_DOC_INSIGHTS, TASK_TWEET_TOPIC};
+use serde_json::json;
+use std::time::Duration;
+
+fn server_error() -> CompletionError {
+    CompletionError::ServerError { status: 503, message: "overloaded".to_string() }
+}
+
+fn tracker(failure_threshold: u32, cooldown: Duration) -> Arc<HealthTracker> {
+    Arc::new(HealthTracker::new(
+        CircuitConfig::default().with_failure_threshold(failure_threshold).with_cooldown(cooldown),
+    ))
+}
+
+fn chain(primary: MockProvider, backup: MockProvider, health: &Arc<HealthTracker>) -> FallbackProvider {
+    FallbackProvider::new(
+        vec![
+            ("primary".to_string(), Box::new(primary.with_model("big")) as Box<dyn CompletionProvider>),
+            ("backup".to_string(), Box::new(backup.with_model("small"))),
+        ],
+        health.clone(),
+    )
+}
+
+#[tokio::test]
+async fn test_falls_back_on_provider_failure() {
+    let health = tracker(3, Duration::from_secs(60));
+    let provider = chain(MockProvider::new(Vec::<String>::new()).with_error(server_error()), MockProvider::always("backup"), &health);
+
+    assert_eq!(provider.complete("Hello").await.unwrap(), "backup");
+    assert_eq!(health.state("primary"), CircuitState::Closed);
+
+    let primary = health.snapshot().into_iter().find(|h| h.name == "primary").unwrap();
+    assert_eq!(primary.failures, 1);
+    assert_eq!(primary.last_error.as_deref(), Some("Server error (503): overloaded"));
+}
+
+#[tokio::test]
+async fn test_request_errors_are_not_retried_elsewhere() {
+    let health = tracker(3, Duration::from_secs(60));
+    let primary = MockProvider::new(Vec::<String>::new())
+        .with_error(CompletionError::ContextTooLong("prompt is too long".to_string()));
+    let provider = chain(primary, MockProvider::always("backup"), &health);
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert!(matches!(err, CompletionError::ContextTooLong(_)));
+    assert!(health.snapshot().is_empty());
+}
+
+#[tokio::test]
+async fn test_open_circuit_skips_the_provider() {
+    let health = tracker(2, Duration::from_secs(60));
+    let primary = MockProvider::new(Vec::<String>::new())
+        .with_error(server_error())
+        .with_error(CompletionError::Timeout)
+        .with_reply("primary");
+    let provider = chain(primary, MockProvider::always("backup"), &health);
+
+    assert_eq!(provider.model(), "big");
+    for _ in 0..2 {
+        assert_eq!(provider.complete("Hello").await.unwrap(), "backup");
+    }
+    assert_eq!(health.state("primary"), CircuitState::Open);
+    assert_eq!(provider.model(), "small");
+
+    // The primary would answer now, but its circuit is still open
+    assert_eq!(provider.complete("Hello").await.unwrap(), "backup");
+}
+
+#[tokio::test]
+async fn test_half_open_trial_reopens_or_closes_the_circuit() {
+    let health = tracker(1, Duration::from_millis(50));
+    let primary = MockProvider::new(Vec::<String>::new())
+        .with_error(server_error())
+        .with_error(server_error())
+        .with_reply("recovered");
+    let provider = chain(primary, MockProvider::always("backup"), &health);
+
+    assert_eq!(provider.complete("Hello").await.unwrap(), "backup");
+    assert_eq!(health.state("primary"), CircuitState::Open);
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(health.state("primary"), CircuitState::HalfOpen);
+    assert_eq!(provider.complete("Hello").await.unwrap(), "backup");
+    assert_eq!(health.state("primary"), CircuitState::Open);
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(provider.complete("Hello").await.unwrap(), "recovered");
+    assert_eq!(health.state("primary"), CircuitState::Closed);
+}
+
+#[tokio::test]
+async fn test_unavailable_once_every_circuit_is_open() {
+    let health = tracker(1, Duration::from_secs(60));
+    let provider = chain(
+        MockProvider::new(Vec::<String>::new()).with_error(server_error()),
+        MockProvider::new(Vec::<String>::new()).with_error(CompletionError::Timeout),
+        &health,
+    );
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert!(matches!(err, CompletionError::Timeout));
+
+    let err = provider.complete("Hello").await.unwrap_err();
+    assert!(matches!(err, CompletionError::Unavailable(_)));
+}
+
+#[test]
+fn test_tasks_fall_back_to_their_command_route() {
+    let config: RegistryConfig = serde_json::from_value(json!({
+        "default": "deepseek",
+        "providers": {"small": {"kind": "ollama", "model": "llama3.2:1b"}},
+        "routes": {"tweet": "small", "doc.insights": "openai"}
+    })).unwrap();
+    let registry = ProviderRegistry::from_config(config);
+    let profile = PersonalityProfile::from_json(r#"{"name": "Nova"}"#).unwrap();
+
+    assert_eq!(registry.name_for_command(TASK_TWEET_TOPIC, &profile), "small");
+    assert_eq!(registry.name_for_command(TASK_DOC_INSIGHTS, &profile), "openai");
+    assert_eq!(registry.name_for_command("doc", &profile), "deepseek");
+    assert_eq!(registry.name_for_command("chat", &profile), "deepseek");
+}
+
+#[tokio::test]
+async fn test_registry_builds_fallback_chains() {
+    let down = MockServer::start(vec![MockResponse::json(503, json!({"error": {"message": "overloaded"}}))]).await;
+    let up = MockServer::start(vec![MockResponse::json(200, json!({
+        "choices": [{"message": {"role": "assistant", "content": "Paris"}}]
+    }))]).await;
+    let config: RegistryConfig = serde_json::from_value(json!({
+        "default": "primary",
+        "providers": {
+            "primary": {"kind": "openai", "base_url": down.base_url, "max_retries": 0, "fallbacks": ["missing", "backup"]},
+            "backup": {"kind": "openai", "base_url": up.base_url, "max_retries": 0}
+        }
+    })).unwrap();
+    let health = tracker(3, Duration::from_secs(60));
+    let registry = ProviderRegistry::from_config(config).with_health(health.clone());
+
+    let provider = registry.build("primary", String::new()).unwrap();
+    assert_eq!(provider.complete("Capital of France?").await.unwrap(), "Paris");
+    assert_eq!(down.requests().await.len(), 1);
+    assert_eq!(health.snapshot().iter().map(|h| h.name.as_str()).collect::<Vec<_>>(), vec!["backup", "primary"]);
+}
+
+#[tokio::test]
+async fn test_model_override_is_not_sent_to_fallbacks() {
+    let down = MockServer::start(vec![MockResponse::json(503, json!({"error": {"message": "overloaded"}}))]).await;
+    let up = MockServer::start(vec![MockResponse::json(200, json!({
+        "message": {"role": "assistant", "content": "Paris"},
+        "done": true
+    }))]).await;
+    let config: RegistryConfig = serde_json::from_value(json!({
+        "default": "primary",
+        "providers": {
+            "primary": {"kind": "openai", "base_url": down.base_url, "max_retries": 0, "fallbacks": ["backup"]},
+            "backup": {"kind": "ollama", "base_url": up.base_url, "model": "llama3", "max_retries": 0}
+        }
+    })).unwrap();
+    let provider = ProviderRegistry::from_config(config).build("primary", String::new()).unwrap();
+
+    let params = GenerationParams { model: Some("gpt-4o".to_string()), ..GenerationParams::default() };
+    let reply = provider.chat(&[ChatMessage::user("Capital of France?")], &params).await;
+    assert_eq!(reply.unwrap().content, "Paris");
+    assert!(down.requests().await[0].contains("\"model\":\"gpt-4o\""));
+    let fallback = &up.requests().await[0];
+    assert!(fallback.contains("\"model\":\"llama3\""), "{}", fallback);
+}
+
+#[test]
+fn test_registry_rejects_replay_recording_from_itself() {
+    let rejects = |providers: serde_json::Value, name: &str, cycle: &str| {
+        let config: RegistryConfig = serde_json::from_value(json!({"default": name, "providers": providers})).unwrap();
+        match ProviderRegistry::from_config(config).build(name, String::new()) {
+            Err(CompletionError::Config(message)) => assert!(message.contains(cycle), "{}", message),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("built a provider that records from itself"),
+        }
+    };
+
+    let direct = json!({
+        "recorder": {"kind": "replay", "record_from": "primary"},
+        "primary": {"kind": "openai", "base_url": "http://localhost:9", "fallbacks": ["recorder"]}
+    });
+    rejects(direct.clone(), "recorder", "recorder -> primary -> recorder");
+    rejects(direct, "primary", "recorder -> primary -> recorder");
+
+    // Through a second replay provider
+    rejects(json!({
+        "first": {"kind": "replay", "record_from": "primary"},
+        "primary": {"kind": "openai", "base_url": "http://localhost:9", "fallbacks": ["second"]},
+        "second": {"kind": "replay", "record_from": "backup"},
+        "backup": {"kind": "openai", "base_url": "http://localhost:9", "fallbacks": ["first"]}
+    }), "first", "first -> primary -> second -> backup -> first");
+}
+
+#[tokio::test]
+async fn test_command_params_override_character_defaults() {
+    let registry = ProviderRegistry::from_config(RegistryConfig::default());
+    let profile = PersonalityProfile::from_json(r#"{
+        "name": "Nova",
+        "provider": "ollama",
+        "generation": {"temperature": 0.9, "top_p": 0.8}
+    }"#).unwrap();
+
+    let chat = registry.params_for_command("chat", &profile);
+    assert_eq!((chat.temperature, chat.top_p), (Some(0.9), Some(0.8)));
+    let insights = registry.params_for_command(TASK_DOC_INSIGHTS, &profile);
+    assert_eq!((insights.temperature, insights.top_p), (Some(0.2), Some(0.8)));
+    assert_eq!(registry.params_for_command(TASK_TWEET_TOPIC, &profile).temperature, Some(1.2));
+
+    let provider = registry.for_character(&profile).unwrap();
+    assert_eq!(provider.params().temperature, Some(0.9));
+    let provider = registry.for_command(TASK_DOC_INSIGHTS, &profile, String::new()).unwrap();
+    assert_eq!(provider.params().temperature, Some(0.2));
+}
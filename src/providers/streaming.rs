@@ -0,0 +1,64 @@
+use crate::completion::{CompletionError, StreamEvent};
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Incremental decoder for a streamed completion body.
+pub trait DeltaParser: Send + 'static {
+    /// Feeds raw bytes and returns every event completed by them.
+    fn feed(&mut self, bytes: &[u8]) -> Result<Vec<StreamEvent>, serde_json::Error>;
+
+    /// Parses whatever is left buffered once the body has ended.
+    fn finish(&mut self) -> Result<Vec<StreamEvent>, serde_json::Error>;
+
+    /// True once the end-of-stream marker has been seen.
+    fn is_done(&self) -> bool;
+}
+
+/// Turns a streamed response body into a stream of deltas and usage reports.
+/// The stream fails with `CompletionError::Timeout` if the body goes quiet
+/// for longer than `idle_timeout`.
+pub fn delta_stream<S, B, E, P>(
+    bytes: S,
+    parser: P,
+    idle_timeout: Duration,
+) -> impl Stream<Item = Result<StreamEvent, CompletionError>>
+where
+    S: Stream<Item = Result<B, E>> + Send + Unpin + 'static,
+    B: AsRef<[u8]>,
+    E: std::error::Error + Send + Sync + 'static,
+    P: DeltaParser,
+{
+    let state = (bytes, parser, VecDeque::new(), false);
+
+    stream::unfold(state, move |(mut bytes, mut parser, mut pending, mut ended)| async move {
+        loop {
+            if let Some(event) = pending.pop_front() {
+                return Some((Ok(event), (bytes, parser, pending, ended)));
+            }
+            if ended || parser.is_done() {
+                return None;
+            }
+
+            let Ok(next) = tokio::time::timeout(idle_timeout, bytes.next()).await else {
+                return Some((Err(CompletionError::Timeout), (bytes, parser, pending, true)));
+            };
+            match next {
+                Some(Ok(chunk)) => match parser.feed(chunk.as_ref()) {
+                    Ok(events) => pending.extend(events),
+                    Err(e) => return Some((Err(e.into()), (bytes, parser, pending, true))),
+                },
+                Some(Err(e)) => {
+                    return Some((Err(CompletionError::Other(Box::new(e))), (bytes, parser, pending, true)))
+                }
+                None => {
+                    ended = true;
+                    match parser.finish() {
+                        Ok(events) => pending.extend(events),
+                        Err(e) => return Some((Err(e.into()), (bytes, parser, pending, ended))),
+                    }
+                }
+            }
+        }
+    })
+}
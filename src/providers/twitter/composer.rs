@@ -1,19 +1,36 @@
 use crate::personality::PersonalityProfile;
 use crate::providers::twitter::twitbrain::Mention;
-use crate::providers::deepseek::deepseek::DeepSeekProvider;
-use crate::completion::CompletionProvider;
+use crate::providers::registry::{ProviderRegistry, TASK_TWEET_TOPIC};
+use crate::completion::{ChatMessage, CompletionProvider};
+use crate::structured::JsonCompletion;
+use crate::usage::{Feature, UsageLedger};
 use anyhow::Result;
+use serde::Deserialize;
 
 const MAX_TWEET_LENGTH: usize = 270;
 const DEFAULT_EMOJI: &str = "💭";
 
+#[derive(Deserialize)]
+struct GeneratedTopic {
+    topic: String,
+}
+
 pub struct TweetComposer;
 
 impl TweetComposer {
-    async fn get_deepseek_provider(profile: &PersonalityProfile) -> Result<DeepSeekProvider> {
-        let api_key = std::env::var("DEEPSEEK_API_KEY")
-            .map_err(|_| anyhow::anyhow!("DEEPSEEK_API_KEY environment variable is not set. Please set it to use AI tweet generation."))?;
-        
+    /// The provider routed to `task` (`tweet` or a sub-task like `tweet.topic`).
+    async fn get_provider(profile: &PersonalityProfile, task: &str) -> Result<Box<dyn CompletionProvider>> {
+        let provider = ProviderRegistry::load_default()
+            .and_then(|registry| registry.for_command(task, profile, Self::system_message(profile)))
+            .map_err(|e| anyhow::anyhow!("Failed to create AI provider: {}", e))?;
+
+        let ledger = UsageLedger::open_default().await
+            .map_err(|e| anyhow::anyhow!("Failed to open usage ledger: {}", e))?;
+        Ok(Box::new(ledger.meter(provider, Feature::Tweet, profile.name.clone())))
+    }
+
+    /// The character's system prompt plus tweet-writing instructions.
+    pub fn system_message(profile: &PersonalityProfile) -> String {
         // Get the base system message from the profile
         let mut system_parts = vec![profile.generate_system_prompt()];
 
@@ -47,22 +64,18 @@ impl TweetComposer {
             }
         }
 
-        let system_message = system_parts.join("\n");
-
-        DeepSeekProvider::new(api_key, system_message)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to create DeepSeek provider: {}", e))
+        system_parts.join("\n")
     }
 
-    // Helper function to count approximate tokens (rough estimation)
-    fn count_tokens(text: &str) -> usize {
-        // Rough approximation: split on whitespace and punctuation
-        text.split(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
-            .filter(|s| !s.is_empty())
-            .count()
+    pub async fn generate_auto_post_topic(profile: &PersonalityProfile) -> Result<String> {
+        let provider = Self::get_provider(profile, TASK_TWEET_TOPIC).await?;
+        Self::generate_auto_post_topic_with(provider.as_ref(), profile).await
     }
 
-    pub async fn generate_auto_post_topic(profile: &PersonalityProfile) -> Result<String> {
+    pub async fn generate_auto_post_topic_with(
+        provider: &dyn CompletionProvider,
+        profile: &PersonalityProfile,
+    ) -> Result<String> {
         let mut prompt_parts = vec![
             format!("You are {}", profile.name),
             format!("Role: {}", profile.get_str("description").unwrap_or_default()),
@@ -130,16 +143,15 @@ impl TweetComposer {
 6. Think of million random your though
 7. Dont make Repeat topics again and again 
 
-Topic:".to_string());
+Respond with a JSON object of the form {\"topic\": \"...\"}".to_string());
 
         let prompt = prompt_parts.join("\n\n");
         
-        let provider = Self::get_deepseek_provider(profile).await?;
-        let topic = provider.complete(&prompt).await?;
+        let generated: GeneratedTopic = provider.complete_json(&[ChatMessage::user(prompt)]).await?;
         
         // Clean up the topic
-        let topic = topic.trim()
-            .trim_start_matches("Topic:")
+        let topic = generated.topic
+            .trim()
             .trim_start_matches("\"")
             .trim_end_matches("\"")
             .trim();
@@ -149,8 +161,27 @@ Topic:".to_string());
 
     #[inline]
     pub async fn generate_auto_tweet(profile: &PersonalityProfile) -> Result<String> {
-        let topic = Self::generate_auto_post_topic(profile).await?;
-        
+        // The topic is a short, cheap task and may be routed to a smaller model
+        let topic_provider = Self::get_provider(profile, TASK_TWEET_TOPIC).await?;
+        let topic = Self::generate_auto_post_topic_with(topic_provider.as_ref(), profile).await?;
+
+        let provider = Self::get_provider(profile, Feature::Tweet.command()).await?;
+        Self::generate_tweet_about_with(provider.as_ref(), profile, &topic).await
+    }
+
+    pub async fn generate_auto_tweet_with(
+        provider: &dyn CompletionProvider,
+        profile: &PersonalityProfile,
+    ) -> Result<String> {
+        let topic = Self::generate_auto_post_topic_with(provider, profile).await?;
+        Self::generate_tweet_about_with(provider, profile, &topic).await
+    }
+
+    pub async fn generate_tweet_about_with(
+        provider: &dyn CompletionProvider,
+        profile: &PersonalityProfile,
+        topic: &str,
+    ) -> Result<String> {
         let mut prompt_parts = vec![
             format!("You are {} - {}", 
                 profile.name,
@@ -176,11 +207,11 @@ Topic:".to_string());
         ));
 
         let prompt = prompt_parts.join("\n\n");
-        let provider = Self::get_deepseek_provider(profile).await?;
         let tweet = provider.complete(&prompt).await?;
         
         Ok(Self::truncate_content(tweet.trim()
             .trim_start_matches("Tweet:")
+            .trim()
             .trim_start_matches("\"")
             .trim_end_matches("\"")
             .trim()
@@ -188,38 +219,62 @@ Topic:".to_string());
     }
 
     pub async fn generate_auto_reply(profile: &PersonalityProfile, original_tweet: &str) -> Result<String> {
-        let deepseek = Self::get_deepseek_provider(profile).await?;
+        let provider = Self::get_provider(profile, Feature::Tweet.command()).await?;
+        Self::generate_auto_reply_with(provider.as_ref(), profile, original_tweet).await
+    }
+
+    pub async fn generate_auto_reply_with(
+        provider: &dyn CompletionProvider,
+        profile: &PersonalityProfile,
+        original_tweet: &str,
+    ) -> Result<String> {
         let prompt = format!(
             "As {}, create a thoughtful reply to this tweet: '{}' \
              Maintain your unique voice while adding value to the conversation.",
             profile.name,
             original_tweet
         );
-        let reply = deepseek.complete(&prompt).await?;
+        let reply = provider.complete(&prompt).await?;
         Ok(Self::truncate_content(reply))
     }
 
     pub async fn generate_dm(profile: &PersonalityProfile, recipient: &str) -> Result<String> {
-        let deepseek = Self::get_deepseek_provider(profile).await?;
+        let provider = Self::get_provider(profile, Feature::Tweet.command()).await?;
+        Self::generate_dm_with(provider.as_ref(), profile, recipient).await
+    }
+
+    pub async fn generate_dm_with(
+        provider: &dyn CompletionProvider,
+        profile: &PersonalityProfile,
+        recipient: &str,
+    ) -> Result<String> {
         let prompt = format!(
             "As {}, write a professional direct message to @{}. \
              Keep it friendly yet professional, reflecting your personality.",
             profile.name,
             recipient
         );
-        let dm = deepseek.complete(&prompt).await?;
+        let dm = provider.complete(&prompt).await?;
         Ok(Self::truncate_content(dm))
     }
 
     pub async fn generate_mention_response(profile: &PersonalityProfile, mention: &Mention) -> Result<String> {
-        let deepseek = Self::get_deepseek_provider(profile).await?;
+        let provider = Self::get_provider(profile, Feature::Tweet.command()).await?;
+        Self::generate_mention_response_with(provider.as_ref(), profile, mention).await
+    }
+
+    pub async fn generate_mention_response_with(
+        provider: &dyn CompletionProvider,
+        profile: &PersonalityProfile,
+        mention: &Mention,
+    ) -> Result<String> {
         let prompt = format!(
             "As {}, respond to this mention: '{}' \
              Keep your response engaging and authentic to your character.",
             profile.name,
             mention.text
         );
-        let response = deepseek.complete(&prompt).await?;
+        let response = provider.complete(&prompt).await?;
         Ok(Self::truncate_content(response))
     }
 
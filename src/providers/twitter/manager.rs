@@ -10,6 +10,7 @@ use std::io::{Write, BufRead, BufReader};
 use crate::personality::PersonalityProfile;
 use crate::providers::twitter::twitbrain::{TwitterProvider, TweetStatus, Mention};
 use crate::providers::twitter::composer::TweetComposer;
+use crate::tokens;
 
 // Constants
 const DEFAULT_EMOJI: &str = "💭";
@@ -74,7 +75,7 @@ impl ConversationManager {
 
     pub async fn handle_command(&mut self, input: &str) -> Result<()> {
         // Show token count for input
-        let token_count = input.split_whitespace().count();
+        let token_count = tokens::count_tokens(input);
         println!("📊 Input tokens: {}", token_count);
 
         match input.trim() {
@@ -1 +1,4 @@
 pub mod twitbrain; pub mod manager; pub mod conversation; pub mod scheduler; pub mod composer;
+
+#[cfg(test)]
+mod tests;
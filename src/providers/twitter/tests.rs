@@ -0,0 +1,49 @@
+use super::composer::TweetComposer;
+use crate::personality::PersonalityProfile;
+use crate::providers::mock::mock::MockProvider;
+
+fn profile() -> PersonalityProfile {
+    PersonalityProfile::from_json(r#"{
+        "name": "Nova",
+        "description": "a curious AI researcher",
+        "expertise": "machine learning",
+        "example_tweets": ["Gradient descent is just hill climbing with commitment issues."]
+    }"#).unwrap()
+}
+
+#[tokio::test]
+async fn test_auto_tweet_uses_generated_topic() {
+    let profile = profile();
+    let provider = MockProvider::new([
+        r#"```json
+{"topic": "Why small models matter"}
+```"#,
+        "Tweet: \"Small models, big ideas.\"",
+    ]);
+
+    let tweet = TweetComposer::generate_auto_tweet_with(&provider, &profile).await.unwrap();
+    assert_eq!(tweet, "Small models, big ideas.");
+
+    let requests = provider.requests();
+    assert_eq!(requests.len(), 2);
+    let tweet_prompt = &requests[1].last().unwrap().content;
+    assert!(tweet_prompt.contains("Write a tweet about this topic : \"Why small models matter\""));
+    assert!(tweet_prompt.contains("machine learning"));
+}
+
+#[tokio::test]
+async fn test_reply_is_truncated_to_tweet_length() {
+    let profile = profile();
+    let provider = MockProvider::always("a".repeat(400));
+
+    let reply = TweetComposer::generate_auto_reply_with(&provider, &profile, "Is AI overhyped?").await.unwrap();
+    assert_eq!(reply.chars().count(), 270);
+    assert!(provider.last_prompt().unwrap().contains("'Is AI overhyped?'"));
+}
+
+#[test]
+fn test_system_message_includes_tweet_style() {
+    let system_message = TweetComposer::system_message(&profile());
+    assert!(system_message.contains("Remember: You are Nova - a curious AI researcher."));
+    assert!(system_message.contains("1. Gradient descent is just hill climbing"));
+}
@@ -4,6 +4,7 @@ use std::error::Error;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+#[derive(Clone)]
 pub struct WebCrawlerManager {
     crawler: Arc<Mutex<WebCrawler>>,
     profile: PersonalityProfile,
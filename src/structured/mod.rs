@@ -0,0 +1,90 @@
+// src/structured/mod.rs
+use serde::de::DeserializeOwned;
+use crate::completion::{ChatMessage, CompletionError, CompletionProvider};
+
+#[cfg(test)]
+mod tests;
+
+/// How many times a reply that does not parse is sent back to the model
+/// with the error before giving up.
+pub const MAX_REPAIR_ATTEMPTS: usize = 2;
+
+/// Typed JSON replies on top of any `CompletionProvider`.
+///
+/// The request goes through `complete_chat_json`, so backends with a JSON
+/// mode constrain the reply to an object; target types should therefore
+/// deserialize from an object rather than a bare array. The reply is cleaned
+/// of code fences and surrounding prose, then validated by deserializing it
+/// into `T`. If that fails, the model is shown its reply and the error and
+/// asked to correct it, up to `MAX_REPAIR_ATTEMPTS` times.
+#[async_trait::async_trait]
+pub trait JsonCompletion {
+    async fn complete_json<T>(&self, messages: &[ChatMessage]) -> Result<T, CompletionError>
+    where
+        T: DeserializeOwned + Send;
+}
+
+#[async_trait::async_trait]
+impl<P: CompletionProvider + ?Sized> JsonCompletion for P {
+    async fn complete_json<T>(&self, messages: &[ChatMessage]) -> Result<T, CompletionError>
+    where
+        T: DeserializeOwned + Send,
+    {
+        let mut conversation = messages.to_vec();
+        let mut attempt = 0;
+        loop {
+            let reply = self.complete_chat_json(&conversation).await?.content;
+            let error = match parse_json::<T>(&reply) {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+
+            if attempt == MAX_REPAIR_ATTEMPTS {
+                return Err(CompletionError::MalformedResponse(format!(
+                    "Reply is not valid JSON for the expected format after {} attempts: {}",
+                    attempt + 1,
+                    error
+                )));
+            }
+            log::warn!("Invalid JSON reply ({}) - asking the model to repair it", error);
+            attempt += 1;
+
+            conversation.push(ChatMessage::assistant(reply));
+            conversation.push(ChatMessage::user(format!(
+                "Your reply could not be parsed: {}. Respond again with only the corrected JSON, \
+                 no code fences or explanations.",
+                error
+            )));
+        }
+    }
+}
+
+/// Deserializes `T` from a model reply, tolerating code fences and prose
+/// around the JSON.
+pub fn parse_json<T: DeserializeOwned>(reply: &str) -> Result<T, serde_json::Error> {
+    serde_json::from_str(extract_json(reply))
+}
+
+/// The JSON value embedded in `reply`: the contents of a ```json fence if
+/// there is one, otherwise the span from the first `{` or `[` to the last
+/// matching closer.
+pub fn extract_json(reply: &str) -> &str {
+    let mut text = reply.trim();
+
+    if let Some(start) = text.find("```") {
+        let fenced = &text[start + 3..];
+        // Skip the language tag on the opening fence line
+        let body = fenced.find('\n').map(|newline| &fenced[newline + 1..]).unwrap_or(fenced);
+        text = body.find("```").map(|end| &body[..end]).unwrap_or(body).trim();
+    }
+
+    let open = match text.find(['{', '[']) {
+        Some(open) => open,
+        None => return text,
+    };
+    let close = if text[open..].starts_with('{') { '}' } else { ']' };
+    match text.rfind(close) {
+        Some(end) if end > open => &text[open..=end],
+        _ => &text[open..],
+    }
+}
@@ -0,0 +1,58 @@
+use super::*;
+use crate::completion::Role;
+use crate::providers::mock::mock::MockProvider;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Answer {
+    city: String,
+    population: u32,
+}
+
+#[test]
+fn test_extract_json_strips_fences_and_prose() {
+    assert_eq!(extract_json("```json\n{\"a\": 1}\n```"), "{\"a\": 1}");
+    assert_eq!(extract_json("```\n[1, 2]\n```"), "[1, 2]");
+    assert_eq!(extract_json("Sure! Here it is: {\"a\": {\"b\": 2}} Hope this helps."), "{\"a\": {\"b\": 2}}");
+    assert_eq!(extract_json("no json here"), "no json here");
+}
+
+#[test]
+fn test_parse_json_validates_against_the_type() {
+    let answer: Answer = parse_json("```json\n{\"city\": \"Rome\", \"population\": 2800000}\n```").unwrap();
+    assert_eq!(answer, Answer { city: "Rome".to_string(), population: 2800000 });
+
+    let err = parse_json::<Answer>("{\"city\": \"Rome\"}").unwrap_err();
+    assert!(err.to_string().contains("missing field `population`"));
+}
+
+#[tokio::test]
+async fn test_complete_json_reprompts_with_the_parse_error() {
+    let provider = MockProvider::new([
+        r#"{"city": "Rome"}"#,
+        r#"{"city": "Rome", "population": 2800000}"#,
+    ]);
+
+    let answer: Answer = provider.complete_json(&[ChatMessage::user("Largest city in Italy?")]).await.unwrap();
+    assert_eq!(answer.population, 2800000);
+
+    let retry = provider.requests().pop().unwrap();
+    let [.., question, reply, repair] = retry.as_slice() else { panic!("repair turns missing") };
+    assert_eq!(question, &ChatMessage::user("Largest city in Italy?"));
+    assert_eq!(reply, &ChatMessage::assistant(r#"{"city": "Rome"}"#));
+    assert_eq!(repair.role, Role::User);
+    assert!(repair.content.contains("missing field `population`"));
+}
+
+#[tokio::test]
+async fn test_complete_json_gives_up_after_repair_attempts() {
+    let provider = MockProvider::always("I cannot answer that.");
+
+    let err = provider
+        .complete_json::<Answer>(&[ChatMessage::user("Largest city in Italy?")])
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, CompletionError::MalformedResponse(_)));
+    assert_eq!(provider.requests().len(), MAX_REPAIR_ATTEMPTS + 1);
+}
@@ -0,0 +1,80 @@
+// src/text/mod.rs
+
+#[cfg(test)]
+mod tests;
+
+/// Words too common to say anything about what a text is about.
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "all", "also", "an", "and", "any", "are", "as", "at", "be", "been", "but", "by", "can", "could",
+    "did", "do", "does", "for", "from", "had", "has", "have", "he", "her", "his", "how", "i", "if", "in", "into",
+    "is", "it", "its", "me", "more", "my", "no", "not", "of", "on", "or", "our", "she", "should", "so", "some",
+    "than", "that", "the", "their", "them", "then", "there", "these", "they", "this", "those", "to", "too", "was",
+    "we", "were", "what", "when", "where", "which", "who", "why", "will", "with", "would", "you", "your",
+];
+
+/// The words of `text` that say what it is about: lower-cased, without
+/// stop words or single letters.
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.chars().count() > 1 && !STOP_WORDS.contains(&word.as_str()))
+}
+
+/// The terms of `text` for keyword search: its `words`, stemmed.
+pub fn tokenize(text: &str) -> Vec<String> {
+    words(text).map(|word| stem(&word)).collect()
+}
+
+/// Strips common English inflections so that e.g. "indexes", "indexed" and
+/// "indexing" share a term. Only consistency between documents and queries
+/// matters, not getting the linguistic root right.
+pub fn stem(word: &str) -> String {
+    if !word.is_ascii() || word.len() <= 3 {
+        return word.to_string();
+    }
+    // "schedule", "schedules" and "scheduled" all become "schedul"
+    let stem = strip_suffix(word);
+    match stem.strip_suffix('e') {
+        Some(root) if root.len() >= 3 => root.to_string(),
+        _ => stem,
+    }
+}
+
+fn strip_suffix(word: &str) -> String {
+    if let Some(stem) = word.strip_suffix("ies").filter(|s| s.len() >= 2) {
+        return format!("{}y", stem);
+    }
+    if let Some(stem) = word.strip_suffix("sses") {
+        return format!("{}ss", stem);
+    }
+    for suffix in ["ing", "ed"] {
+        if let Some(stem) = word.strip_suffix(suffix).filter(|s| s.len() >= 3 && s.chars().any(is_vowel)) {
+            return undouble(stem);
+        }
+    }
+    if let Some(stem) = word.strip_suffix("ly").filter(|s| s.len() >= 3) {
+        return stem.to_string();
+    }
+    if let Some(stem) = word.strip_suffix("es").filter(|s| ["s", "x", "z", "ch", "sh"].iter().any(|end| s.ends_with(end))) {
+        return stem.to_string();
+    }
+    if word.ends_with('s') && !["ss", "us", "is"].iter().any(|end| word.ends_with(end)) {
+        return word[..word.len() - 1].to_string();
+    }
+    word.to_string()
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y')
+}
+
+/// "runn" to "run", leaving the doubled letters English keeps ("fall").
+fn undouble(stem: &str) -> String {
+    let bytes = stem.as_bytes();
+    let n = bytes.len();
+    if n >= 2 && bytes[n - 1] == bytes[n - 2] && !matches!(bytes[n - 1], b'l' | b's' | b'z') && !is_vowel(bytes[n - 1] as char) {
+        stem[..n - 1].to_string()
+    } else {
+        stem.to_string()
+    }
+}
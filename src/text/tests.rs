@@ -0,0 +1,12 @@
+use super::*;
+
+#[test]
+fn test_tokenize_drops_stopwords_and_stems() {
+    assert_eq!(tokenize("What is the indexing of the Libraries?"), vec!["index", "library"]);
+    assert_eq!(stem("indexes"), "index");
+    assert_eq!(stem("indexed"), "index");
+    assert_eq!(stem("running"), "run");
+    assert_eq!(stem("falling"), "fall");
+    assert_eq!(stem("class"), "class");
+    assert_eq!(stem("scheduled"), stem("schedules"));
+}
@@ -0,0 +1,175 @@
+// src/tokens/mod.rs
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+use crate::completion::{with_system_message, ChatMessage, CompletionProvider, Role};
+
+#[cfg(test)]
+mod tests;
+
+/// Context window assumed for models missing from `CONTEXT_WINDOWS`.
+pub const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+/// Output tokens reserved when a provider does not set `max_tokens`.
+pub const DEFAULT_RESERVED_OUTPUT: usize = 1_024;
+
+/// Tokens each chat message costs on top of its content (role and separators).
+const TOKENS_PER_MESSAGE: usize = 4;
+
+/// Tokens that prime the assistant's reply.
+const REPLY_PRIMING_TOKENS: usize = 3;
+
+/// Context windows by model-name prefix. The longest matching prefix wins.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("deepseek-chat", 65_536),
+    ("deepseek-reasoner", 65_536),
+    ("deepseek-coder", 16_384),
+    ("deepseek", 65_536),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("llama3.1", 131_072),
+    ("llama3.2", 131_072),
+    ("llama3.3", 131_072),
+    ("llama3", 8_192),
+    ("llama2", 4_096),
+    ("mistral", 32_768),
+    ("mixtral", 32_768),
+    ("qwen2.5", 32_768),
+    ("gemma2", 8_192),
+    ("phi3", 4_096),
+];
+
+/// The shared BPE encoder. DeepSeek, Llama and friends each have their own
+/// vocabulary, but cl100k is close enough for budgeting and reporting.
+fn bpe() -> &'static CoreBPE {
+    static BPE: OnceLock<CoreBPE> = OnceLock::new();
+    BPE.get_or_init(|| tiktoken_rs::cl100k_base().expect("embedded cl100k_base vocabulary"))
+}
+
+/// Number of tokens in `text`.
+pub fn count_tokens(text: &str) -> usize {
+    bpe().encode_with_special_tokens(text).len()
+}
+
+/// Prompt tokens for a whole conversation, including per-message overhead.
+pub fn count_message_tokens(messages: &[ChatMessage]) -> usize {
+    messages
+        .iter()
+        .map(|message| TOKENS_PER_MESSAGE + count_tokens(&message.content))
+        .sum::<usize>()
+        + REPLY_PRIMING_TOKENS
+}
+
+/// The first `max_tokens` tokens of `text`.
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    let tokens = bpe().encode_with_special_tokens(text);
+    if tokens.len() <= max_tokens {
+        return text.to_string();
+    }
+    bpe().decode(tokens[..max_tokens].to_vec()).unwrap_or_else(|_| {
+        // A cut through a multi-byte character cannot be decoded; fall back to characters
+        text.chars().take(max_tokens * 3).collect()
+    })
+}
+
+/// Context window of `model`, matched by prefix against a built-in table.
+pub fn context_window(model: &str) -> usize {
+    let model = model.to_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, window)| *window)
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+}
+
+/// Prompt-token allowance for one request: the model's context window minus
+/// the tokens reserved for the reply.
+///
+/// Everything that grows without bound (chat history, knowledge-base
+/// snippets, document text) goes through a budget before it is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextBudget {
+    prompt_tokens: usize,
+}
+
+impl ContextBudget {
+    pub fn new(context_window: usize, reserved_output: usize) -> Self {
+        Self {
+            prompt_tokens: context_window.saturating_sub(reserved_output),
+        }
+    }
+
+    pub fn for_provider(provider: &dyn CompletionProvider) -> Self {
+        let reserved = provider.max_tokens().map(|t| t as usize).unwrap_or(DEFAULT_RESERVED_OUTPUT);
+        Self::new(provider.context_window(), reserved)
+    }
+
+    pub fn prompt_tokens(&self) -> usize {
+        self.prompt_tokens
+    }
+
+    /// Tokens left after `used` tokens of fixed prompt.
+    pub fn remaining(&self, used: usize) -> usize {
+        self.prompt_tokens.saturating_sub(used)
+    }
+
+    /// Drops the oldest turns after any leading system message until the
+    /// conversation fits. The system message and the final turn are always
+    /// kept; if they alone are too long, the final turn is truncated.
+    pub fn fit_messages(&self, mut messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+        let first_droppable = usize::from(messages.first().map(|m| m.role) == Some(Role::System));
+
+        while count_message_tokens(&messages) > self.prompt_tokens && messages.len() > first_droppable + 1 {
+            messages.remove(first_droppable);
+        }
+
+        let overflow = count_message_tokens(&messages).saturating_sub(self.prompt_tokens);
+        if overflow > 0 {
+            if let Some(last) = messages.last_mut() {
+                let keep = count_tokens(&last.content).saturating_sub(overflow);
+                last.content = truncate_to_tokens(&last.content, keep);
+            }
+        }
+        messages
+    }
+
+    /// Truncates `text` to what is left after `used` tokens of surrounding prompt.
+    pub fn fit_text(&self, text: &str, used: usize) -> String {
+        truncate_to_tokens(text, self.remaining(used))
+    }
+
+    /// Keeps snippets, in priority order, while they fit in what is left after
+    /// `used` tokens of surrounding prompt.
+    pub fn fit_snippets(&self, snippets: Vec<String>, used: usize) -> Vec<String> {
+        let mut remaining = self.remaining(used);
+        snippets
+            .into_iter()
+            .take_while(|snippet| {
+                let cost = count_tokens(snippet);
+                let fits = cost <= remaining;
+                remaining = remaining.saturating_sub(cost);
+                fits
+            })
+            .collect()
+    }
+}
+
+/// Adds the provider's system prompt if missing and trims the conversation to
+/// the provider's context budget.
+pub fn fit_to_context(provider: &dyn CompletionProvider, messages: &[ChatMessage]) -> Vec<ChatMessage> {
+    ContextBudget::for_provider(provider).fit_messages(with_system_message(provider.system_message(), messages))
+}
+
+/// Truncates `text` so that a single-prompt request made of `surrounding` plus
+/// `text` fits the provider's context budget.
+pub fn fit_text_for(provider: &dyn CompletionProvider, surrounding: &str, text: &str) -> String {
+    let used = count_message_tokens(&with_system_message(provider.system_message(), &[ChatMessage::user(surrounding)]));
+    ContextBudget::for_provider(provider).fit_text(text, used)
+}
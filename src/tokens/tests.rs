@@ -0,0 +1,84 @@
+use super::*;
+use crate::completion::ChatMessage;
+use crate::providers::mock::mock::MockProvider;
+
+#[test]
+fn test_count_tokens_uses_bpe() {
+    assert_eq!(count_tokens(""), 0);
+    assert_eq!(count_tokens("hello world"), 2);
+    // Whitespace splitting would say 1
+    assert!(count_tokens("supercalifragilisticexpialidocious") > 1);
+    assert_eq!(
+        count_message_tokens(&[ChatMessage::user("hello world")]),
+        TOKENS_PER_MESSAGE + 2 + REPLY_PRIMING_TOKENS
+    );
+}
+
+#[test]
+fn test_truncate_to_tokens() {
+    let text = "one two three four five six";
+    assert_eq!(truncate_to_tokens(text, 100), text);
+    assert_eq!(truncate_to_tokens(text, 3), "one two three");
+    assert_eq!(truncate_to_tokens(text, 0), "");
+}
+
+#[test]
+fn test_context_window_table() {
+    assert_eq!(context_window("deepseek-chat"), 65_536);
+    assert_eq!(context_window("gpt-4o-mini"), 128_000);
+    assert_eq!(context_window("gpt-4"), 8_192);
+    assert_eq!(context_window("llama3.1:8b"), 131_072);
+    assert_eq!(context_window("meta-llama/Llama3.1-70B"), 131_072);
+    assert_eq!(context_window("something-unknown"), DEFAULT_CONTEXT_WINDOW);
+}
+
+#[test]
+fn test_fit_messages_drops_oldest_turns_first() {
+    let long_turn = "word ".repeat(40);
+    let messages = vec![
+        ChatMessage::system("You are terse."),
+        ChatMessage::user(long_turn.clone()),
+        ChatMessage::assistant(long_turn.clone()),
+        ChatMessage::user("latest question"),
+    ];
+    let budget = ContextBudget::new(110, 40);
+
+    let fitted = budget.fit_messages(messages.clone());
+    assert!(count_message_tokens(&fitted) <= budget.prompt_tokens());
+    assert_eq!(fitted.first(), messages.first());
+    assert_eq!(fitted.last(), messages.last());
+    assert_eq!(fitted.len(), 3);
+
+    // Plenty of room: nothing changes
+    assert_eq!(ContextBudget::new(10_000, 0).fit_messages(messages.clone()), messages);
+}
+
+#[test]
+fn test_fit_messages_truncates_an_oversized_final_turn() {
+    let budget = ContextBudget::new(50, 0);
+    let fitted = budget.fit_messages(vec![ChatMessage::system("sys"), ChatMessage::user("word ".repeat(200))]);
+
+    assert_eq!(fitted.len(), 2);
+    assert!(count_message_tokens(&fitted) <= 50);
+}
+
+#[test]
+fn test_fit_snippets_keeps_priority_order() {
+    let budget = ContextBudget::new(30, 0);
+    let snippets = vec!["a b c d e".to_string(), "f g h i j".to_string(), "k l m n o".to_string()];
+
+    assert_eq!(budget.fit_snippets(snippets.clone(), 16), snippets[..2].to_vec());
+    assert!(budget.fit_snippets(snippets, 30).is_empty());
+}
+
+#[tokio::test]
+async fn test_document_text_is_trimmed_to_the_provider_budget() {
+    let provider = MockProvider::always("ok").with_model("gpt-4").with_system_message("sys");
+    let text = "lorem ipsum ".repeat(10_000);
+
+    let fitted = fit_text_for(&provider, "Summarize:\n\n", &text);
+    let budget = ContextBudget::for_provider(&provider);
+    let request = with_system_message("sys", &[ChatMessage::user(format!("Summarize:\n\n{}", fitted))]);
+    assert!(count_message_tokens(&request) <= budget.prompt_tokens());
+    assert!(count_tokens(&fitted) > budget.prompt_tokens() - 50);
+}
@@ -0,0 +1,59 @@
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+use super::{string_argument, Tool, ToolError};
+use crate::completion::CompletionProvider;
+use crate::providers::document::DocumentProcessor;
+
+/// Extracts key insights from a local document.
+pub struct AnalyzeDocumentTool {
+    provider: Arc<dyn CompletionProvider>,
+}
+
+impl AnalyzeDocumentTool {
+    pub fn new(provider: Arc<dyn CompletionProvider>) -> Self {
+        Self { provider }
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for AnalyzeDocumentTool {
+    fn name(&self) -> &str {
+        "analyze_document"
+    }
+
+    fn description(&self) -> &str {
+        "Extract the key insights from a local document (PDF, Word, Excel, text or image)."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "Path of the file on the local machine"}
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(&self, arguments: Value) -> Result<String, ToolError> {
+        let path = string_argument(&arguments, "path")?;
+        if !Path::new(path).exists() {
+            return Err(ToolError::InvalidArguments(format!("file not found: {}", path)));
+        }
+
+        let mut processor = DocumentProcessor::new(self.provider.clone())
+            .map_err(|e| ToolError::Failed(e.to_string()))?;
+        let insights = processor.process_document(path).await
+            .map_err(|e| ToolError::Failed(e.to_string()))?;
+
+        Ok(format!(
+            "Insights from {}:\n{}",
+            path,
+            insights.iter()
+                .map(|i| format!("• {} (relevance {:.2})", i.text, i.relevance))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))
+    }
+}
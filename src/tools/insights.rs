@@ -0,0 +1,52 @@
+use serde_json::{json, Value};
+use super::{string_argument, Tool, ToolError};
+use crate::database::Database;
+
+const MAX_RESULTS: usize = 10;
+
+/// Searches insights saved from previously analyzed documents.
+pub struct SearchInsightsTool {
+    db: Database,
+}
+
+impl SearchInsightsTool {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for SearchInsightsTool {
+    fn name(&self) -> &str {
+        "search_insights"
+    }
+
+    fn description(&self) -> &str {
+        "Search the insights saved from previously analyzed documents."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "Text the insight should contain"}
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn execute(&self, arguments: Value) -> Result<String, ToolError> {
+        let query = string_argument(&arguments, "query")?;
+        let insights = self.db.search_document_insights(query).await
+            .map_err(|e| ToolError::Failed(e.to_string()))?;
+
+        if insights.is_empty() {
+            return Ok(format!("No saved insights match \"{}\".", query));
+        }
+        Ok(insights.iter()
+            .take(MAX_RESULTS)
+            .map(|(path, text, relevance)| format!("• [{}] {} (relevance {:.2})", path, text, relevance))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
@@ -0,0 +1,200 @@
+// src/tools/mod.rs
+pub mod web;
+pub mod document;
+pub mod insights;
+
+#[cfg(test)]
+mod tests;
+
+pub use web::FetchUrlTool;
+pub use document::AnalyzeDocumentTool;
+pub use insights::SearchInsightsTool;
+
+use futures::StreamExt;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use crate::completion::{
+    ChatMessage, Completion, CompletionError, CompletionProvider, StreamEvent, ToolCall, ToolDefinition, Usage,
+};
+use crate::tokens;
+
+/// Rounds of tool calls allowed per request; the last round offers no tools,
+/// so the model has to answer with what it has.
+pub const MAX_TOOL_ROUNDS: usize = 5;
+
+/// Tool output is cut to this many tokens before it is sent back.
+pub const MAX_TOOL_OUTPUT_TOKENS: usize = 2_000;
+
+#[derive(Debug)]
+pub enum ToolError {
+    UnknownTool(String),
+    InvalidArguments(String),
+    Failed(String),
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolError::UnknownTool(name) => write!(f, "Unknown tool: {}", name),
+            ToolError::InvalidArguments(msg) => write!(f, "Invalid arguments: {}", msg),
+            ToolError::Failed(msg) => write!(f, "Tool failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+/// An action the model can ask the agent to take during a chat.
+#[async_trait::async_trait]
+pub trait Tool: Send + Sync {
+    /// Name the model calls the tool by.
+    fn name(&self) -> &str;
+
+    /// What the tool does, shown to the model.
+    fn description(&self) -> &str;
+
+    /// JSON schema of the arguments object.
+    fn parameters(&self) -> Value;
+
+    /// Runs the tool and returns its output as text for the model.
+    async fn execute(&self, arguments: Value) -> Result<String, ToolError>;
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: self.parameters(),
+        }
+    }
+}
+
+/// Reads a required string argument.
+pub fn string_argument<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, ToolError> {
+    arguments
+        .get(name)
+        .and_then(Value::as_str)
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| ToolError::InvalidArguments(format!("missing string argument `{}`", name)))
+}
+
+/// The final reply of a tool-assisted chat.
+#[derive(Debug, Clone)]
+pub struct ToolRun {
+    pub completion: Completion,
+    /// Tokens used across every round, not just the final one.
+    pub usage: Usage,
+}
+
+/// The tools available to the chat agent.
+#[derive(Default, Clone)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `tool`, replacing any tool of the same name.
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.retain(|existing| existing.name() != tool.name());
+        self.tools.push(tool);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool>> {
+        self.tools.iter().find(|tool| tool.name() == name)
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|tool| tool.definition()).collect()
+    }
+
+    /// Runs one requested call. Failures are reported to the model as the
+    /// call's output rather than aborting the chat.
+    pub async fn execute(&self, call: &ToolCall) -> String {
+        let result = match self.get(&call.function.name) {
+            Some(tool) => match serde_json::from_str::<Value>(&call.function.arguments) {
+                Ok(arguments) => tool.execute(arguments).await,
+                Err(e) => Err(ToolError::InvalidArguments(e.to_string())),
+            },
+            None => Err(ToolError::UnknownTool(call.function.name.clone())),
+        };
+
+        match result {
+            Ok(output) => tokens::truncate_to_tokens(&output, MAX_TOOL_OUTPUT_TOKENS),
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+
+    /// Sends `messages` with the tool definitions, executes whatever the model
+    /// asks for and feeds the results back until it gives a final answer.
+    /// Replies are streamed: `on_delta` receives their text as it arrives and
+    /// `on_call` is invoked before each call is executed.
+    pub async fn run<D, F>(
+        &self,
+        provider: &dyn CompletionProvider,
+        messages: &[ChatMessage],
+        mut on_delta: D,
+        mut on_call: F,
+    ) -> Result<ToolRun, CompletionError>
+    where
+        D: FnMut(&str),
+        F: FnMut(&ToolCall),
+    {
+        let definitions = self.definitions();
+        let mut conversation = messages.to_vec();
+        let mut usage = Usage::default();
+
+        for round in 1..=MAX_TOOL_ROUNDS {
+            let tools = if round < MAX_TOOL_ROUNDS { definitions.as_slice() } else { &[] };
+            let completion = Self::stream_round(provider, &conversation, tools, &mut on_delta).await?;
+            usage.prompt_tokens += completion.usage.prompt_tokens;
+            usage.completion_tokens += completion.usage.completion_tokens;
+
+            if completion.tool_calls.is_empty() {
+                return Ok(ToolRun { completion, usage });
+            }
+
+            conversation.push(ChatMessage::from_completion(&completion));
+            for call in &completion.tool_calls {
+                on_call(call);
+                let output = self.execute(call).await;
+                conversation.push(ChatMessage::tool_result(call.id.clone(), output));
+            }
+        }
+
+        Err(CompletionError::MalformedResponse(format!(
+            "Model kept requesting tools after {} rounds",
+            MAX_TOOL_ROUNDS
+        )))
+    }
+
+    /// One streamed reply, put back together once it has finished.
+    async fn stream_round(
+        provider: &dyn CompletionProvider,
+        conversation: &[ChatMessage],
+        tools: &[ToolDefinition],
+        on_delta: &mut impl FnMut(&str),
+    ) -> Result<Completion, CompletionError> {
+        let mut stream = provider.complete_with_tools_stream(conversation, tools).await?;
+        let mut content = String::new();
+        let mut usage = None;
+        let mut tool_calls = Vec::new();
+
+        while let Some(event) = stream.next().await {
+            match event? {
+                StreamEvent::Delta(text) => {
+                    on_delta(&text);
+                    content.push_str(&text);
+                }
+                StreamEvent::Usage(reported) => usage = Some(reported),
+                StreamEvent::ToolCalls(calls) => tool_calls.extend(calls),
+            }
+        }
+
+        let usage = usage.unwrap_or_else(|| Usage::estimate(conversation, &content));
+        Ok(Completion { tool_calls, ..Completion::new(content, usage) })
+    }
+}
@@ -0,0 +1,118 @@
+use super::*;
+use crate::completion::Role;
+use crate::database::Database;
+use crate::providers::mock::mock::MockProvider;
+use serde_json::json;
+
+/// Upper-cases its `text` argument.
+struct ShoutTool;
+
+#[async_trait::async_trait]
+impl Tool for ShoutTool {
+    fn name(&self) -> &str {
+        "shout"
+    }
+
+    fn description(&self) -> &str {
+        "Upper-case some text."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({"type": "object", "properties": {"text": {"type": "string"}}, "required": ["text"]})
+    }
+
+    async fn execute(&self, arguments: Value) -> Result<String, ToolError> {
+        Ok(string_argument(&arguments, "text")?.to_uppercase())
+    }
+}
+
+fn registry() -> ToolRegistry {
+    let mut tools = ToolRegistry::new();
+    tools.register(Arc::new(ShoutTool));
+    tools
+}
+
+#[tokio::test]
+async fn test_run_feeds_tool_results_back_until_final_answer() {
+    let provider = MockProvider::new(Vec::<String>::new())
+        .with_tool_call("shout", json!({"text": "hello"}))
+        .with_reply("The tool said HELLO.");
+    let mut seen = Vec::new();
+
+    let run = registry()
+        .run(&provider, &[ChatMessage::user("Shout hello")], |_| {}, |call| seen.push(call.function.name.clone()))
+        .await
+        .unwrap();
+
+    assert_eq!(run.completion.content, "The tool said HELLO.");
+    assert_eq!(seen, vec!["shout"]);
+    assert!(run.usage.prompt_tokens > 0);
+
+    let last = provider.requests().pop().unwrap();
+    let [.., request, result] = last.as_slice() else { panic!("tool turns missing") };
+    assert_eq!(request.role, Role::Assistant);
+    assert_eq!(request.tool_calls[0].function.name, "shout");
+    assert_eq!(result, &ChatMessage::tool_result("call_1", "HELLO"));
+}
+
+#[tokio::test]
+async fn test_tool_errors_are_reported_to_the_model() {
+    let tools = registry();
+
+    let unknown = ToolCall::new("call_1", "delete_everything", &json!({}));
+    assert_eq!(tools.execute(&unknown).await, "Error: Unknown tool: delete_everything");
+
+    let missing = ToolCall::new("call_2", "shout", &json!({"txt": "hi"}));
+    assert_eq!(tools.execute(&missing).await, "Error: Invalid arguments: missing string argument `text`");
+}
+
+#[tokio::test]
+async fn test_run_gives_up_on_endless_tool_calls() {
+    let mut provider = MockProvider::new(Vec::<String>::new());
+    for _ in 0..MAX_TOOL_ROUNDS {
+        provider = provider.with_tool_call("shout", json!({"text": "again"}));
+    }
+
+    let err = registry().run(&provider, &[ChatMessage::user("Loop")], |_| {}, |_| {}).await.unwrap_err();
+    assert!(matches!(err, CompletionError::MalformedResponse(_)));
+    assert_eq!(provider.requests().len(), MAX_TOOL_ROUNDS);
+}
+
+#[tokio::test]
+async fn test_run_streams_the_final_answer() {
+    let provider = MockProvider::new(Vec::<String>::new())
+        .with_tool_call("shout", json!({"text": "hello"}))
+        .with_reply("The tool said HELLO.");
+    let mut deltas = Vec::new();
+
+    let run = registry()
+        .run(&provider, &[ChatMessage::user("Shout hello")], |text| deltas.push(text.to_string()), |_| {})
+        .await
+        .unwrap();
+
+    assert_eq!(deltas, vec!["The ", "tool ", "said ", "HELLO."]);
+    assert_eq!(run.completion.content, "The tool said HELLO.");
+}
+
+#[tokio::test]
+async fn test_search_insights_tool_reads_saved_insights() {
+    let db = Database::new(":memory:").await.unwrap();
+    db.save_document_insight("notes.md".to_string(), "Launch is in March".to_string(), 0.9, "document".to_string())
+        .await
+        .unwrap();
+    let tool = SearchInsightsTool::new(db);
+
+    let found = tool.execute(json!({"query": "launch"})).await.unwrap();
+    assert_eq!(found, "• [notes.md] Launch is in March (relevance 0.90)");
+
+    let none = tool.execute(json!({"query": "budget"})).await.unwrap();
+    assert_eq!(none, "No saved insights match \"budget\".");
+}
+
+#[test]
+fn test_definitions_use_openai_tool_format() {
+    let definition = registry().definitions().pop().unwrap().to_openai();
+    assert_eq!(definition["type"], "function");
+    assert_eq!(definition["function"]["name"], "shout");
+    assert_eq!(definition["function"]["parameters"]["required"], json!(["text"]));
+}
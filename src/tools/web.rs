@@ -0,0 +1,42 @@
+use serde_json::{json, Value};
+use super::{string_argument, Tool, ToolError};
+use crate::providers::web_crawler::crawler_manager::WebCrawlerManager;
+
+/// Fetches a web page and returns its text.
+pub struct FetchUrlTool {
+    crawler: WebCrawlerManager,
+}
+
+impl FetchUrlTool {
+    pub fn new(crawler: WebCrawlerManager) -> Self {
+        Self { crawler }
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for FetchUrlTool {
+    fn name(&self) -> &str {
+        "fetch_url"
+    }
+
+    fn description(&self) -> &str {
+        "Fetch a web page and return its text content."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string", "description": "Absolute http(s) URL of the page"}
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, arguments: Value) -> Result<String, ToolError> {
+        let url = string_argument(&arguments, "url")?;
+        let content = self.crawler.analyze_url(url).await
+            .map_err(|e| ToolError::Failed(format!("Failed to fetch {}: {}", url, e)))?;
+        Ok(format!("Content of {}:\n{}", url, content))
+    }
+}
@@ -0,0 +1,369 @@
+// src/usage/mod.rs
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::completion::{
+    with_system_message, ChatMessage, Completion, CompletionError, CompletionProvider, CompletionStream,
+    GenerationParams, StreamEvent, ToolDefinition, Usage,
+};
+use crate::database::{Database, DatabaseError, UsageEntry, UsageGroup, UsageTotals, DEFAULT_DB_PATH};
+
+#[cfg(test)]
+mod tests;
+
+/// What a completion was requested for, recorded with every ledger entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    Chat,
+    Doc,
+    Web,
+    Research,
+    Tweet,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [Feature::Chat, Feature::Doc, Feature::Web, Feature::Research, Feature::Tweet];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::Chat => "chat",
+            Feature::Doc => "doc",
+            Feature::Web => "web",
+            Feature::Research => "research",
+            Feature::Tweet => "tweet",
+        }
+    }
+
+    /// The registry command this feature's provider is routed by.
+    pub fn command(&self) -> &'static str {
+        match self {
+            Feature::Research => "web",
+            other => other.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// USD per million (input, output) tokens, matched by model-name prefix.
+/// Models not listed (local Ollama models, for instance) are free.
+const PRICING: &[(&str, f64, f64)] = &[
+    ("deepseek-chat", 0.27, 1.10),
+    ("deepseek-reasoner", 0.55, 2.19),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4-turbo", 10.00, 30.00),
+    ("gpt-4", 30.00, 60.00),
+    ("gpt-3.5-turbo", 0.50, 1.50),
+];
+
+/// Cost in USD of `usage` on `model`.
+pub fn cost_usd(model: &str, usage: &Usage) -> f64 {
+    let model = model.to_lowercase();
+    PRICING
+        .iter()
+        .filter(|(prefix, _, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _, _)| prefix.len())
+        .map(|(_, input, output)| {
+            (usage.prompt_tokens as f64 * input + usage.completion_tokens as f64 * output) / 1_000_000.0
+        })
+        .unwrap_or(0.0)
+}
+
+/// Daily spending limits in USD. Once today's ledger reaches a limit, further
+/// calls are refused with `CompletionError::BudgetExceeded`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BudgetLimits {
+    pub daily_usd: Option<f64>,
+    pub per_feature_usd: HashMap<Feature, f64>,
+}
+
+impl BudgetLimits {
+    /// Reads `DAILY_BUDGET_USD` and per-feature `DAILY_BUDGET_<FEATURE>_USD`
+    /// (e.g. `DAILY_BUDGET_TWEET_USD`).
+    pub fn from_env() -> Self {
+        let var = |name: String| env::var(name).ok().and_then(|v| v.parse::<f64>().ok());
+
+        let limits = match var("DAILY_BUDGET_USD".to_string()) {
+            Some(limit) => Self::default().with_daily(limit),
+            None => Self::default(),
+        };
+        Feature::ALL.iter().fold(limits, |limits, feature| {
+            match var(format!("DAILY_BUDGET_{}_USD", feature.as_str().to_uppercase())) {
+                Some(limit) => limits.with_feature(*feature, limit),
+                None => limits,
+            }
+        })
+    }
+
+    pub fn with_daily(mut self, limit_usd: f64) -> Self {
+        self.daily_usd = Some(limit_usd);
+        self
+    }
+
+    pub fn with_feature(mut self, feature: Feature, limit_usd: f64) -> Self {
+        self.per_feature_usd.insert(feature, limit_usd);
+        self
+    }
+}
+
+/// Spend over a reporting window, broken down three ways.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub days: u32,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+    pub spent_today_usd: f64,
+    pub daily_budget_usd: Option<f64>,
+    pub by_day: Vec<UsageTotals>,
+    pub by_character: Vec<UsageTotals>,
+    pub by_feature: Vec<UsageTotals>,
+}
+
+/// Records completion usage in the database and enforces daily budgets.
+#[derive(Clone)]
+pub struct UsageLedger {
+    db: Database,
+    limits: BudgetLimits,
+}
+
+impl UsageLedger {
+    pub fn new(db: Database, limits: BudgetLimits) -> Self {
+        Self { db, limits }
+    }
+
+    /// The ledger in `data/agent.db`, with limits from the environment.
+    pub async fn open_default() -> Result<Self, DatabaseError> {
+        Ok(Self::new(Database::new(DEFAULT_DB_PATH).await?, BudgetLimits::from_env()))
+    }
+
+    /// Wraps `provider` so every call made through it is checked and recorded.
+    pub fn meter(
+        &self,
+        provider: Arc<dyn CompletionProvider>,
+        feature: Feature,
+        character: impl Into<String>,
+    ) -> MeteredProvider {
+        MeteredProvider {
+            inner: provider,
+            ledger: self.clone(),
+            feature,
+            character: character.into(),
+        }
+    }
+
+    /// Fails if today's spend has reached the overall or the feature's limit.
+    pub async fn check_budget(&self, feature: Feature) -> Result<(), CompletionError> {
+        let spent = |feature: Option<Feature>| async move {
+            self.db
+                .spend_today(feature.map(|f| f.as_str().to_string()))
+                .await
+                .map_err(|e| CompletionError::Other(Box::new(e)))
+        };
+
+        if let Some(limit) = self.limits.daily_usd {
+            let spent = spent(None).await?;
+            if spent >= limit {
+                return Err(CompletionError::BudgetExceeded(format!(
+                    "${:.4} spent today of the ${:.2} daily budget",
+                    spent, limit
+                )));
+            }
+        }
+        if let Some(&limit) = self.limits.per_feature_usd.get(&feature) {
+            let spent = spent(Some(feature)).await?;
+            if spent >= limit {
+                return Err(CompletionError::BudgetExceeded(format!(
+                    "${:.4} spent on {} today of its ${:.2} daily budget",
+                    spent, feature, limit
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn record(
+        &self,
+        model: &str,
+        feature: Feature,
+        character: &str,
+        usage: Usage,
+        latency_ms: u64,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .record_usage(UsageEntry {
+                model: model.to_string(),
+                feature: feature.as_str().to_string(),
+                character: character.to_string(),
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                latency_ms,
+                cost_usd: cost_usd(model, &usage),
+            })
+            .await
+    }
+
+    /// Usage over the last `days` days, today included.
+    pub async fn report(&self, days: u32) -> Result<UsageReport, DatabaseError> {
+        let by_day = self.db.usage_totals(UsageGroup::Day, days).await?;
+        let by_character = self.db.usage_totals(UsageGroup::Character, days).await?;
+        let by_feature = self.db.usage_totals(UsageGroup::Feature, days).await?;
+
+        Ok(UsageReport {
+            days,
+            requests: by_day.iter().map(|t| t.requests).sum(),
+            prompt_tokens: by_day.iter().map(|t| t.prompt_tokens).sum(),
+            completion_tokens: by_day.iter().map(|t| t.completion_tokens).sum(),
+            cost_usd: by_day.iter().map(|t| t.cost_usd).sum(),
+            spent_today_usd: self.db.spend_today(None).await?,
+            daily_budget_usd: self.limits.daily_usd,
+            by_day,
+            by_character,
+            by_feature,
+        })
+    }
+}
+
+/// A provider decorator that enforces the ledger's budgets before each call
+/// and records model, tokens, latency and cost after it.
+///
+/// Recording failures are logged rather than failing the completion.
+pub struct MeteredProvider {
+    inner: Arc<dyn CompletionProvider>,
+    ledger: UsageLedger,
+    feature: Feature,
+    character: String,
+}
+
+impl MeteredProvider {
+    async fn metered(
+        &self,
+        params: &GenerationParams,
+        call: impl Future<Output = Result<Completion, CompletionError>>,
+    ) -> Result<Completion, CompletionError> {
+        self.ledger.check_budget(self.feature).await?;
+
+        let started = Instant::now();
+        let completion = call.await?;
+        self.record(params.model_or(self.inner.model()), completion.usage, started).await;
+        Ok(completion)
+    }
+
+    /// Like `metered`, for a streamed reply: recorded once the stream ends,
+    /// estimating the counts if the provider never reported usage.
+    async fn metered_stream(
+        &self,
+        messages: &[ChatMessage],
+        params: &GenerationParams,
+        call: impl Future<Output = Result<CompletionStream, CompletionError>>,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.ledger.check_budget(self.feature).await?;
+
+        let started = Instant::now();
+        let deltas = call.await?;
+
+        // Tally the stream as it passes through
+        let tally = Arc::new(Mutex::new((String::new(), None::<Usage>)));
+        let observed = {
+            let tally = tally.clone();
+            deltas.inspect(move |event| {
+                let mut tally = tally.lock().unwrap();
+                match event {
+                    Ok(StreamEvent::Delta(text)) => tally.0.push_str(text),
+                    Ok(StreamEvent::Usage(usage)) => tally.1 = Some(*usage),
+                    Ok(StreamEvent::ToolCalls(_)) | Err(_) => {}
+                }
+            })
+        };
+
+        let ledger = self.ledger.clone();
+        let model = params.model_or(self.inner.model()).to_string();
+        let (feature, character) = (self.feature, self.character.clone());
+        let prompt = with_system_message(self.inner.system_message(), messages);
+        let finish = stream::once(async move {
+            let (response, usage) = std::mem::take(&mut *tally.lock().unwrap());
+            let usage = usage.unwrap_or_else(|| Usage::estimate(&prompt, &response));
+            let latency_ms = started.elapsed().as_millis() as u64;
+            if let Err(e) = ledger.record(&model, feature, &character, usage, latency_ms).await {
+                eprintln!("Warning: Failed to record usage: {}", e);
+            }
+        })
+        .filter_map(|_| async { None });
+
+        Ok(Box::pin(observed.chain(finish)))
+    }
+
+    async fn record(&self, model: &str, usage: Usage, started: Instant) {
+        let latency_ms = started.elapsed().as_millis() as u64;
+        if let Err(e) = self.ledger.record(model, self.feature, &self.character, usage, latency_ms).await {
+            eprintln!("Warning: Failed to record usage: {}", e);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CompletionProvider for MeteredProvider {
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn system_message(&self) -> &str {
+        self.inner.system_message()
+    }
+
+    fn params(&self) -> GenerationParams {
+        self.inner.params()
+    }
+
+    fn context_window(&self) -> usize {
+        self.inner.context_window()
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.metered(params, self.inner.chat(messages, params)).await
+    }
+
+    async fn chat_json(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<Completion, CompletionError> {
+        self.metered(params, self.inner.chat_json(messages, params)).await
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.inner.supports_tools()
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<Completion, CompletionError> {
+        self.metered(params, self.inner.chat_with_tools(messages, tools, params)).await
+    }
+
+    async fn chat_stream(&self, messages: &[ChatMessage], params: &GenerationParams) -> Result<CompletionStream, CompletionError> {
+        self.metered_stream(messages, params, self.inner.chat_stream(messages, params)).await
+    }
+
+    async fn chat_with_tools_stream(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+        params: &GenerationParams,
+    ) -> Result<CompletionStream, CompletionError> {
+        self.metered_stream(messages, params, self.inner.chat_with_tools_stream(messages, tools, params)).await
+    }
+}
@@ -0,0 +1,96 @@
+use super::*;
+use crate::providers::mock::mock::MockProvider;
+
+async fn ledger(limits: BudgetLimits) -> UsageLedger {
+    UsageLedger::new(Database::new(":memory:").await.unwrap(), limits)
+}
+
+fn deepseek(response: &str) -> Arc<dyn CompletionProvider> {
+    Arc::new(MockProvider::always(response).with_model("deepseek-chat"))
+}
+
+#[test]
+fn test_cost_usd_uses_longest_matching_prefix() {
+    let usage = Usage::new(1_000_000, 1_000_000);
+    assert!((cost_usd("deepseek-chat", &usage) - 1.37).abs() < 1e-9);
+    assert!((cost_usd("gpt-4o-mini-2024-07-18", &usage) - 0.75).abs() < 1e-9);
+    assert!((cost_usd("gpt-4o", &usage) - 12.5).abs() < 1e-9);
+    assert_eq!(cost_usd("llama3", &usage), 0.0);
+}
+
+#[tokio::test]
+async fn test_metered_chat_records_usage_and_cost() {
+    let ledger = ledger(BudgetLimits::default()).await;
+    let provider = ledger.meter(deepseek("Hello there"), Feature::Chat, "Nova");
+
+    let completion = provider.complete_chat(&[ChatMessage::user("Hi")]).await.unwrap();
+    let report = ledger.report(7).await.unwrap();
+
+    assert_eq!(report.requests, 1);
+    assert_eq!(report.prompt_tokens, completion.usage.prompt_tokens as u64);
+    assert_eq!(report.completion_tokens, completion.usage.completion_tokens as u64);
+    assert!((report.cost_usd - cost_usd("deepseek-chat", &completion.usage)).abs() < 1e-12);
+    assert_eq!(report.by_character[0].key, "Nova");
+    assert_eq!(report.by_feature[0].key, "chat");
+}
+
+#[tokio::test]
+async fn test_metered_stream_is_recorded_when_it_ends() {
+    let ledger = ledger(BudgetLimits::default()).await;
+    let provider = ledger.meter(deepseek("Streamed reply"), Feature::Web, "Nova");
+
+    let events: Vec<_> = provider
+        .complete_stream(&[ChatMessage::user("Hi")])
+        .await
+        .unwrap()
+        .collect()
+        .await;
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].as_ref().unwrap().as_delta(), Some("Streamed reply"));
+    let report = ledger.report(1).await.unwrap();
+    assert_eq!(report.requests, 1);
+    assert_eq!(report.by_feature[0].key, "web");
+}
+
+#[tokio::test]
+async fn test_daily_budget_refuses_calls_once_spent() {
+    let ledger = ledger(BudgetLimits::default().with_daily(0.000001)).await;
+    let provider = ledger.meter(deepseek("Hello there"), Feature::Chat, "Nova");
+
+    provider.complete_chat(&[ChatMessage::user("Hi")]).await.unwrap();
+    let err = provider.complete_chat(&[ChatMessage::user("Hi again")]).await.unwrap_err();
+
+    assert!(matches!(err, CompletionError::BudgetExceeded(_)));
+    assert_eq!(ledger.report(1).await.unwrap().requests, 1);
+}
+
+#[tokio::test]
+async fn test_feature_budget_only_limits_that_feature() {
+    let ledger = ledger(BudgetLimits::default().with_feature(Feature::Tweet, 0.000001)).await;
+    let tweets = ledger.meter(deepseek("A tweet"), Feature::Tweet, "Nova");
+    let chat = ledger.meter(deepseek("A reply"), Feature::Chat, "Nova");
+
+    tweets.complete_chat(&[ChatMessage::user("Tweet")]).await.unwrap();
+    let err = tweets.complete_chat(&[ChatMessage::user("Tweet")]).await.unwrap_err();
+    assert!(matches!(err, CompletionError::BudgetExceeded(_)));
+
+    assert!(chat.complete_chat(&[ChatMessage::user("Hi")]).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_report_groups_by_character_and_feature() {
+    let ledger = ledger(BudgetLimits::default()).await;
+    ledger.record("deepseek-chat", Feature::Chat, "Nova", Usage::new(100, 10), 5).await.unwrap();
+    ledger.record("deepseek-chat", Feature::Chat, "Nova", Usage::new(100, 10), 5).await.unwrap();
+    ledger.record("llama3", Feature::Doc, "Sage", Usage::new(50, 5), 5).await.unwrap();
+
+    let report = ledger.report(7).await.unwrap();
+
+    assert_eq!(report.requests, 3);
+    assert_eq!(report.by_day.len(), 1);
+    let nova = report.by_character.iter().find(|t| t.key == "Nova").unwrap();
+    assert_eq!((nova.requests, nova.prompt_tokens, nova.completion_tokens), (2, 200, 20));
+    let doc = report.by_feature.iter().find(|t| t.key == "doc").unwrap();
+    assert_eq!((doc.requests, doc.cost_usd), (1, 0.0));
+}